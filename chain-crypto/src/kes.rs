@@ -1,6 +1,13 @@
-use crate::sign::VerificationAlgorithm;
+use crate::sign::{Signature, VerificationAlgorithm};
 
 pub trait KeyEvolvingSignatureAlgorithm: VerificationAlgorithm {
     /// Get the period associated with this signature
     fn get_period(sig: &Self::Signature) -> u32;
 }
+
+impl<T: ?Sized, A: KeyEvolvingSignatureAlgorithm> Signature<T, A> {
+    /// Get the period of the key that produced this signature
+    pub fn get_period(&self) -> u32 {
+        A::get_period(&self.signdata)
+    }
+}
@@ -54,7 +54,7 @@ where
 }
 
 pub struct Signature<T: ?Sized, A: VerificationAlgorithm> {
-    signdata: A::Signature,
+    pub(crate) signdata: A::Signature,
     phantom: PhantomData<T>,
 }
 
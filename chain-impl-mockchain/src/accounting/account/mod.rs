@@ -9,7 +9,7 @@ pub mod last_rewards;
 
 use crate::date::Epoch;
 use crate::value::*;
-use imhamt::{Hamt, InsertError, UpdateError};
+use imhamt::{Diff, Hamt, InsertError, UpdateError};
 use std::collections::hash_map::DefaultHasher;
 use std::fmt::{self, Debug};
 use std::hash::Hash;
@@ -104,6 +104,24 @@ impl<ID: Clone + Eq + Hash, Extra: Clone> Ledger<ID, Extra> {
         self.0.lookup(account).ok_or(LedgerError::NonExistent)
     }
 
+    /// Set the state of an account, creating the account if it does not exist
+    pub(crate) fn set_state(&self, identifier: ID, state: AccountState<Extra>) -> Self {
+        Ledger(
+            self.0
+                .insert_or_update_simple(identifier, state.clone(), |_| Some(state)),
+        )
+    }
+
+    /// Remove an account from this ledger whatever its value
+    ///
+    /// If the identifier does not match any account, error out
+    pub(crate) fn remove_state(&self, identifier: &ID) -> Result<Self, LedgerError> {
+        self.0
+            .remove(identifier)
+            .map(Ledger)
+            .map_err(|_| LedgerError::NonExistent)
+    }
+
     /// Remove an account from this ledger
     ///
     /// If the account still have value > 0, then error
@@ -197,6 +215,14 @@ impl<ID: Clone + Eq + Hash, Extra: Clone> Ledger<ID, Extra> {
     }
 }
 
+impl<ID: Clone + Eq + Hash, Extra: Clone + PartialEq> Ledger<ID, Extra> {
+    /// Compute the accounts added, removed and changed between this ledger
+    /// and a newer one.
+    pub fn diff<'a>(&'a self, new: &'a Self) -> Vec<Diff<'a, ID, AccountState<Extra>>> {
+        self.0.diff(&new.0)
+    }
+}
+
 impl<ID: Clone + Eq + Hash + Debug, Extra: Clone + Debug> Debug for Ledger<ID, Extra> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -1,4 +1,5 @@
 mod delegation;
+mod multisig;
 mod pool;
 
 #[cfg(any(test, feature = "property-test-api"))]
@@ -7,6 +8,7 @@ mod test;
 use crate::transaction::{Payload, PayloadData, PayloadSlice};
 
 pub use delegation::{OwnerStakeDelegation, StakeDelegation};
+pub use multisig::MultisigDeclaration;
 pub use pool::{
     GenesisPraosLeaderHash, IndexSignatures, ManagementThreshold, PoolId, PoolOwnersSigned,
     PoolPermissions, PoolRegistration, PoolRegistrationHash, PoolRetirement, PoolSignature,
@@ -19,6 +21,7 @@ pub enum CertificateSlice<'a> {
     PoolRegistration(PayloadSlice<'a, PoolRegistration>),
     PoolRetirement(PayloadSlice<'a, PoolRetirement>),
     PoolUpdate(PayloadSlice<'a, PoolUpdate>),
+    MultisigDeclaration(PayloadSlice<'a, MultisigDeclaration>),
 }
 
 impl<'a> From<PayloadSlice<'a, StakeDelegation>> for CertificateSlice<'a> {
@@ -50,6 +53,12 @@ impl<'a> From<PayloadSlice<'a, PoolUpdate>> for CertificateSlice<'a> {
     }
 }
 
+impl<'a> From<PayloadSlice<'a, MultisigDeclaration>> for CertificateSlice<'a> {
+    fn from(payload: PayloadSlice<'a, MultisigDeclaration>) -> CertificateSlice<'a> {
+        CertificateSlice::MultisigDeclaration(payload)
+    }
+}
+
 impl<'a> CertificateSlice<'a> {
     pub fn into_owned(self) -> Certificate {
         match self {
@@ -62,6 +71,9 @@ impl<'a> CertificateSlice<'a> {
             CertificateSlice::OwnerStakeDelegation(c) => {
                 Certificate::OwnerStakeDelegation(c.into_payload())
             }
+            CertificateSlice::MultisigDeclaration(c) => {
+                Certificate::MultisigDeclaration(c.into_payload())
+            }
         }
     }
 }
@@ -73,6 +85,7 @@ pub enum CertificatePayload {
     PoolRegistration(PayloadData<PoolRegistration>),
     PoolRetirement(PayloadData<PoolRetirement>),
     PoolUpdate(PayloadData<PoolUpdate>),
+    MultisigDeclaration(PayloadData<MultisigDeclaration>),
 }
 
 impl CertificatePayload {
@@ -83,6 +96,7 @@ impl CertificatePayload {
             CertificatePayload::PoolRegistration(payload) => payload.borrow().into(),
             CertificatePayload::PoolRetirement(payload) => payload.borrow().into(),
             CertificatePayload::PoolUpdate(payload) => payload.borrow().into(),
+            CertificatePayload::MultisigDeclaration(payload) => payload.borrow().into(),
         }
     }
 }
@@ -105,6 +119,9 @@ impl<'a> From<&'a Certificate> for CertificatePayload {
             Certificate::PoolUpdate(payload) => {
                 CertificatePayload::PoolUpdate(payload.payload_data())
             }
+            Certificate::MultisigDeclaration(payload) => {
+                CertificatePayload::MultisigDeclaration(payload.payload_data())
+            }
         }
     }
 }
@@ -116,6 +133,7 @@ pub enum Certificate {
     PoolRegistration(PoolRegistration),
     PoolRetirement(PoolRetirement),
     PoolUpdate(PoolUpdate),
+    MultisigDeclaration(MultisigDeclaration),
 }
 
 impl From<StakeDelegation> for Certificate {
@@ -148,6 +166,12 @@ impl From<PoolUpdate> for Certificate {
     }
 }
 
+impl From<MultisigDeclaration> for Certificate {
+    fn from(cert: MultisigDeclaration) -> Certificate {
+        Certificate::MultisigDeclaration(cert)
+    }
+}
+
 impl Certificate {
     pub fn need_auth(&self) -> bool {
         match self {
@@ -156,6 +180,7 @@ impl Certificate {
             Certificate::PoolRetirement(_) => <PoolRetirement as Payload>::HAS_AUTH,
             Certificate::StakeDelegation(_) => <StakeDelegation as Payload>::HAS_AUTH,
             Certificate::OwnerStakeDelegation(_) => <OwnerStakeDelegation as Payload>::HAS_AUTH,
+            Certificate::MultisigDeclaration(_) => <MultisigDeclaration as Payload>::HAS_AUTH,
         }
     }
 }
@@ -170,6 +195,7 @@ pub enum SignedCertificate {
     PoolRegistration(PoolRegistration, <PoolRegistration as Payload>::Auth),
     PoolRetirement(PoolRetirement, <PoolRetirement as Payload>::Auth),
     PoolUpdate(PoolUpdate, <PoolUpdate as Payload>::Auth),
+    MultisigDeclaration(MultisigDeclaration, <MultisigDeclaration as Payload>::Auth),
 }
 
 #[cfg(test)]
@@ -186,6 +212,7 @@ mod tests {
             Certificate::PoolRetirement(_) => true,
             Certificate::StakeDelegation(_) => true,
             Certificate::OwnerStakeDelegation(_) => false,
+            Certificate::MultisigDeclaration(_) => false,
         };
         TestResult::from_bool(certificate.need_auth() == expected_result)
     }
@@ -0,0 +1,63 @@
+use crate::certificate::CertificateSlice;
+use crate::multisig::Declaration;
+use crate::transaction::{Payload, PayloadAuthData, PayloadData, PayloadSlice};
+
+use chain_core::{
+    mempack::{ReadBuf, ReadError, Readable},
+    property,
+};
+use std::marker::PhantomData;
+use typed_bytes::{ByteArray, ByteBuilder};
+
+/// Register a multisig account on the chain, from its declaration.
+///
+/// The account identifier is derived from the declaration, so anyone knowing the declaration
+/// can register it, and no authentication is required.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultisigDeclaration {
+    pub declaration: Declaration,
+}
+
+impl MultisigDeclaration {
+    pub fn serialize_in(&self, bb: ByteBuilder<Self>) -> ByteBuilder<Self> {
+        bb.sub(|sb| self.declaration.serialize_in(sb))
+    }
+
+    pub fn serialize(&self) -> ByteArray<Self> {
+        self.serialize_in(ByteBuilder::new()).finalize()
+    }
+}
+
+impl property::Serialize for MultisigDeclaration {
+    type Error = std::io::Error;
+    fn serialize<W: std::io::Write>(&self, mut writer: W) -> Result<(), Self::Error> {
+        writer.write_all(self.serialize().as_slice())
+    }
+}
+
+impl Readable for MultisigDeclaration {
+    fn read<'a>(buf: &mut ReadBuf<'a>) -> Result<Self, ReadError> {
+        let declaration = Declaration::read(buf)?;
+        Ok(MultisigDeclaration { declaration })
+    }
+}
+
+impl Payload for MultisigDeclaration {
+    const HAS_DATA: bool = true;
+    const HAS_AUTH: bool = false;
+    type Auth = ();
+    fn payload_data(&self) -> PayloadData<Self> {
+        PayloadData(
+            self.serialize_in(ByteBuilder::new())
+                .finalize_as_vec()
+                .into(),
+            PhantomData,
+        )
+    }
+    fn payload_auth_data(_: &Self::Auth) -> PayloadAuthData<Self> {
+        PayloadAuthData(Vec::with_capacity(0).into(), PhantomData)
+    }
+    fn to_certificate_slice<'a>(p: PayloadSlice<'a, Self>) -> Option<CertificateSlice<'a>> {
+        Some(CertificateSlice::from(p))
+    }
+}
@@ -87,6 +87,14 @@ impl Arbitrary for OwnerStakeDelegation {
     }
 }
 
+impl Arbitrary for MultisigDeclaration {
+    fn arbitrary<G: Gen>(g: &mut G) -> Self {
+        Self {
+            declaration: Arbitrary::arbitrary(g),
+        }
+    }
+}
+
 impl Arbitrary for PoolRegistration {
     fn arbitrary<G: Gen>(g: &mut G) -> Self {
         let start_validity: DurationSeconds = u64::arbitrary(g).into();
@@ -122,13 +130,14 @@ impl Arbitrary for PoolRegistration {
 
 impl Arbitrary for Certificate {
     fn arbitrary<G: Gen>(g: &mut G) -> Self {
-        let option = u8::arbitrary(g) % 5;
+        let option = u8::arbitrary(g) % 6;
         match option {
             0 => Certificate::StakeDelegation(Arbitrary::arbitrary(g)),
             1 => Certificate::OwnerStakeDelegation(Arbitrary::arbitrary(g)),
             2 => Certificate::PoolRegistration(Arbitrary::arbitrary(g)),
             3 => Certificate::PoolRetirement(Arbitrary::arbitrary(g)),
             4 => Certificate::PoolUpdate(Arbitrary::arbitrary(g)),
+            5 => Certificate::MultisigDeclaration(Arbitrary::arbitrary(g)),
             _ => panic!("unimplemented"),
         }
     }
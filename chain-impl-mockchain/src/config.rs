@@ -5,7 +5,7 @@ use crate::rewards::{Ratio, TaxType};
 use crate::value::Value;
 use crate::{
     chaintypes::ConsensusType,
-    fee::{LinearFee, PerCertificateFee},
+    fee::{LinearFee, PerCertificateFee, PerExtendedCertificateFee},
 };
 use chain_addr::Discrimination;
 use chain_core::mempack::{ReadBuf, ReadError, Readable};
@@ -62,6 +62,7 @@ pub enum ConfigParam {
     SlotsPerEpoch(u32),
     SlotDuration(u8),
     EpochStabilityDepth(u32),
+    BftSlotsRatio(Milli),
     ConsensusGenesisPraosActiveSlotsCoeff(Milli),
     BlockContentMaxSize(u32),
     AddBftLeader(BftLeaderId),
@@ -78,6 +79,16 @@ pub enum ConfigParam {
     RewardLimitNone,
     RewardLimitByAbsoluteStake(Ratio),
     PoolRewardParticipationCapping((NonZeroU32, NonZeroU32)),
+    RewardsHistoryRetention(u32),
+    PoolRewardSaturation(Option<NonZeroU32>),
+    PoolRewardPerformance(bool),
+    StakeVotingQuorum(Ratio),
+    StakeVotingThreshold(Ratio),
+    MetadataMaxSize(u32),
+    PerMetadataByteFee(u64),
+    PerFragmentByteFee(u64),
+    PoolDeposit(Value),
+    PerExtendedCertificateFees(PerExtendedCertificateFee),
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -97,7 +108,7 @@ pub enum RewardParams {
 }
 
 // Discriminants can NEVER be 1024 or higher
-#[derive(AsRefStr, Clone, Copy, Debug, EnumIter, EnumString, PartialEq)]
+#[derive(AsRefStr, Clone, Copy, Debug, EnumIter, EnumString, PartialEq, Eq)]
 pub enum Tag {
     #[strum(to_string = "discrimination")]
     Discrimination = 1,
@@ -111,6 +122,8 @@ pub enum Tag {
     SlotDuration = 5,
     #[strum(to_string = "epoch-stability-depth")]
     EpochStabilityDepth = 6,
+    #[strum(to_string = "bft-slots-ratio")]
+    BftSlotsRatio = 7,
     #[strum(to_string = "genesis-praos-param-f")]
     ConsensusGenesisPraosActiveSlotsCoeff = 8,
     #[strum(to_string = "block-content-max-size")]
@@ -143,9 +156,44 @@ pub enum Tag {
     RewardLimitByAbsoluteStake = 24,
     #[strum(to_string = "pool-reward-participation-capping")]
     PoolRewardParticipationCapping = 25,
+    #[strum(to_string = "rewards-history-retention")]
+    RewardsHistoryRetention = 26,
+    #[strum(to_string = "pool-reward-saturation")]
+    PoolRewardSaturation = 27,
+    #[strum(to_string = "pool-reward-performance")]
+    PoolRewardPerformance = 28,
+    #[strum(to_string = "stake-voting-quorum")]
+    StakeVotingQuorum = 29,
+    #[strum(to_string = "stake-voting-threshold")]
+    StakeVotingThreshold = 30,
+    #[strum(to_string = "metadata-max-size")]
+    MetadataMaxSize = 31,
+    #[strum(to_string = "per-metadata-byte-fee")]
+    PerMetadataByteFee = 32,
+    #[strum(to_string = "per-fragment-byte-fee")]
+    PerFragmentByteFee = 33,
+    #[strum(to_string = "pool-deposit")]
+    PoolDeposit = 34,
+    #[strum(to_string = "per-extended-certificate-fees")]
+    PerExtendedCertificateFees = 35,
 }
 
+/// The parameters which can only be set in the block0. An update proposal
+/// trying to change any of them is rejected.
+pub const IMMUTABLE_PARAMS: [Tag; 5] = [
+    Tag::Discrimination,
+    Tag::Block0Date,
+    Tag::KESUpdateSpeed,
+    Tag::TreasuryAdd,
+    Tag::RewardPot,
+];
+
 impl Tag {
+    /// Check if the parameter can only be set in the block0
+    pub fn is_immutable(self) -> bool {
+        IMMUTABLE_PARAMS.contains(&self)
+    }
+
     pub fn from_u16(v: u16) -> Option<Self> {
         match v {
             1 => Some(Tag::Discrimination),
@@ -154,6 +202,7 @@ impl Tag {
             4 => Some(Tag::SlotsPerEpoch),
             5 => Some(Tag::SlotDuration),
             6 => Some(Tag::EpochStabilityDepth),
+            7 => Some(Tag::BftSlotsRatio),
             8 => Some(Tag::ConsensusGenesisPraosActiveSlotsCoeff),
             9 => Some(Tag::BlockContentMaxSize),
             11 => Some(Tag::AddBftLeader),
@@ -170,6 +219,16 @@ impl Tag {
             23 => Some(Tag::RewardLimitNone),
             24 => Some(Tag::RewardLimitByAbsoluteStake),
             25 => Some(Tag::PoolRewardParticipationCapping),
+            26 => Some(Tag::RewardsHistoryRetention),
+            27 => Some(Tag::PoolRewardSaturation),
+            28 => Some(Tag::PoolRewardPerformance),
+            29 => Some(Tag::StakeVotingQuorum),
+            30 => Some(Tag::StakeVotingThreshold),
+            31 => Some(Tag::MetadataMaxSize),
+            32 => Some(Tag::PerMetadataByteFee),
+            33 => Some(Tag::PerFragmentByteFee),
+            34 => Some(Tag::PoolDeposit),
+            35 => Some(Tag::PerExtendedCertificateFees),
             _ => None,
         }
     }
@@ -184,6 +243,7 @@ impl<'a> From<&'a ConfigParam> for Tag {
             ConfigParam::SlotsPerEpoch(_) => Tag::SlotsPerEpoch,
             ConfigParam::SlotDuration(_) => Tag::SlotDuration,
             ConfigParam::EpochStabilityDepth(_) => Tag::EpochStabilityDepth,
+            ConfigParam::BftSlotsRatio(_) => Tag::BftSlotsRatio,
             ConfigParam::ConsensusGenesisPraosActiveSlotsCoeff(_) => {
                 Tag::ConsensusGenesisPraosActiveSlotsCoeff
             }
@@ -202,6 +262,16 @@ impl<'a> From<&'a ConfigParam> for Tag {
             ConfigParam::RewardLimitNone => Tag::RewardLimitNone,
             ConfigParam::RewardLimitByAbsoluteStake(_) => Tag::RewardLimitByAbsoluteStake,
             ConfigParam::PoolRewardParticipationCapping(..) => Tag::PoolRewardParticipationCapping,
+            ConfigParam::RewardsHistoryRetention(_) => Tag::RewardsHistoryRetention,
+            ConfigParam::PoolRewardSaturation(_) => Tag::PoolRewardSaturation,
+            ConfigParam::PoolRewardPerformance(_) => Tag::PoolRewardPerformance,
+            ConfigParam::StakeVotingQuorum(_) => Tag::StakeVotingQuorum,
+            ConfigParam::StakeVotingThreshold(_) => Tag::StakeVotingThreshold,
+            ConfigParam::MetadataMaxSize(_) => Tag::MetadataMaxSize,
+            ConfigParam::PerMetadataByteFee(_) => Tag::PerMetadataByteFee,
+            ConfigParam::PerFragmentByteFee(_) => Tag::PerFragmentByteFee,
+            ConfigParam::PoolDeposit(_) => Tag::PoolDeposit,
+            ConfigParam::PerExtendedCertificateFees(_) => Tag::PerExtendedCertificateFees,
         }
     }
 }
@@ -227,6 +297,9 @@ impl Readable for ConfigParam {
             Tag::EpochStabilityDepth => {
                 ConfigParamVariant::from_payload(bytes).map(ConfigParam::EpochStabilityDepth)
             }
+            Tag::BftSlotsRatio => {
+                ConfigParamVariant::from_payload(bytes).map(ConfigParam::BftSlotsRatio)
+            }
             Tag::ConsensusGenesisPraosActiveSlotsCoeff => ConfigParamVariant::from_payload(bytes)
                 .map(ConfigParam::ConsensusGenesisPraosActiveSlotsCoeff),
             Tag::BlockContentMaxSize => {
@@ -273,6 +346,36 @@ impl Readable for ConfigParam {
             }
             Tag::PoolRewardParticipationCapping => ConfigParamVariant::from_payload(bytes)
                 .map(ConfigParam::PoolRewardParticipationCapping),
+            Tag::RewardsHistoryRetention => {
+                ConfigParamVariant::from_payload(bytes).map(ConfigParam::RewardsHistoryRetention)
+            }
+            Tag::PoolRewardSaturation => {
+                ConfigParamVariant::from_payload(bytes).map(ConfigParam::PoolRewardSaturation)
+            }
+            Tag::PoolRewardPerformance => {
+                ConfigParamVariant::from_payload(bytes).map(ConfigParam::PoolRewardPerformance)
+            }
+            Tag::StakeVotingQuorum => {
+                ConfigParamVariant::from_payload(bytes).map(ConfigParam::StakeVotingQuorum)
+            }
+            Tag::StakeVotingThreshold => {
+                ConfigParamVariant::from_payload(bytes).map(ConfigParam::StakeVotingThreshold)
+            }
+            Tag::MetadataMaxSize => {
+                ConfigParamVariant::from_payload(bytes).map(ConfigParam::MetadataMaxSize)
+            }
+            Tag::PerMetadataByteFee => {
+                ConfigParamVariant::from_payload(bytes).map(ConfigParam::PerMetadataByteFee)
+            }
+            Tag::PerFragmentByteFee => {
+                ConfigParamVariant::from_payload(bytes).map(ConfigParam::PerFragmentByteFee)
+            }
+            Tag::PoolDeposit => {
+                ConfigParamVariant::from_payload(bytes).map(ConfigParam::PoolDeposit)
+            }
+            Tag::PerExtendedCertificateFees => {
+                ConfigParamVariant::from_payload(bytes).map(ConfigParam::PerExtendedCertificateFees)
+            }
         }
         .map_err(Into::into)
     }
@@ -290,6 +393,7 @@ impl property::Serialize for ConfigParam {
             ConfigParam::SlotsPerEpoch(data) => data.to_payload(),
             ConfigParam::SlotDuration(data) => data.to_payload(),
             ConfigParam::EpochStabilityDepth(data) => data.to_payload(),
+            ConfigParam::BftSlotsRatio(data) => data.to_payload(),
             ConfigParam::ConsensusGenesisPraosActiveSlotsCoeff(data) => data.to_payload(),
             ConfigParam::BlockContentMaxSize(data) => data.to_payload(),
             ConfigParam::AddBftLeader(data) => data.to_payload(),
@@ -306,6 +410,16 @@ impl property::Serialize for ConfigParam {
             ConfigParam::RewardLimitNone => Vec::with_capacity(0),
             ConfigParam::RewardLimitByAbsoluteStake(data) => data.to_payload(),
             ConfigParam::PoolRewardParticipationCapping(data) => data.to_payload(),
+            ConfigParam::RewardsHistoryRetention(data) => data.to_payload(),
+            ConfigParam::PoolRewardSaturation(data) => data.to_payload(),
+            ConfigParam::PoolRewardPerformance(data) => data.to_payload(),
+            ConfigParam::StakeVotingQuorum(data) => data.to_payload(),
+            ConfigParam::StakeVotingThreshold(data) => data.to_payload(),
+            ConfigParam::MetadataMaxSize(data) => data.to_payload(),
+            ConfigParam::PerMetadataByteFee(data) => data.to_payload(),
+            ConfigParam::PerFragmentByteFee(data) => data.to_payload(),
+            ConfigParam::PoolDeposit(data) => data.to_payload(),
+            ConfigParam::PerExtendedCertificateFees(data) => data.to_payload(),
         };
         let taglen = TagLen::new(tag, bytes.len()).ok_or_else(|| {
             io::Error::new(
@@ -591,6 +705,30 @@ impl ConfigParamVariant for u32 {
     }
 }
 
+impl ConfigParamVariant for NonZeroU32 {
+    fn to_payload(&self) -> Vec<u8> {
+        self.get().to_payload()
+    }
+
+    fn from_payload(payload: &[u8]) -> Result<Self, Error> {
+        let mut rb = ReadBuf::from(payload);
+        let x = rb.get_nz_u32()?;
+        rb.expect_end()?;
+        Ok(x)
+    }
+}
+
+/// Zero stands for `None`
+impl ConfigParamVariant for Option<NonZeroU32> {
+    fn to_payload(&self) -> Vec<u8> {
+        self.map_or(0, |v| v.get()).to_payload()
+    }
+
+    fn from_payload(payload: &[u8]) -> Result<Self, Error> {
+        u32::from_payload(payload).map(NonZeroU32::new)
+    }
+}
+
 impl ConfigParamVariant for (NonZeroU32, NonZeroU32) {
     fn to_payload(&self) -> Vec<u8> {
         let bb: ByteBuilder<()> = ByteBuilder::new();
@@ -632,6 +770,9 @@ impl ConfigParamVariant for LinearFee {
             coefficient: u64::from_payload(&payload[8..16])?,
             certificate: u64::from_payload(&payload[16..24])?,
             per_certificate_fees: PerCertificateFee::default(),
+            per_extended_certificate_fees: PerExtendedCertificateFee::default(),
+            per_metadata_byte: 0,
+            per_fragment_byte: 0,
         })
     }
 }
@@ -672,6 +813,30 @@ impl ConfigParamVariant for PerCertificateFee {
     }
 }
 
+// Format is:
+// POOL_RETIREMENT (u64) POOL_UPDATE (u64) MULTISIG_DECLARATION (u64)
+// with 0 for an unset fee.
+impl ConfigParamVariant for PerExtendedCertificateFee {
+    fn to_payload(&self) -> Vec<u8> {
+        let bb: ByteBuilder<()> = ByteBuilder::new();
+        bb.u64(self.certificate_pool_retirement.map_or(0, |v| v.get()))
+            .u64(self.certificate_pool_update.map_or(0, |v| v.get()))
+            .u64(self.certificate_multisig_declaration.map_or(0, |v| v.get()))
+            .finalize_as_vec()
+    }
+
+    fn from_payload(payload: &[u8]) -> Result<Self, Error> {
+        let mut rb = ReadBuf::from(payload);
+        let fee = PerExtendedCertificateFee {
+            certificate_pool_retirement: NonZeroU64::new(rb.get_u64()?),
+            certificate_pool_update: NonZeroU64::new(rb.get_u64()?),
+            certificate_multisig_declaration: NonZeroU64::new(rb.get_u64()?),
+        };
+        rb.expect_end()?;
+        Ok(fee)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 struct TagLen(u16);
 
@@ -724,6 +889,38 @@ mod test {
 
             TestResult::from_bool(fee == decoded)
         }
+
+        fn per_extended_certificate_fee_to_payload_from_payload(fee: PerExtendedCertificateFee) -> TestResult {
+            let payload = fee.to_payload();
+            let decoded = PerExtendedCertificateFee::from_payload(&payload).unwrap();
+
+            TestResult::from_bool(fee == decoded)
+        }
+    }
+
+    #[test]
+    fn pool_reward_saturation_encodes_none_as_zero() {
+        let none = ConfigParam::PoolRewardSaturation(None);
+        let bytes = property::Serialize::serialize_as_vec(&none).unwrap();
+        assert_eq!(ConfigParam::read(&mut ReadBuf::from(&bytes)), Ok(none));
+
+        let target = NonZeroU32::new(100).unwrap();
+        assert_eq!(
+            Option::<NonZeroU32>::from_payload(&target.to_payload()),
+            Ok(Some(target))
+        );
+    }
+
+    #[test]
+    fn per_extended_certificate_fee_with_max_fees_fits_in_a_parameter() {
+        let max = NonZeroU64::new(u64::MAX);
+        let mut fee = PerExtendedCertificateFee::default();
+        fee.pool_retirement(max);
+        fee.pool_update(max);
+        fee.multisig_declaration(max);
+        let param = ConfigParam::PerExtendedCertificateFees(fee);
+        let bytes = property::Serialize::serialize_as_vec(&param).unwrap();
+        assert_eq!(ConfigParam::read(&mut ReadBuf::from(&bytes)), Ok(param));
     }
 
     impl Arbitrary for Tag {
@@ -770,7 +967,7 @@ mod test {
 
     impl Arbitrary for ConfigParam {
         fn arbitrary<G: Gen>(g: &mut G) -> Self {
-            match u8::arbitrary(g) % 16 {
+            match u8::arbitrary(g) % 27 {
                 0 => ConfigParam::Block0Date(Arbitrary::arbitrary(g)),
                 1 => ConfigParam::Discrimination(Arbitrary::arbitrary(g)),
                 2 => ConfigParam::ConsensusVersion(Arbitrary::arbitrary(g)),
@@ -787,6 +984,17 @@ mod test {
                 13 => ConfigParam::RewardParams(Arbitrary::arbitrary(g)),
                 14 => ConfigParam::PerCertificateFees(Arbitrary::arbitrary(g)),
                 15 => ConfigParam::FeesInTreasury(Arbitrary::arbitrary(g)),
+                16 => ConfigParam::RewardsHistoryRetention(Arbitrary::arbitrary(g)),
+                17 => ConfigParam::PoolRewardSaturation(NonZeroU32::new(Arbitrary::arbitrary(g))),
+                18 => ConfigParam::PoolRewardPerformance(Arbitrary::arbitrary(g)),
+                19 => ConfigParam::StakeVotingQuorum(Arbitrary::arbitrary(g)),
+                20 => ConfigParam::StakeVotingThreshold(Arbitrary::arbitrary(g)),
+                21 => ConfigParam::MetadataMaxSize(Arbitrary::arbitrary(g)),
+                22 => ConfigParam::PerMetadataByteFee(Arbitrary::arbitrary(g)),
+                23 => ConfigParam::PerFragmentByteFee(Arbitrary::arbitrary(g)),
+                24 => ConfigParam::PoolDeposit(Arbitrary::arbitrary(g)),
+                25 => ConfigParam::BftSlotsRatio(Milli::from_millis(u64::arbitrary(g) % 1001)),
+                26 => ConfigParam::PerExtendedCertificateFees(Arbitrary::arbitrary(g)),
                 _ => unreachable!(),
             }
         }
@@ -58,6 +58,15 @@ impl From<EpochPosition> for BlockDate {
     }
 }
 
+impl From<BlockDate> for EpochPosition {
+    fn from(date: BlockDate) -> EpochPosition {
+        EpochPosition {
+            epoch: chain_time::Epoch(date.epoch),
+            slot: chain_time::era::EpochSlotOffset(date.slot_id),
+        }
+    }
+}
+
 impl property::BlockDate for BlockDate {
     fn from_epoch_slot_id(epoch: Epoch, slot_id: SlotId) -> Self {
         BlockDate {
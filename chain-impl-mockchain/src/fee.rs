@@ -1,16 +1,23 @@
 use crate::certificate::CertificateSlice;
+use crate::fragment::FRAGMENT_HEADER_SIZE;
 use crate::transaction as tx;
 use crate::value::Value;
 use std::num::NonZeroU64;
 
 /// Linear fee using the basic affine formula
-/// `COEFFICIENT * bytes(COUNT(tx.inputs) + COUNT(tx.outputs)) + CONSTANT + CERTIFICATE*COUNT(certificates)`.
+/// `COEFFICIENT * bytes(COUNT(tx.inputs) + COUNT(tx.outputs)) + CONSTANT + CERTIFICATE*COUNT(certificates)
+///  + PER_METADATA_BYTE * SIZE(metadata) + PER_FRAGMENT_BYTE * SIZE(fragment)`.
+///
+/// The size of the fragment is the size of its serialization, header included.
 #[derive(PartialEq, Eq, PartialOrd, Debug, Clone, Copy)]
 pub struct LinearFee {
     pub constant: u64,
     pub coefficient: u64,
     pub certificate: u64,
     pub per_certificate_fees: PerCertificateFee,
+    pub per_extended_certificate_fees: PerExtendedCertificateFee,
+    pub per_metadata_byte: u64,
+    pub per_fragment_byte: u64,
 }
 
 #[derive(PartialEq, Eq, PartialOrd, Debug, Clone, Copy, Default)]
@@ -20,6 +27,15 @@ pub struct PerCertificateFee {
     pub certificate_owner_stake_delegation: Option<NonZeroU64>,
 }
 
+/// Fees of the certificates which are not part of the original
+/// `PerCertificateFee` table
+#[derive(PartialEq, Eq, PartialOrd, Debug, Clone, Copy, Default)]
+pub struct PerExtendedCertificateFee {
+    pub certificate_pool_retirement: Option<NonZeroU64>,
+    pub certificate_pool_update: Option<NonZeroU64>,
+    pub certificate_multisig_declaration: Option<NonZeroU64>,
+}
+
 impl LinearFee {
     pub fn new(constant: u64, coefficient: u64, certificate: u64) -> Self {
         LinearFee {
@@ -27,12 +43,30 @@ impl LinearFee {
             coefficient,
             certificate,
             per_certificate_fees: PerCertificateFee::default(),
+            per_extended_certificate_fees: PerExtendedCertificateFee::default(),
+            per_metadata_byte: 0,
+            per_fragment_byte: 0,
         }
     }
 
     pub fn per_certificate_fees(&mut self, per_certificate_fees: PerCertificateFee) {
         self.per_certificate_fees = per_certificate_fees;
     }
+
+    pub fn per_extended_certificate_fees(
+        &mut self,
+        per_extended_certificate_fees: PerExtendedCertificateFee,
+    ) {
+        self.per_extended_certificate_fees = per_extended_certificate_fees;
+    }
+
+    pub fn per_metadata_byte(&mut self, per_metadata_byte: u64) {
+        self.per_metadata_byte = per_metadata_byte;
+    }
+
+    pub fn per_fragment_byte(&mut self, per_fragment_byte: u64) {
+        self.per_fragment_byte = per_fragment_byte;
+    }
 }
 
 impl PerCertificateFee {
@@ -48,7 +82,7 @@ impl PerCertificateFee {
         }
     }
 
-    fn fees_for_certificate<'a>(&self, cert: CertificateSlice<'a>) -> Option<Value> {
+    fn fees_for_certificate<'a>(&self, cert: &CertificateSlice<'a>) -> Option<Value> {
         match cert {
             CertificateSlice::PoolRegistration(_) => {
                 self.certificate_pool_registration.map(|v| Value(v.get()))
@@ -64,15 +98,56 @@ impl PerCertificateFee {
     }
 }
 
+impl PerExtendedCertificateFee {
+    pub fn pool_retirement(&mut self, certificate_pool_retirement: Option<NonZeroU64>) {
+        self.certificate_pool_retirement = certificate_pool_retirement;
+    }
+
+    pub fn pool_update(&mut self, certificate_pool_update: Option<NonZeroU64>) {
+        self.certificate_pool_update = certificate_pool_update;
+    }
+
+    pub fn multisig_declaration(&mut self, certificate_multisig_declaration: Option<NonZeroU64>) {
+        self.certificate_multisig_declaration = certificate_multisig_declaration;
+    }
+
+    fn fees_for_certificate<'a>(&self, cert: &CertificateSlice<'a>) -> Option<Value> {
+        match cert {
+            CertificateSlice::PoolRetirement(_) => {
+                self.certificate_pool_retirement.map(|v| Value(v.get()))
+            }
+            CertificateSlice::PoolUpdate(_) => self.certificate_pool_update.map(|v| Value(v.get())),
+            CertificateSlice::MultisigDeclaration(_) => self
+                .certificate_multisig_declaration
+                .map(|v| Value(v.get())),
+            _ => None,
+        }
+    }
+}
+
 pub trait FeeAlgorithm {
     fn baseline(&self) -> Value;
     fn fees_for_inputs_outputs(&self, inputs: u8, outputs: u8) -> Value;
     fn fees_for_certificate<'a>(&self, cert: CertificateSlice<'a>) -> Value;
+    fn fees_for_metadata(&self, size: usize) -> Value;
+    fn fees_for_fragment_size(&self, size: usize) -> Value;
 
-    fn calculate<'a>(&self, cert: Option<CertificateSlice<'a>>, inputs: u8, outputs: u8) -> Value {
+    /// Calculate the fees of a transaction, from its certificate, its number of
+    /// inputs and outputs, the size in bytes of its metadata (zero if it has
+    /// none) and the size in bytes of the fragment holding it.
+    fn calculate<'a>(
+        &self,
+        cert: Option<CertificateSlice<'a>>,
+        inputs: u8,
+        outputs: u8,
+        metadata_size: usize,
+        fragment_size: usize,
+    ) -> Value {
         self.baseline()
             .saturating_add(self.fees_for_inputs_outputs(inputs, outputs))
             .saturating_add(cert.map_or(Value::zero(), |c| self.fees_for_certificate(c)))
+            .saturating_add(self.fees_for_metadata(metadata_size))
+            .saturating_add(self.fees_for_fragment_size(fragment_size))
     }
 
     fn calculate_tx<P: tx::Payload>(&self, tx: &tx::TransactionSlice<P>) -> Value {
@@ -80,6 +155,8 @@ pub trait FeeAlgorithm {
             tx.payload().to_certificate_slice(),
             tx.nb_inputs(),
             tx.nb_outputs(),
+            tx.payload().to_metadata_slice().map_or(0, |m| m.len()),
+            FRAGMENT_HEADER_SIZE + tx.size(),
         )
     }
 }
@@ -98,15 +175,28 @@ impl FeeAlgorithm for LinearFee {
 
     fn fees_for_certificate<'a>(&self, cert_slice: CertificateSlice<'a>) -> Value {
         self.per_certificate_fees
-            .fees_for_certificate(cert_slice)
+            .fees_for_certificate(&cert_slice)
+            .or_else(|| {
+                self.per_extended_certificate_fees
+                    .fees_for_certificate(&cert_slice)
+            })
             .unwrap_or(Value(self.certificate))
     }
+
+    fn fees_for_metadata(&self, size: usize) -> Value {
+        Value(self.per_metadata_byte.saturating_mul(size as u64))
+    }
+
+    fn fees_for_fragment_size(&self, size: usize) -> Value {
+        Value(self.per_fragment_byte.saturating_mul(size as u64))
+    }
 }
 
 #[cfg(any(test, feature = "property-test-api"))]
 mod test {
     use super::*;
     use crate::certificate::{Certificate, CertificatePayload};
+    use crate::fragment::Fragment;
     use quickcheck::{Arbitrary, Gen, TestResult};
     use quickcheck_macros::quickcheck;
 
@@ -120,6 +210,16 @@ mod test {
         }
     }
 
+    impl Arbitrary for PerExtendedCertificateFee {
+        fn arbitrary<G: Gen>(g: &mut G) -> Self {
+            let mut fee = PerExtendedCertificateFee::default();
+            fee.pool_retirement(NonZeroU64::new(u64::arbitrary(g)));
+            fee.pool_update(NonZeroU64::new(u64::arbitrary(g)));
+            fee.multisig_declaration(NonZeroU64::new(u64::arbitrary(g)));
+            fee
+        }
+    }
+
     impl Arbitrary for LinearFee {
         fn arbitrary<G: Gen>(g: &mut G) -> Self {
             Self {
@@ -127,6 +227,9 @@ mod test {
                 coefficient: Arbitrary::arbitrary(g),
                 certificate: Arbitrary::arbitrary(g),
                 per_certificate_fees: PerCertificateFee::new(None, None, None),
+                per_extended_certificate_fees: PerExtendedCertificateFee::default(),
+                per_metadata_byte: 0,
+                per_fragment_byte: 0,
             }
         }
     }
@@ -138,8 +241,10 @@ mod test {
         outputs: u8,
         mut fee: LinearFee,
         per_certificate_fees: PerCertificateFee,
+        per_extended_certificate_fees: PerExtendedCertificateFee,
     ) -> TestResult {
         fee.per_certificate_fees(per_certificate_fees);
+        fee.per_extended_certificate_fees(per_extended_certificate_fees);
         let per_certificate_fees = fee.per_certificate_fees;
         if per_certificate_fees.certificate_pool_registration.is_none()
             || per_certificate_fees.certificate_stake_delegation.is_none()
@@ -151,7 +256,7 @@ mod test {
         }
 
         let certificate_payload: CertificatePayload = (&certificate).into();
-        let fee_value = fee.calculate(Some(certificate_payload.as_slice()), inputs, outputs);
+        let fee_value = fee.calculate(Some(certificate_payload.as_slice()), inputs, outputs, 0, 0);
         let inputs_outputs_fee: u64 = (inputs + outputs) as u64 * fee.coefficient;
         let expected_value = Value(
             calculate_expected_cert_fee_value(&certificate, &fee)
@@ -177,7 +282,51 @@ mod test {
             Certificate::OwnerStakeDelegation { .. } => {
                 cert_fees.certificate_owner_stake_delegation.unwrap().into()
             }
-            _ => fee.certificate,
+            Certificate::MultisigDeclaration { .. } => fee
+                .per_extended_certificate_fees
+                .certificate_multisig_declaration
+                .map_or(fee.certificate, |v| v.into()),
+            Certificate::PoolRetirement { .. } => fee
+                .per_extended_certificate_fees
+                .certificate_pool_retirement
+                .map_or(fee.certificate, |v| v.into()),
+            Certificate::PoolUpdate { .. } => fee
+                .per_extended_certificate_fees
+                .certificate_pool_update
+                .map_or(fee.certificate, |v| v.into()),
         }
     }
+
+    #[quickcheck]
+    pub fn linear_fee_metadata_calculation(fee: LinearFee, metadata: Vec<u8>) -> TestResult {
+        let mut fee = fee;
+        fee.per_metadata_byte(3);
+        let size = metadata.len() as u64;
+        let tx = tx::TxBuilder::new()
+            .set_payload(&tx::Metadata::new(metadata).unwrap())
+            .set_ios(&[], &[])
+            .set_witnesses(&[])
+            .set_payload_auth(&());
+        let expected = fee
+            .calculate(None, 0, 0, 0, 0)
+            .saturating_add(Value(3 * size));
+        TestResult::from_bool(fee.calculate_tx(&tx.as_slice()) == expected)
+    }
+
+    #[quickcheck]
+    pub fn linear_fee_fragment_size_calculation(fee: LinearFee, metadata: Vec<u8>) -> TestResult {
+        let mut fee = fee;
+        fee.per_fragment_byte(2);
+        let tx = tx::TxBuilder::new()
+            .set_payload(&tx::Metadata::new(metadata).unwrap())
+            .set_ios(&[], &[])
+            .set_witnesses(&[])
+            .set_payload_auth(&());
+        let fragment_size = Fragment::Metadata(tx.clone()).to_raw().as_ref().len() as u64;
+        let expected = fee
+            .calculate(None, 0, 0, 0, 0)
+            .saturating_add(fee.fees_for_metadata(tx.as_slice().metadata().len()))
+            .saturating_add(Value(2 * fragment_size));
+        TestResult::from_bool(fee.calculate_tx(&tx.as_slice()) == expected)
+    }
 }
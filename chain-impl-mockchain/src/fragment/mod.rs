@@ -13,13 +13,23 @@ pub use content::{BlockContentHash, BlockContentSize, Contents, ContentsBuilder}
 
 use crate::{
     certificate,
-    transaction::{NoExtra, Transaction},
-    update::{SignedUpdateProposal, SignedUpdateVote},
+    transaction::{Metadata, NoExtra, Payload, Transaction},
+    update::{
+        SignedPoolUpdateVote, SignedTreasuryTransferProposal, SignedUpdateProposal,
+        SignedUpdateVote,
+    },
 };
 
 #[cfg(any(test, feature = "property-test-api"))]
 pub mod test;
 
+/// Size of the fragment header: the padding byte and the fragment tag
+pub const FRAGMENT_HEADER_SIZE: usize = 2;
+
+/// Flag set on the tag of a fragment holding a transaction with a validity
+/// interval, as the interval is not part of the original transaction encoding
+const VALIDITY_TAG_FLAG: u8 = 0x80;
+
 /// Old name for Fragment. (soft) deprecated
 pub type Message = Fragment;
 
@@ -39,6 +49,10 @@ pub enum Fragment {
     PoolUpdate(Transaction<certificate::PoolUpdate>),
     UpdateProposal(SignedUpdateProposal),
     UpdateVote(SignedUpdateVote),
+    TreasuryTransferProposal(SignedTreasuryTransferProposal),
+    PoolUpdateVote(SignedPoolUpdateVote),
+    MultisigDeclaration(Transaction<certificate::MultisigDeclaration>),
+    Metadata(Transaction<Metadata>),
 }
 
 impl PartialEq for Fragment {
@@ -61,6 +75,10 @@ pub(super) enum FragmentTag {
     PoolUpdate = 7,
     UpdateProposal = 8,
     UpdateVote = 9,
+    TreasuryTransferProposal = 10,
+    PoolUpdateVote = 11,
+    MultisigDeclaration = 12,
+    Metadata = 13,
 }
 
 impl FragmentTag {
@@ -76,6 +94,10 @@ impl FragmentTag {
             7 => Some(FragmentTag::PoolUpdate),
             8 => Some(FragmentTag::UpdateProposal),
             9 => Some(FragmentTag::UpdateVote),
+            10 => Some(FragmentTag::TreasuryTransferProposal),
+            11 => Some(FragmentTag::PoolUpdateVote),
+            12 => Some(FragmentTag::MultisigDeclaration),
+            13 => Some(FragmentTag::Metadata),
             _ => None,
         }
     }
@@ -95,9 +117,29 @@ impl Fragment {
             Fragment::PoolUpdate(_) => FragmentTag::PoolUpdate,
             Fragment::UpdateProposal(_) => FragmentTag::UpdateProposal,
             Fragment::UpdateVote(_) => FragmentTag::UpdateVote,
+            Fragment::TreasuryTransferProposal(_) => FragmentTag::TreasuryTransferProposal,
+            Fragment::PoolUpdateVote(_) => FragmentTag::PoolUpdateVote,
+            Fragment::MultisigDeclaration(_) => FragmentTag::MultisigDeclaration,
+            Fragment::Metadata(_) => FragmentTag::Metadata,
         }
     }
 
+    /// Check if this is a transaction with a validity interval
+    fn has_validity_interval(&self) -> bool {
+        let validity = match self {
+            Fragment::Transaction(tx) => tx.validity(),
+            Fragment::OwnerStakeDelegation(tx) => tx.validity(),
+            Fragment::StakeDelegation(tx) => tx.validity(),
+            Fragment::PoolRegistration(tx) => tx.validity(),
+            Fragment::PoolRetirement(tx) => tx.validity(),
+            Fragment::PoolUpdate(tx) => tx.validity(),
+            Fragment::MultisigDeclaration(tx) => tx.validity(),
+            Fragment::Metadata(tx) => tx.validity(),
+            _ => return false,
+        };
+        !validity.is_unbounded()
+    }
+
     /// Get the serialized representation of this message
     pub fn to_raw(&self) -> FragmentRaw {
         use chain_core::packer::*;
@@ -105,7 +147,11 @@ impl Fragment {
         let v = Vec::new();
         let mut codec = Codec::new(v);
         codec.put_u8(0).unwrap();
-        codec.put_u8(self.get_tag() as u8).unwrap();
+        let mut tag = self.get_tag() as u8;
+        if self.has_validity_interval() {
+            tag |= VALIDITY_TAG_FLAG;
+        }
+        codec.put_u8(tag).unwrap();
         match self {
             Fragment::Initial(i) => i.serialize(&mut codec).unwrap(),
             Fragment::OldUtxoDeclaration(s) => s.serialize(&mut codec).unwrap(),
@@ -117,6 +163,10 @@ impl Fragment {
             Fragment::PoolUpdate(pm) => pm.serialize(&mut codec).unwrap(),
             Fragment::UpdateProposal(proposal) => proposal.serialize(&mut codec).unwrap(),
             Fragment::UpdateVote(vote) => vote.serialize(&mut codec).unwrap(),
+            Fragment::TreasuryTransferProposal(proposal) => proposal.serialize(&mut codec).unwrap(),
+            Fragment::PoolUpdateVote(vote) => vote.serialize(&mut codec).unwrap(),
+            Fragment::MultisigDeclaration(decl) => decl.serialize(&mut codec).unwrap(),
+            Fragment::Metadata(tx) => tx.serialize(&mut codec).unwrap(),
         }
         FragmentRaw(codec.into_inner())
     }
@@ -143,31 +193,65 @@ impl Readable for Fragment {
         }
 
         let tag = buf.get_u8()?;
-        match FragmentTag::from_u8(tag) {
+        let with_validity = tag & VALIDITY_TAG_FLAG != 0;
+        let fragment = match FragmentTag::from_u8(tag & !VALIDITY_TAG_FLAG) {
             Some(FragmentTag::Initial) => ConfigParams::read(buf).map(Fragment::Initial),
             Some(FragmentTag::OldUtxoDeclaration) => {
                 legacy::UtxoDeclaration::read(buf).map(Fragment::OldUtxoDeclaration)
             }
-            Some(FragmentTag::Transaction) => Transaction::read(buf).map(Fragment::Transaction),
+            Some(FragmentTag::Transaction) => {
+                read_transaction(buf, with_validity).map(Fragment::Transaction)
+            }
             Some(FragmentTag::OwnerStakeDelegation) => {
-                Transaction::read(buf).map(Fragment::OwnerStakeDelegation)
+                read_transaction(buf, with_validity).map(Fragment::OwnerStakeDelegation)
             }
             Some(FragmentTag::StakeDelegation) => {
-                Transaction::read(buf).map(Fragment::StakeDelegation)
+                read_transaction(buf, with_validity).map(Fragment::StakeDelegation)
             }
             Some(FragmentTag::PoolRegistration) => {
-                Transaction::read(buf).map(Fragment::PoolRegistration)
+                read_transaction(buf, with_validity).map(Fragment::PoolRegistration)
             }
             Some(FragmentTag::PoolRetirement) => {
-                Transaction::read(buf).map(Fragment::PoolRetirement)
+                read_transaction(buf, with_validity).map(Fragment::PoolRetirement)
+            }
+            Some(FragmentTag::PoolUpdate) => {
+                read_transaction(buf, with_validity).map(Fragment::PoolUpdate)
             }
-            Some(FragmentTag::PoolUpdate) => Transaction::read(buf).map(Fragment::PoolUpdate),
             Some(FragmentTag::UpdateProposal) => {
                 SignedUpdateProposal::read(buf).map(Fragment::UpdateProposal)
             }
             Some(FragmentTag::UpdateVote) => SignedUpdateVote::read(buf).map(Fragment::UpdateVote),
+            Some(FragmentTag::TreasuryTransferProposal) => {
+                SignedTreasuryTransferProposal::read(buf).map(Fragment::TreasuryTransferProposal)
+            }
+            Some(FragmentTag::PoolUpdateVote) => {
+                SignedPoolUpdateVote::read(buf).map(Fragment::PoolUpdateVote)
+            }
+            Some(FragmentTag::MultisigDeclaration) => {
+                read_transaction(buf, with_validity).map(Fragment::MultisigDeclaration)
+            }
+            Some(FragmentTag::Metadata) => {
+                read_transaction(buf, with_validity).map(Fragment::Metadata)
+            }
             None => Err(ReadError::UnknownTag(tag as u32)),
+        }?;
+
+        // only the fragments of transactions with a validity interval are flagged
+        if with_validity != fragment.has_validity_interval() {
+            return Err(ReadError::UnknownTag(tag as u32));
         }
+        Ok(fragment)
+    }
+}
+
+fn read_transaction<P: Payload>(
+    buf: &mut ReadBuf,
+    with_validity: bool,
+) -> Result<Transaction<P>, ReadError> {
+    if with_validity {
+        Transaction::read_with_validity(buf)
+    } else {
+        Transaction::read(buf)
     }
 }
 
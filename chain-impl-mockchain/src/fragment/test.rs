@@ -1,12 +1,13 @@
 #[warn(unused_imports)]
 use super::*;
 use crate::config::ConfigParam;
+use crate::transaction::{TxBuilder, ValidityInterval};
 use quickcheck::{Arbitrary, Gen, TestResult};
 use quickcheck_macros::quickcheck;
 
 impl Arbitrary for Fragment {
     fn arbitrary<G: Gen>(g: &mut G) -> Self {
-        match g.next_u32() % 10 {
+        match g.next_u32() % 14 {
             0 => Fragment::Initial(Arbitrary::arbitrary(g)),
             1 => Fragment::OldUtxoDeclaration(Arbitrary::arbitrary(g)),
             2 => Fragment::Transaction(Arbitrary::arbitrary(g)),
@@ -16,7 +17,11 @@ impl Arbitrary for Fragment {
             6 => Fragment::PoolRetirement(Arbitrary::arbitrary(g)),
             //7 => Fragment::PoolUpdate(Arbitrary::arbitrary(g)),
             8 => Fragment::UpdateProposal(Arbitrary::arbitrary(g)),
-            _ => Fragment::UpdateVote(Arbitrary::arbitrary(g)),
+            9 => Fragment::UpdateVote(Arbitrary::arbitrary(g)),
+            10 => Fragment::TreasuryTransferProposal(Arbitrary::arbitrary(g)),
+            11 => Fragment::PoolUpdateVote(Arbitrary::arbitrary(g)),
+            12 => Fragment::MultisigDeclaration(Arbitrary::arbitrary(g)),
+            _ => Fragment::Metadata(Arbitrary::arbitrary(g)),
         }
     }
 }
@@ -27,6 +32,41 @@ fn fragment_serialization_bijection(b: Fragment) -> TestResult {
     TestResult::from_bool(b == b_got)
 }
 
+#[quickcheck]
+fn transaction_with_validity_serialization_bijection(
+    tx: Transaction<NoExtra>,
+    validity: ValidityInterval,
+) -> TestResult {
+    let tx = tx.as_slice();
+    let inputs: Vec<_> = tx.inputs().iter().collect();
+    let outputs: Vec<_> = tx.outputs().iter().collect();
+    let witnesses: Vec<_> = tx.witnesses().iter().collect();
+    let tx = TxBuilder::new()
+        .set_nopayload()
+        .set_validity(validity)
+        .set_ios(&inputs, &outputs)
+        .set_witnesses(&witnesses)
+        .set_payload_auth(&());
+    let raw = Fragment::Transaction(tx).to_raw();
+    let flagged = raw.as_ref()[1] & VALIDITY_TAG_FLAG != 0;
+    match Fragment::from_raw(&raw) {
+        Ok(Fragment::Transaction(got)) => {
+            TestResult::from_bool(got.validity() == validity && flagged != validity.is_unbounded())
+        }
+        _ => TestResult::failed(),
+    }
+}
+
+#[test]
+fn validity_flag_is_rejected_on_other_fragments() {
+    let mut raw = Fragment::Initial(ConfigParams::new())
+        .to_raw()
+        .as_ref()
+        .to_vec();
+    raw[1] |= VALIDITY_TAG_FLAG;
+    assert!(Fragment::from_raw(&FragmentRaw(raw)).is_err());
+}
+
 quickcheck! {
     fn initial_ents_serialization_bijection(config_params: ConfigParams) -> TestResult {
         property::testing::serialization_bijection_r(config_params)
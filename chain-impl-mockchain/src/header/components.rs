@@ -27,6 +27,11 @@ impl KESSignature {
     pub fn verify(&self, pk: &PublicKey<SumEd25519_12>, data: &[u8]) -> Verification {
         self.0.verify_slice(pk, data)
     }
+
+    /// the KES period of the key that produced this signature
+    pub fn period(&self) -> u32 {
+        self.0.get_period()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -1,9 +1,12 @@
 use crate::block::{BlockDate, Header, Proof};
+use crate::date::Epoch;
 use crate::{
     key::BftLeaderId,
-    leadership::{Error, ErrorKind, Verification},
+    leadership::{bft_slot_index, is_bft_slot, Error, ErrorKind, Verification},
     ledger::Ledger,
+    milli::Milli,
 };
+use chain_time::era::TimeEra;
 use std::sync::Arc;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -13,6 +16,9 @@ pub struct BftRoundRobinIndex(u64);
 #[derive(Debug)]
 pub struct LeadershipData {
     pub(crate) leaders: Arc<Vec<BftLeaderId>>,
+    /// share of the slots led by the BFT leaders, the round robin only going
+    /// over these slots
+    pub(crate) bft_slots_ratio: Milli,
 }
 
 impl LeadershipData {
@@ -24,18 +30,27 @@ impl LeadershipData {
 
         Some(LeadershipData {
             leaders: Arc::clone(&ledger.settings.bft_leaders),
+            bft_slots_ratio: Milli::ONE,
         })
     }
 
+    /// Only lead the slots selected by `is_bft_slot` for the given ratio
+    pub fn with_bft_slots_ratio(self, bft_slots_ratio: Milli) -> Self {
+        LeadershipData {
+            bft_slots_ratio,
+            ..self
+        }
+    }
+
     #[inline]
     pub fn number_of_leaders(&self) -> usize {
         self.leaders.len()
     }
 
     #[inline]
-    fn offset(&self, block_number: u64) -> BftRoundRobinIndex {
+    fn offset(&self, slot_id: u32) -> BftRoundRobinIndex {
         let max = self.number_of_leaders() as u64;
-        BftRoundRobinIndex((block_number % max) as u64)
+        BftRoundRobinIndex(bft_slot_index(self.bft_slots_ratio, slot_id) % max)
     }
 
     pub(crate) fn verify(&self, block_header: &Header) -> Verification {
@@ -54,9 +69,60 @@ impl LeadershipData {
         }
     }
 
+    /// The round robin assignment of the leaders to the BFT slots of the epoch
+    pub fn leader_schedule(&self, era: &TimeEra, epoch: Epoch) -> Vec<(BlockDate, BftLeaderId)> {
+        (0..era.slots_per_epoch())
+            .filter(|slot_id| is_bft_slot(self.bft_slots_ratio, *slot_id))
+            .map(|slot_id| {
+                let BftRoundRobinIndex(ofs) = self.offset(slot_id);
+                (
+                    BlockDate { epoch, slot_id },
+                    self.leaders[ofs as usize].clone(),
+                )
+            })
+            .collect()
+    }
+
     #[inline]
     pub(crate) fn get_leader_at(&self, date: BlockDate) -> Result<BftLeaderId, Error> {
-        let BftRoundRobinIndex(ofs) = self.offset(date.slot_id as u64);
+        let BftRoundRobinIndex(ofs) = self.offset(date.slot_id);
         Ok(self.leaders[ofs as usize].clone())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{ConfigBuilder, LedgerBuilder, TestGen};
+
+    #[test]
+    pub fn leader_schedule_is_round_robin() {
+        let ledger = LedgerBuilder::from_config(ConfigBuilder::new(0).with_slots_per_epoch(7))
+            .build()
+            .expect("cannot build test ledger")
+            .ledger;
+        let leaders: Vec<BftLeaderId> = TestGen::leaders_pairs()
+            .take(3)
+            .map(|leader| leader.id())
+            .collect();
+        let bft = LeadershipData {
+            leaders: Arc::new(leaders.clone()),
+            bft_slots_ratio: Milli::ONE,
+        };
+
+        let schedule = bft.leader_schedule(ledger.era(), 1);
+
+        assert_eq!(schedule.len(), 7);
+        for (slot_id, (date, leader_id)) in schedule.into_iter().enumerate() {
+            assert_eq!(
+                date,
+                BlockDate {
+                    epoch: 1,
+                    slot_id: slot_id as u32
+                }
+            );
+            assert_eq!(leader_id, leaders[slot_id % 3]);
+            assert_eq!(bft.get_leader_at(date).unwrap(), leader_id);
+        }
+    }
+}
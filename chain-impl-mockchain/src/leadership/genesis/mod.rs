@@ -1,20 +1,23 @@
 mod vrfeval;
 
 use crate::{
-    certificate::PoolId,
+    certificate::{PoolId, PoolRegistration},
     chaineval::PraosNonce,
     date::{BlockDate, Epoch},
     header::{Header, HeaderDesc, Proof},
-    leadership::{Error, ErrorKind, Verification},
+    leadership::{Error, ErrorKind, GenesisLeader, Verification},
     ledger::Ledger,
     setting::ActiveSlotsCoeff,
     stake::{PercentStake, PoolsState, Stake, StakeDistribution},
 };
 use chain_crypto::Verification as SigningVerification;
 use chain_crypto::{Curve25519_2HashDH, SecretKey};
+use chain_time::{SlotDuration, TimeEra, TimeFrame, Timeline};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
 use thiserror::Error;
 pub(crate) use vrfeval::witness_to_nonce;
-use vrfeval::VrfEvaluator;
+use vrfeval::{slot_leader_probability, VrfEvaluator};
 pub use vrfeval::{Threshold, VrfEvalFailure, Witness, WitnessOutput};
 
 /// Genesis Praos leadership data for a specific epoch
@@ -25,8 +28,16 @@ pub struct LeadershipData {
     // the epoch this leader selection is valid for
     epoch: Epoch,
     active_slots_coeff: ActiveSlotsCoeff,
+    // the time parameters used to find the expected KES period of a block
+    era: TimeEra,
+    time_frame: TimeFrame,
+    block0_start_time: SystemTime,
+    kes_update_speed: u32,
 }
 
+/// Number of periods a `SumEd25519_12` key can evolve through
+const KES_PERIODS: u32 = 1 << 12;
+
 #[derive(Debug, Error)]
 enum GenesisError {
     #[error("Wrong epoch, expected epoch {expected} but received block at epoch {actual}")]
@@ -35,6 +46,27 @@ enum GenesisError {
     TotalStakeIsZero,
 }
 
+#[derive(Debug, Error)]
+enum KesError {
+    #[error("KES key of poolId: {poolid} is not valid yet in {bdesc}")]
+    NotYetValid { bdesc: HeaderDesc, poolid: String },
+    #[error("KES key of poolId: {poolid} has expired in {bdesc}, expected period {period}")]
+    Expired {
+        bdesc: HeaderDesc,
+        poolid: String,
+        period: u64,
+    },
+    #[error(
+        "Invalid KES period in {bdesc}, poolId: {poolid}, expected: {expected}, actual: {actual}"
+    )]
+    InvalidPeriod {
+        bdesc: HeaderDesc,
+        poolid: String,
+        expected: u32,
+        actual: u32,
+    },
+}
+
 #[derive(Debug, Error)]
 enum VrfError {
     #[error("Invalid Vrf Proof Structure in {bdesc} for poolId: {poolid}")]
@@ -54,12 +86,22 @@ enum VrfError {
 
 impl LeadershipData {
     pub fn new(epoch: Epoch, ledger: &Ledger) -> Self {
+        let block0_start_time =
+            SystemTime::UNIX_EPOCH + Duration::from_secs(ledger.static_params.block0_start_time.0);
+        let time_frame = TimeFrame::new(
+            Timeline::new(block0_start_time),
+            SlotDuration::from_secs(ledger.settings.slot_duration as u32),
+        );
         LeadershipData {
             epoch_nonce: ledger.settings.consensus_nonce.clone(),
             nodes: ledger.delegation.clone(),
-            distribution: ledger.get_stake_distribution(),
+            distribution: ledger.epoch_stake_distribution(epoch),
             epoch,
             active_slots_coeff: ledger.settings.active_slots_coeff,
+            era: ledger.era.clone(),
+            time_frame,
+            block0_start_time,
+            kes_update_speed: ledger.static_params.kes_update_speed,
         }
     }
 
@@ -118,6 +160,106 @@ impl LeadershipData {
         }
     }
 
+    /// Compute every slot of the epoch the pool is elected leader of, along
+    /// with the VRF witness to put in the block header
+    pub fn leader_schedule(
+        &self,
+        leader: &GenesisLeader,
+    ) -> Result<Vec<(BlockDate, Witness)>, Error> {
+        let mut schedule = Vec::new();
+        for slot_id in 0..self.era.slots_per_epoch() {
+            let date = BlockDate {
+                epoch: self.epoch,
+                slot_id,
+            };
+            if let Some(witness) = self.leader(&leader.node_id, &leader.vrf_key, date)? {
+                schedule.push((date, witness));
+            }
+        }
+        Ok(schedule)
+    }
+
+    /// Expected number of blocks of every pool of the stake distribution
+    /// for the epoch.
+    ///
+    /// This is an average over the possible VRF outputs, to compare the
+    /// number of blocks a pool actually produced with, as the real schedule
+    /// can only be computed by the owner of the pool's VRF key.
+    pub fn expected_blocks(&self) -> HashMap<PoolId, f64> {
+        let total_stake = self.distribution.total_stake();
+        let slots = self.era.slots_per_epoch() as f64;
+        self.distribution
+            .to_pools
+            .iter()
+            .map(|(pool_id, pool)| {
+                let expected = if total_stake == Stake::zero() {
+                    0.0
+                } else {
+                    let stake = PercentStake::new(pool.stake.total, total_stake);
+                    slots * slot_leader_probability(self.active_slots_coeff, &stake)
+                };
+                (pool_id.clone(), expected)
+            })
+            .collect()
+    }
+
+    /// The KES period a pool's key is expected to be at for the given date
+    ///
+    /// The period starts at the pool's `start_validity` and moves forward every
+    /// `kes_update_speed` seconds. A `kes_update_speed` of zero means the key
+    /// never evolves.
+    fn kes_period(&self, pool: &PoolRegistration, date: BlockDate) -> Option<u64> {
+        let slot = self.era.from_era_to_slot(date.into());
+        let slot_time = self.time_frame.slot_to_systemtime(slot)?;
+        let start_validity = Duration::from_secs(pool.start_validity.into());
+        let elapsed = slot_time
+            .duration_since(self.block0_start_time + start_validity)
+            .ok()?;
+        match self.kes_update_speed {
+            0 => Some(0),
+            speed => Some(elapsed.as_secs() / u64::from(speed)),
+        }
+    }
+
+    fn verify_kes_period(
+        &self,
+        block_header: &Header,
+        node_id: &PoolId,
+        pool: &PoolRegistration,
+        signature_period: u32,
+    ) -> Verification {
+        let poolid = node_id.to_string();
+        match self.kes_period(pool, block_header.block_date()) {
+            None => Verification::Failure(Error::new_(
+                ErrorKind::KesKeyNotYetValid,
+                KesError::NotYetValid {
+                    bdesc: block_header.description(),
+                    poolid,
+                },
+            )),
+            Some(period) if period >= u64::from(KES_PERIODS) => Verification::Failure(Error::new_(
+                ErrorKind::KesKeyExpired,
+                KesError::Expired {
+                    bdesc: block_header.description(),
+                    poolid,
+                    period,
+                },
+            )),
+            Some(period) if period != u64::from(signature_period) => {
+                Verification::Failure(Error::new_(
+                    ErrorKind::InvalidKesPeriod,
+                    KesError::InvalidPeriod {
+                        bdesc: block_header.description(),
+                        poolid,
+                        expected: period as u32,
+                        actual: signature_period,
+                    },
+                ))
+            }
+            Some(_) => Verification::Success,
+        }
+    }
+
     pub(crate) fn verify(&self, block_header: &Header) -> Verification {
         if block_header.block_date().epoch != self.epoch {
             return Verification::Failure(Error::new_(
@@ -139,6 +281,16 @@ impl LeadershipData {
                     self.nodes.lookup_reg(node_id),
                 ) {
                     (Some(stake), Some(pool_info)) => {
+                        let kes_verification = self.verify_kes_period(
+                            block_header,
+                            node_id,
+                            pool_info,
+                            genesis_praos_proof.kes_proof.period(),
+                        );
+                        if kes_verification.failure() {
+                            return kes_verification;
+                        }
+
                         // Calculate the total stake.
                         let total_stake = stake_snapshot.total_stake();
 
@@ -538,6 +690,8 @@ mod tests {
 
     use crate::fragment::Contents;
     use crate::header::{BlockVersion, HeaderBuilderNew};
+    use crate::testing::{data::StakePool, TestGen};
+    use chain_time::DurationSeconds;
 
     #[test]
     pub fn leadership_verify_different_epoch() {
@@ -693,4 +847,207 @@ mod tests {
 
         assert!(selection.verify(&header).failure());
     }
+
+    fn kes_signed_header(
+        stake_pool: &StakePool,
+        pool_id: &PoolId,
+        date: BlockDate,
+        evolutions: u32,
+    ) -> Header {
+        let mut kes_key = stake_pool.kes().private_key().clone();
+        for _ in 0..evolutions {
+            SecretKey::evolve(&mut kes_key);
+        }
+        HeaderBuilderNew::new(BlockVersion::KesVrfproof, &Contents::empty())
+            .set_parent(&HeaderId::zero_hash(), crate::chaintypes::ChainLength(1))
+            .set_date(date)
+            .to_genesis_praos_builder()
+            .unwrap()
+            .set_consensus_data(pool_id, &TestGen::vrf_proof(stake_pool).into())
+            .sign_using(&kes_key)
+            .generalize()
+    }
+
+    fn kes_leadership(kes_update_speed: u32, registration: PoolRegistration) -> LeadershipData {
+        let mut ledger = LedgerBuilder::from_config(
+            ConfigBuilder::new(0)
+                .with_slot_duration(20)
+                .with_kes_update_speed(kes_update_speed),
+        )
+        .build()
+        .expect("cannot build test ledger")
+        .ledger;
+        let pool_id = registration.to_id();
+        ledger.delegation = ledger
+            .delegation()
+            .register_stake_pool(registration)
+            .expect("cannot register stake pool");
+        let mut selection = LeadershipData::new(0, &ledger);
+        update_stake_pool_total_value(&mut selection, &pool_id, Stake::from_value(Value(100)));
+        selection
+    }
+
+    fn kes_failure(selection: &LeadershipData, header: &Header) -> Option<ErrorKind> {
+        match selection.verify(header) {
+            Verification::Failure(error) => Some(error.kind().clone()),
+            Verification::Success => None,
+        }
+    }
+
+    #[test]
+    pub fn leadership_verify_kes_current_period() {
+        let stake_pool = StakePoolBuilder::new().build();
+        // one KES period every 3 slots
+        let selection = kes_leadership(60, stake_pool.info());
+        let date = BlockDate {
+            epoch: 0,
+            slot_id: 7,
+        };
+        let header = kes_signed_header(&stake_pool, &stake_pool.id(), date, 2);
+
+        assert!(selection
+            .verify_kes_period(&header, &stake_pool.id(), &stake_pool.info(), 2)
+            .success());
+        assert_ne!(
+            kes_failure(&selection, &header),
+            Some(ErrorKind::InvalidKesPeriod)
+        );
+    }
+
+    #[test]
+    pub fn leadership_verify_kes_wrong_period() {
+        let stake_pool = StakePoolBuilder::new().build();
+        let selection = kes_leadership(60, stake_pool.info());
+        let date = BlockDate {
+            epoch: 0,
+            slot_id: 7,
+        };
+
+        for evolutions in &[0, 1, 3] {
+            let header = kes_signed_header(&stake_pool, &stake_pool.id(), date, *evolutions);
+            assert_eq!(
+                kes_failure(&selection, &header),
+                Some(ErrorKind::InvalidKesPeriod)
+            );
+        }
+    }
+
+    #[test]
+    pub fn leadership_verify_kes_key_expired() {
+        let stake_pool = StakePoolBuilder::new().build();
+        // 4096 periods of 1 second are over after 205 slots of 20 seconds
+        let selection = kes_leadership(1, stake_pool.info());
+        let date = BlockDate {
+            epoch: 0,
+            slot_id: 205,
+        };
+        let header = kes_signed_header(&stake_pool, &stake_pool.id(), date, 0);
+
+        assert_eq!(
+            kes_failure(&selection, &header),
+            Some(ErrorKind::KesKeyExpired)
+        );
+    }
+
+    #[test]
+    pub fn leadership_verify_kes_key_not_yet_valid() {
+        let stake_pool = StakePoolBuilder::new().build();
+        let mut registration = stake_pool.info();
+        registration.start_validity = DurationSeconds::from(100).into();
+        let selection = kes_leadership(60, registration.clone());
+        let date = BlockDate {
+            epoch: 0,
+            slot_id: 4,
+        };
+        let header = kes_signed_header(&stake_pool, &registration.to_id(), date, 0);
+
+        assert_eq!(
+            kes_failure(&selection, &header),
+            Some(ErrorKind::KesKeyNotYetValid)
+        );
+        let date = BlockDate {
+            epoch: 0,
+            slot_id: 5,
+        };
+        assert!(selection
+            .verify_kes_period(
+                &kes_signed_header(&stake_pool, &registration.to_id(), date, 0),
+                &registration.to_id(),
+                &registration,
+                0
+            )
+            .success());
+    }
+
+    fn schedule_leadership(slots_per_epoch: u32, active_slots_coeff: Milli) -> LeadershipData {
+        let ledger = LedgerBuilder::from_config(
+            ConfigBuilder::new(0)
+                .with_slots_per_epoch(slots_per_epoch)
+                .with_active_slots_coeff(active_slots_coeff),
+        )
+        .build()
+        .expect("cannot build test ledger")
+        .ledger;
+        LeadershipData::new(0, &ledger)
+    }
+
+    #[test]
+    pub fn leader_schedule_matches_leader_for_each_slot() {
+        let slots_per_epoch = 200;
+        let stake_pool = StakePoolBuilder::new().build();
+        let mut selection = schedule_leadership(slots_per_epoch, Milli::HALF);
+        update_stake_pool_total_value(
+            &mut selection,
+            &stake_pool.id(),
+            Stake::from_value(Value(100)),
+        );
+        let leader = GenesisLeader {
+            node_id: stake_pool.id(),
+            sig_key: stake_pool.kes().private_key().clone(),
+            vrf_key: stake_pool.vrf().private_key().clone(),
+        };
+
+        let scheduled: Vec<BlockDate> = selection
+            .leader_schedule(&leader)
+            .unwrap()
+            .into_iter()
+            .map(|(date, _)| date)
+            .collect();
+        let elected: Vec<BlockDate> = (0..slots_per_epoch)
+            .map(|slot_id| BlockDate { epoch: 0, slot_id })
+            .filter(|date| {
+                selection
+                    .leader(&stake_pool.id(), &leader.vrf_key, *date)
+                    .unwrap()
+                    .is_some()
+            })
+            .collect();
+
+        assert!(!scheduled.is_empty());
+        assert_eq!(scheduled, elected);
+    }
+
+    #[test]
+    pub fn expected_blocks_follows_stake() {
+        let slots_per_epoch = 1000;
+        let small_pool = StakePoolBuilder::new().build();
+        let big_pool = StakePoolBuilder::new().build();
+        let mut selection = schedule_leadership(slots_per_epoch, Milli::from_millis(100));
+        update_stake_pool_total_value(
+            &mut selection,
+            &small_pool.id(),
+            Stake::from_value(Value(100)),
+        );
+        update_stake_pool_total_value(
+            &mut selection,
+            &big_pool.id(),
+            Stake::from_value(Value(300)),
+        );
+
+        let expected_blocks = selection.expected_blocks();
+        let expected = |ratio: f64| slots_per_epoch as f64 * (1.0 - 0.9f64.powf(ratio));
+        assert_eq!(expected_blocks.len(), 2);
+        assert!((expected_blocks[&small_pool.id()] - expected(0.25)).abs() < 1e-6);
+        assert!((expected_blocks[&big_pool.id()] - expected(0.75)).abs() < 1e-6);
+    }
 }
@@ -122,6 +122,14 @@ fn above_stake_threshold(
     threshold < phi(active_slots_coeff, stake)
 }
 
+/// Probability for the given stake to be elected leader of a slot
+pub(crate) fn slot_leader_probability(
+    active_slots_coeff: ActiveSlotsCoeff,
+    stake: &PercentStake,
+) -> f64 {
+    phi(active_slots_coeff, stake).0
+}
+
 fn phi(active_slots_coeff: ActiveSlotsCoeff, rs: &PercentStake) -> Threshold {
     let t = rs.as_float();
     let f: f64 = active_slots_coeff.into();
@@ -5,10 +5,12 @@ use crate::{
     date::Epoch,
     key::BftLeaderId,
     ledger::{Ledger, LedgerParameters},
+    milli::Milli,
     stake::StakeDistribution,
 };
 use chain_crypto::{Curve25519_2HashDH, Ed25519, SecretKey, SumEd25519_12};
 use chain_time::era::TimeEra;
+use std::collections::HashMap;
 
 pub mod bft;
 pub mod genesis;
@@ -25,6 +27,9 @@ pub enum ErrorKind {
     InvalidBlockMessage,
     InvalidStateUpdate,
     VrfNonceIsEmptyButNotSupposedTo,
+    KesKeyNotYetValid,
+    KesKeyExpired,
+    InvalidKesPeriod,
 }
 
 #[derive(Debug)]
@@ -72,6 +77,19 @@ pub enum LeaderOutput {
 pub enum LeadershipConsensus {
     Bft(bft::LeadershipData),
     GenesisPraos(genesis::LeadershipData),
+    /// Genesis Praos where a share of the slots of the epoch is reserved to
+    /// the BFT leaders, see `is_bft_slot`
+    Hybrid {
+        bft: bft::LeadershipData,
+        genesis_praos: genesis::LeadershipData,
+        bft_slots_ratio: Milli,
+    },
+}
+
+/// The leader selection applying to a given slot
+enum SlotConsensus<'a> {
+    Bft(&'a bft::LeadershipData),
+    GenesisPraos(&'a genesis::LeadershipData),
 }
 
 /// Leadership represent a given epoch and their associated leader or metadata.
@@ -86,32 +104,82 @@ pub struct Leadership {
     ledger_parameters: LedgerParameters,
 }
 
+/// Tell whether the slot of an epoch is reserved to the BFT leaders, given
+/// the share of the slots reserved to them.
+///
+/// The BFT slots are spread evenly over the epoch: the slot `slot_id` is a BFT
+/// slot if `floor(slot_id * ratio)` and `floor((slot_id + 1) * ratio)` differ.
+pub fn is_bft_slot(bft_slots_ratio: Milli, slot_id: u32) -> bool {
+    let ratio = bft_slots_ratio.to_millis();
+    let slot_id = slot_id as u64;
+    (slot_id + 1) * ratio / 1000 > slot_id * ratio / 1000
+}
+
+/// Index of a slot selected by `is_bft_slot` among the BFT slots of the epoch,
+/// which the round robin of the BFT leaders goes over.
+pub fn bft_slot_index(bft_slots_ratio: Milli, slot_id: u32) -> u64 {
+    slot_id as u64 * bft_slots_ratio.to_millis() / 1000
+}
+
+/// Number of slots reserved to the BFT leaders in an epoch of `slots_per_epoch`
+/// slots, as selected by `is_bft_slot`
+pub fn bft_slots_count(bft_slots_ratio: Milli, slots_per_epoch: u32) -> u32 {
+    (slots_per_epoch as u64 * bft_slots_ratio.to_millis() / 1000) as u32
+}
+
 impl LeadershipConsensus {
     #[inline]
-    fn verify_version(&self, block_version: BlockVersion) -> Verification {
+    fn at(&self, date: BlockDate) -> SlotConsensus<'_> {
         match self {
-            LeadershipConsensus::Bft(_) if block_version == BlockVersion::Ed25519Signed => {
-                Verification::Success
+            LeadershipConsensus::Bft(bft) => SlotConsensus::Bft(bft),
+            LeadershipConsensus::GenesisPraos(genesis_praos) => {
+                SlotConsensus::GenesisPraos(genesis_praos)
             }
-            LeadershipConsensus::GenesisPraos(_) if block_version == BlockVersion::KesVrfproof => {
-                Verification::Success
+            LeadershipConsensus::Hybrid {
+                bft,
+                genesis_praos,
+                bft_slots_ratio,
+            } => {
+                if is_bft_slot(*bft_slots_ratio, date.slot_id) {
+                    SlotConsensus::Bft(bft)
+                } else {
+                    SlotConsensus::GenesisPraos(genesis_praos)
+                }
             }
-            _ => Verification::Failure(Error::new(ErrorKind::IncompatibleBlockVersion)),
+        }
+    }
+}
+
+impl<'a> SlotConsensus<'a> {
+    #[inline]
+    fn block_version(&self) -> BlockVersion {
+        match self {
+            SlotConsensus::Bft(_) => BlockVersion::Ed25519Signed,
+            SlotConsensus::GenesisPraos(_) => BlockVersion::KesVrfproof,
+        }
+    }
+
+    #[inline]
+    fn verify_version(&self, block_version: BlockVersion) -> Verification {
+        if block_version == self.block_version() {
+            Verification::Success
+        } else {
+            Verification::Failure(Error::new(ErrorKind::IncompatibleBlockVersion))
         }
     }
 
     #[inline]
     fn verify_leader(&self, block_header: &Header) -> Verification {
         match self {
-            LeadershipConsensus::Bft(bft) => bft.verify(block_header),
-            LeadershipConsensus::GenesisPraos(genesis_praos) => genesis_praos.verify(block_header),
+            SlotConsensus::Bft(bft) => bft.verify(block_header),
+            SlotConsensus::GenesisPraos(genesis_praos) => genesis_praos.verify(block_header),
         }
     }
 
     #[inline]
     fn is_leader(&self, leader: &Leader, date: BlockDate) -> Result<LeaderOutput, Error> {
         match self {
-            LeadershipConsensus::Bft(bft) => match leader.bft_leader {
+            SlotConsensus::Bft(bft) => match leader.bft_leader {
                 Some(ref bft_leader) => {
                     let bft_leader_id = bft.get_leader_at(date)?;
                     if bft_leader_id == bft_leader.sig_key.to_public().into() {
@@ -122,7 +190,7 @@ impl LeadershipConsensus {
                 }
                 None => Ok(LeaderOutput::None),
             },
-            LeadershipConsensus::GenesisPraos(genesis_praos) => match leader.genesis_leader {
+            SlotConsensus::GenesisPraos(genesis_praos) => match leader.genesis_leader {
                 None => Ok(LeaderOutput::None),
                 Some(ref gen_leader) => {
                     match genesis_praos.leader(&gen_leader.node_id, &gen_leader.vrf_key, date) {
@@ -145,7 +213,17 @@ impl Leadership {
                 LeadershipConsensus::Bft(bft::LeadershipData::new(ledger).unwrap())
             }
             ConsensusType::GenesisPraos => {
-                LeadershipConsensus::GenesisPraos(genesis::LeadershipData::new(epoch, ledger))
+                let genesis_praos = genesis::LeadershipData::new(epoch, ledger);
+                let bft_slots_ratio = ledger.settings.bft_slots_ratio;
+                // without BFT leaders, all the slots are left to the stake pools
+                match bft::LeadershipData::new(ledger) {
+                    Some(bft) if bft_slots_ratio > Milli::ZERO => LeadershipConsensus::Hybrid {
+                        bft: bft.with_bft_slots_ratio(bft_slots_ratio),
+                        genesis_praos,
+                        bft_slots_ratio,
+                    },
+                    _ => LeadershipConsensus::GenesisPraos(genesis_praos),
+                }
             }
         };
         Leadership {
@@ -165,7 +243,11 @@ impl Leadership {
     pub fn stake_distribution(&self) -> Option<&StakeDistribution> {
         match &self.inner {
             LeadershipConsensus::Bft(_) => None,
-            LeadershipConsensus::GenesisPraos(inner) => Some(inner.distribution()),
+            LeadershipConsensus::GenesisPraos(inner)
+            | LeadershipConsensus::Hybrid {
+                genesis_praos: inner,
+                ..
+            } => Some(inner.distribution()),
         }
     }
 
@@ -202,12 +284,19 @@ impl Leadership {
     /// Verify whether this header has been produced by a leader that fits with the leadership
     ///
     pub fn verify(&self, block_header: &Header) -> Verification {
-        try_check!(self.inner.verify_version(block_header.block_version()));
+        let consensus = self.inner.at(block_header.block_date());
+        try_check!(consensus.verify_version(block_header.block_version()));
 
-        try_check!(self.inner.verify_leader(block_header));
+        try_check!(consensus.verify_leader(block_header));
         Verification::Success
     }
 
+    /// The version of the block expected at the given date: BFT signed blocks
+    /// in the BFT slots, KES and VRF proved blocks in the others.
+    pub fn block_version_at(&self, date: BlockDate) -> BlockVersion {
+        self.inner.at(date).block_version()
+    }
+
     /// Test that the given leader object is able to create a valid block for the leadership
     /// at a given date.
     pub fn is_leader_for_date<'a>(
@@ -215,7 +304,99 @@ impl Leadership {
         leader: &'a Leader,
         date: BlockDate,
     ) -> Result<LeaderOutput, Error> {
-        self.inner.is_leader(leader, date)
+        self.inner.at(date).is_leader(leader, date)
+    }
+
+    /// Compute every slot of the epoch the given leader is able to create a
+    /// block for, as `is_leader_for_date` would tell for each of them.
+    pub fn leader_schedule(
+        &self,
+        leader: &Leader,
+    ) -> Result<Vec<(BlockDate, LeaderOutput)>, Error> {
+        match &self.inner {
+            LeadershipConsensus::Bft(bft) => Ok(self.bft_schedule(bft, leader)),
+            LeadershipConsensus::GenesisPraos(genesis_praos) => {
+                self.genesis_praos_schedule(genesis_praos, leader)
+            }
+            LeadershipConsensus::Hybrid {
+                bft,
+                genesis_praos,
+                bft_slots_ratio,
+            } => {
+                let mut schedule = self.bft_schedule(bft, leader);
+                schedule.extend(
+                    self.genesis_praos_schedule(genesis_praos, leader)?
+                        .into_iter()
+                        .filter(|(date, _)| !is_bft_slot(*bft_slots_ratio, date.slot_id)),
+                );
+                schedule.sort_by_key(|(date, _)| *date);
+                Ok(schedule)
+            }
+        }
+    }
+
+    fn bft_schedule(
+        &self,
+        bft: &bft::LeadershipData,
+        leader: &Leader,
+    ) -> Vec<(BlockDate, LeaderOutput)> {
+        match leader.bft_leader {
+            None => Vec::new(),
+            Some(ref bft_leader) => {
+                let bft_leader_id: BftLeaderId = bft_leader.sig_key.to_public().into();
+                bft.leader_schedule(&self.era, self.epoch)
+                    .into_iter()
+                    .filter(|(_, leader_id)| *leader_id == bft_leader_id)
+                    .map(|(date, leader_id)| (date, LeaderOutput::Bft(leader_id)))
+                    .collect()
+            }
+        }
+    }
+
+    fn genesis_praos_schedule(
+        &self,
+        genesis_praos: &genesis::LeadershipData,
+        leader: &Leader,
+    ) -> Result<Vec<(BlockDate, LeaderOutput)>, Error> {
+        match leader.genesis_leader {
+            None => Ok(Vec::new()),
+            Some(ref gen_leader) => Ok(genesis_praos
+                .leader_schedule(gen_leader)?
+                .into_iter()
+                .map(|(date, witness)| {
+                    (
+                        date,
+                        LeaderOutput::GenesisPraos(gen_leader.node_id.clone(), witness),
+                    )
+                })
+                .collect()),
+        }
+    }
+
+    /// Expected number of blocks of each stake pool for the epoch, to monitor
+    /// the pools. Not available for BFT, where the schedule is known.
+    pub fn expected_blocks(&self) -> Option<HashMap<PoolId, f64>> {
+        match &self.inner {
+            LeadershipConsensus::Bft(_) => None,
+            LeadershipConsensus::GenesisPraos(inner) => Some(inner.expected_blocks()),
+            LeadershipConsensus::Hybrid {
+                genesis_praos,
+                bft_slots_ratio,
+                ..
+            } => {
+                // the pools only get the slots not reserved to the BFT leaders
+                let slots = self.era.slots_per_epoch();
+                let pool_slots = slots - bft_slots_count(*bft_slots_ratio, slots);
+                let share = pool_slots as f64 / slots as f64;
+                Some(
+                    genesis_praos
+                        .expected_blocks()
+                        .into_iter()
+                        .map(|(pool_id, expected)| (pool_id, expected * share))
+                        .collect(),
+                )
+            }
+        }
     }
 }
 
@@ -248,6 +429,10 @@ impl Error {
         }
     }
 
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
     pub fn new_<E>(kind: ErrorKind, cause: E) -> Self
     where
         E: std::error::Error + 'static,
@@ -276,6 +461,9 @@ impl std::fmt::Display for ErrorKind {
             ErrorKind::InvalidBlockMessage => write!(f, "Invalid block message"),
             ErrorKind::InvalidStateUpdate => write!(f, "Invalid State Update"),
             ErrorKind::VrfNonceIsEmptyButNotSupposedTo => write!(f, "Vrf Nonce is empty"),
+            ErrorKind::KesKeyNotYetValid => write!(f, "KES key is not valid yet"),
+            ErrorKind::KesKeyExpired => write!(f, "KES key has expired"),
+            ErrorKind::InvalidKesPeriod => write!(f, "KES signature is from the wrong period"),
         }
     }
 }
@@ -293,3 +481,169 @@ impl std::error::Error for Error {
         self.cause.as_ref().map(std::ops::Deref::deref)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chaintypes::ChainLength;
+    use crate::fragment::Contents;
+    use crate::header::HeaderBuilderNew;
+    use crate::key::Hash;
+    use crate::testing::{ConfigBuilder, LedgerBuilder, TestGen};
+
+    fn hybrid_leadership(
+        slots_per_epoch: u32,
+        bft_slots_ratio: Milli,
+        bft_leaders: usize,
+    ) -> (Leadership, Vec<SecretKey<Ed25519>>) {
+        let bft_leader_keys: Vec<SecretKey<Ed25519>> = (0..bft_leaders)
+            .map(|_| TestGen::leader_pair().key())
+            .collect();
+        let leaders: Vec<BftLeaderId> = bft_leader_keys
+            .iter()
+            .map(|key| key.to_public().into())
+            .collect();
+        let ledger = LedgerBuilder::from_config(
+            ConfigBuilder::new(0)
+                .with_consensus_version(ConsensusType::GenesisPraos)
+                .with_slots_per_epoch(slots_per_epoch)
+                .with_leaders(&leaders)
+                .with_bft_slots_ratio(bft_slots_ratio),
+        )
+        .build()
+        .expect("cannot build test ledger")
+        .ledger;
+        (Leadership::new(0, &ledger), bft_leader_keys)
+    }
+
+    fn bft_leader(bft_leader_key: &SecretKey<Ed25519>) -> Leader {
+        Leader {
+            bft_leader: Some(BftLeader {
+                sig_key: bft_leader_key.clone(),
+            }),
+            genesis_leader: None,
+        }
+    }
+
+    fn bft_header(date: BlockDate, bft_leader_key: &SecretKey<Ed25519>) -> Header {
+        HeaderBuilderNew::new(BlockVersion::Ed25519Signed, &Contents::empty())
+            .set_parent(&Hash::zero_hash(), ChainLength(1))
+            .set_date(date)
+            .to_bft_builder()
+            .unwrap()
+            .sign_using(bft_leader_key)
+            .generalize()
+    }
+
+    #[test]
+    pub fn bft_slots_are_spread_evenly() {
+        let slots_per_epoch = 100;
+        for millis in &[0, 1, 250, 333, 500, 999, 1000] {
+            let ratio = Milli::from_millis(*millis);
+            let bft_slots: Vec<u32> = (0..slots_per_epoch)
+                .filter(|slot_id| is_bft_slot(ratio, *slot_id))
+                .collect();
+            assert_eq!(
+                bft_slots.len() as u32,
+                bft_slots_count(ratio, slots_per_epoch)
+            );
+            // no long run of pool slots in the middle of the epoch
+            if bft_slots.len() > 1 {
+                let max_gap = 1000 / millis + 1;
+                assert!(bft_slots
+                    .windows(2)
+                    .all(|pair| (pair[1] - pair[0]) as u64 <= max_gap));
+            }
+        }
+        assert!((0..slots_per_epoch).all(|slot_id| !is_bft_slot(Milli::ZERO, slot_id)));
+        assert!((0..slots_per_epoch).all(|slot_id| is_bft_slot(Milli::ONE, slot_id)));
+    }
+
+    #[test]
+    pub fn hybrid_leadership_dispatches_per_slot() {
+        let (leadership, bft_leader_keys) = hybrid_leadership(10, Milli::HALF, 1);
+        let bft_leader_key = &bft_leader_keys[0];
+        let leader = bft_leader(bft_leader_key);
+
+        for slot_id in 0..10 {
+            let date = leadership.date_at_slot(slot_id);
+            let is_bft = is_bft_slot(Milli::HALF, slot_id);
+            let expected_version = if is_bft {
+                BlockVersion::Ed25519Signed
+            } else {
+                BlockVersion::KesVrfproof
+            };
+            assert_eq!(leadership.block_version_at(date), expected_version);
+
+            let output = leadership.is_leader_for_date(&leader, date).unwrap();
+            assert_eq!(is_bft, matches!(output, LeaderOutput::Bft(_)));
+
+            let verification = leadership.verify(&bft_header(date, bft_leader_key));
+            if is_bft {
+                assert!(verification.success());
+            } else {
+                match verification {
+                    Verification::Failure(error) => {
+                        assert_eq!(error.kind(), &ErrorKind::IncompatibleBlockVersion)
+                    }
+                    Verification::Success => panic!("BFT block accepted in a stake pool slot"),
+                }
+            }
+        }
+
+        let scheduled: Vec<u32> = leadership
+            .leader_schedule(&leader)
+            .unwrap()
+            .into_iter()
+            .map(|(date, _)| date.slot_id)
+            .collect();
+        assert_eq!(scheduled, vec![1, 3, 5, 7, 9]);
+    }
+
+    #[test]
+    pub fn hybrid_leadership_round_robin_goes_over_bft_slots() {
+        let (leadership, bft_leader_keys) = hybrid_leadership(10, Milli::HALF, 2);
+
+        // the BFT slots are 1, 3, 5, 7 and 9, led in turn by each leader
+        for (i, bft_leader_key) in bft_leader_keys.iter().enumerate() {
+            let leader = bft_leader(bft_leader_key);
+            let expected: Vec<u32> = (0..10)
+                .filter(|slot_id| is_bft_slot(Milli::HALF, *slot_id))
+                .enumerate()
+                .filter(|(index, _)| index % 2 == i)
+                .map(|(_, slot_id)| slot_id)
+                .collect();
+            let scheduled: Vec<u32> = leadership
+                .leader_schedule(&leader)
+                .unwrap()
+                .into_iter()
+                .map(|(date, _)| date.slot_id)
+                .collect();
+            assert_eq!(scheduled, expected);
+
+            for slot_id in (0..10).filter(|slot_id| is_bft_slot(Milli::HALF, *slot_id)) {
+                let date = leadership.date_at_slot(slot_id);
+                let is_leader = expected.contains(&slot_id);
+                let output = leadership.is_leader_for_date(&leader, date).unwrap();
+                assert_eq!(is_leader, matches!(output, LeaderOutput::Bft(_)));
+                let verification = leadership.verify(&bft_header(date, bft_leader_key));
+                assert_eq!(is_leader, verification.success());
+            }
+        }
+    }
+
+    #[test]
+    pub fn no_bft_slots_without_ratio() {
+        let (leadership, _) = hybrid_leadership(10, Milli::ZERO, 1);
+        assert!(matches!(
+            leadership.consensus(),
+            LeadershipConsensus::GenesisPraos(_)
+        ));
+        for slot_id in 0..10 {
+            assert_eq!(
+                leadership.block_version_at(leadership.date_at_slot(slot_id)),
+                BlockVersion::KesVrfproof
+            );
+        }
+    }
+}
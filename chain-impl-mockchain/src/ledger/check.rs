@@ -1,5 +1,7 @@
 use super::{Block0Error, Error};
 use crate::certificate;
+use crate::date::BlockDate;
+use crate::setting::Settings;
 use crate::transaction::*;
 use crate::value::Value;
 use chain_addr::Address;
@@ -158,6 +160,27 @@ pub(super) fn valid_transaction_ios_number<'a, P>(
     Ok(())
 }
 
+pub(super) fn valid_transaction_date<'a, P>(
+    tx: &TransactionSlice<'a, P>,
+    date: BlockDate,
+) -> Result<(), Error> {
+    let validity = tx.validity();
+    if_cond_fail_with!(
+        !validity.contains(&date),
+        Error::TransactionNotValidAtDate { date, validity }
+    )
+}
+
+pub(super) fn valid_metadata_size(metadata: &[u8], settings: &Settings) -> Result<(), Error> {
+    if_cond_fail_with!(
+        metadata.len() > settings.metadata_max_size as usize,
+        Error::MetadataTooBig {
+            size: metadata.len(),
+            max: settings.metadata_max_size,
+        }
+    )
+}
+
 #[cfg(test)]
 mod tests {
 
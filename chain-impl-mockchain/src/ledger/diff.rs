@@ -0,0 +1,75 @@
+use super::ledger::Ledger;
+use super::pots::Pots;
+use crate::account::Identifier;
+use crate::accounting::account::AccountState;
+use crate::certificate::PoolId;
+use crate::fragment::ConfigParams;
+use crate::legacy::OldAddress;
+use crate::multisig;
+use crate::stake::PoolState;
+use crate::utxo::Entry;
+use chain_addr::Address;
+use imhamt::Diff;
+
+/// Differences between two ledger states, as computed by `Ledger::diff`.
+///
+/// Pool additions are registrations, removals are retirements and changes
+/// are updates of the pool state (registration update, rewards...).
+#[derive(Debug, Clone)]
+pub struct LedgerDiff<'a> {
+    pub utxos_added: Vec<Entry<'a, Address>>,
+    pub utxos_removed: Vec<Entry<'a, Address>>,
+    pub old_utxos_removed: Vec<Entry<'a, OldAddress>>,
+    pub accounts: Vec<Diff<'a, Identifier, AccountState<()>>>,
+    pub multisig_accounts: Vec<Diff<'a, multisig::Identifier, AccountState<()>>>,
+    pub multisig_declarations: Vec<Diff<'a, multisig::Identifier, multisig::Declaration>>,
+    pub pools: Vec<Diff<'a, PoolId, PoolState>>,
+    /// old and new pots, if any of them changed
+    pub pots: Option<(&'a Pots, &'a Pots)>,
+    /// parameters to apply to the old settings to get the new ones
+    pub settings: ConfigParams,
+}
+
+impl<'a> LedgerDiff<'a> {
+    pub fn is_empty(&self) -> bool {
+        self.utxos_added.is_empty()
+            && self.utxos_removed.is_empty()
+            && self.old_utxos_removed.is_empty()
+            && self.accounts.is_empty()
+            && self.multisig_accounts.is_empty()
+            && self.multisig_declarations.is_empty()
+            && self.pools.is_empty()
+            && self.pots.is_none()
+            && self.settings.iter().next().is_none()
+    }
+}
+
+impl Ledger {
+    /// Compute the differences between this ledger and a newer one.
+    ///
+    /// The ledger structures are persistent, so a ledger derived from another
+    /// shares most of its state with it: the shared parts are skipped and
+    /// diffing the ledgers of consecutive blocks is proportional to the
+    /// changes made by the block, not to the size of the ledger.
+    pub fn diff<'a>(&'a self, new: &'a Ledger) -> LedgerDiff<'a> {
+        let (utxos_added, utxos_removed) = self.utxos.diff(&new.utxos);
+        // old utxos can only be declared in block0, and get spent afterward
+        let (_, old_utxos_removed) = self.oldutxos.diff(&new.oldutxos);
+        let pots = if self.pots != new.pots {
+            Some((&self.pots, &new.pots))
+        } else {
+            None
+        };
+        LedgerDiff {
+            utxos_added,
+            utxos_removed,
+            old_utxos_removed,
+            accounts: self.accounts.diff(&new.accounts),
+            multisig_accounts: self.multisig.diff_accounts(&new.multisig),
+            multisig_declarations: self.multisig.diff_declarations(&new.multisig),
+            pools: self.delegation.diff(&new.delegation),
+            pots,
+            settings: self.settings.diff(&new.settings),
+        }
+    }
+}
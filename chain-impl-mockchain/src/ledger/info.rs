@@ -17,6 +17,9 @@ impl Ledger {
             era: _,
             pots: _,
             leaders_log: _,
+            rewards_history: _,
+            stake_snapshots: _,
+            stake_aggregates: _,
         } = self;
 
         vec![
@@ -58,6 +61,9 @@ impl Ledger {
             era: era1,
             pots: pots1,
             leaders_log: leaders_log1,
+            rewards_history: rewards_history1,
+            stake_snapshots: stake_snapshots1,
+            stake_aggregates: stake_aggregates1,
         } = self;
 
         let Ledger {
@@ -74,6 +80,9 @@ impl Ledger {
             era: era2,
             pots: pots2,
             leaders_log: leaders_log2,
+            rewards_history: rewards_history2,
+            stake_snapshots: stake_snapshots2,
+            stake_aggregates: stake_aggregates2,
         } = other;
 
         vec![
@@ -90,6 +99,18 @@ impl Ledger {
             format!("era-same: {}", era1 == era2),
             format!("pots-same: {}", pots1 == pots2),
             format!("leaders-log-same: {}", leaders_log1 == leaders_log2),
+            format!(
+                "rewards-history-same: {}",
+                rewards_history1 == rewards_history2
+            ),
+            format!(
+                "stake-snapshots-same: {}",
+                stake_snapshots1 == stake_snapshots2
+            ),
+            format!(
+                "stake-aggregates-same: {}",
+                stake_aggregates1 == stake_aggregates2
+            ),
         ]
     }
 }
@@ -1,10 +1,12 @@
 use super::ledger::{Error, Ledger, LedgerStaticParameters};
 use super::pots::{self, Pots};
-use super::LeadersParticipationRecord;
+use super::{EpochRewardsHistory, LeadersParticipationRecord, RewardsHistory, StakeSnapshots};
 use crate::chaintypes::ChainLength;
 use crate::config::ConfigParam;
-use crate::date::BlockDate;
-use crate::stake::PoolsState;
+use crate::date::{BlockDate, Epoch};
+use crate::fragment::FragmentId;
+use crate::stake::{PoolError, PoolsState, StakeAggregates, StakeDistribution};
+use crate::transaction::{Output, TransactionIndex};
 use crate::{account, legacy, multisig, setting, update, utxo};
 use chain_addr::Address;
 use chain_time::TimeEra;
@@ -28,6 +30,12 @@ pub enum Entry<'a> {
             &'a crate::update::UpdateProposalState,
         ),
     ),
+    TreasuryTransferProposal(
+        (
+            &'a crate::update::UpdateProposalId,
+            &'a crate::update::TreasuryTransferProposalState,
+        ),
+    ),
     MultisigAccount(
         (
             &'a crate::multisig::Identifier,
@@ -42,6 +50,8 @@ pub enum Entry<'a> {
     ),
     StakePool((&'a crate::certificate::PoolId, &'a crate::stake::PoolState)),
     LeaderParticipation((&'a crate::certificate::PoolId, &'a u32)),
+    RewardsHistory((&'a Epoch, &'a EpochRewardsHistory)),
+    StakeSnapshot((&'a Epoch, &'a StakeDistribution)),
 }
 
 #[derive(Clone)]
@@ -63,6 +73,12 @@ pub enum EntryOwned {
             crate::update::UpdateProposalState,
         ),
     ),
+    TreasuryTransferProposal(
+        (
+            crate::update::UpdateProposalId,
+            crate::update::TreasuryTransferProposalState,
+        ),
+    ),
     MultisigAccount(
         (
             crate::multisig::Identifier,
@@ -72,6 +88,8 @@ pub enum EntryOwned {
     MultisigDeclaration((crate::multisig::Identifier, crate::multisig::Declaration)),
     StakePool((crate::certificate::PoolId, crate::stake::PoolState)),
     LeaderParticipation((crate::certificate::PoolId, u32)),
+    RewardsHistory((Epoch, EpochRewardsHistory)),
+    StakeSnapshot((Epoch, StakeDistribution)),
     StopEntry,
 }
 
@@ -103,6 +121,9 @@ impl EntryOwned {
             EntryOwned::UpdateProposal((proposal_id, proposal_state)) => {
                 Some(Entry::UpdateProposal((proposal_id, proposal_state)))
             }
+            EntryOwned::TreasuryTransferProposal((proposal_id, proposal_state)) => Some(
+                Entry::TreasuryTransferProposal((proposal_id, proposal_state)),
+            ),
             EntryOwned::MultisigAccount((identifier, account_state)) => {
                 Some(Entry::MultisigAccount((identifier, account_state)))
             }
@@ -115,6 +136,12 @@ impl EntryOwned {
             EntryOwned::LeaderParticipation((pool_id, participation)) => {
                 Some(Entry::LeaderParticipation((pool_id, participation)))
             }
+            EntryOwned::RewardsHistory((epoch, history)) => {
+                Some(Entry::RewardsHistory((epoch, history)))
+            }
+            EntryOwned::StakeSnapshot((epoch, distribution)) => {
+                Some(Entry::StakeSnapshot((epoch, distribution)))
+            }
             EntryOwned::StopEntry => None,
         }
     }
@@ -141,6 +168,13 @@ enum IterState<'a> {
             crate::update::UpdateProposalState,
         >,
     ),
+    TreasuryTransferProposals(
+        std::collections::btree_map::Iter<
+            'a,
+            crate::update::UpdateProposalId,
+            crate::update::TreasuryTransferProposalState,
+        >,
+    ),
     MultisigAccounts(crate::accounting::account::Iter<'a, crate::multisig::Identifier, ()>),
     MultisigDeclarations(
         imhamt::HamtIter<'a, crate::multisig::Identifier, crate::multisig::Declaration>,
@@ -148,6 +182,8 @@ enum IterState<'a> {
     StakePools(imhamt::HamtIter<'a, crate::certificate::PoolId, crate::stake::PoolState>),
     Pots(pots::Entries<'a>),
     LeaderParticipations(imhamt::HamtIter<'a, crate::certificate::PoolId, u32>),
+    RewardsHistory(imhamt::HamtIter<'a, Epoch, EpochRewardsHistory>),
+    StakeSnapshots(imhamt::HamtIter<'a, Epoch, Arc<StakeDistribution>>),
     Done,
 }
 
@@ -201,11 +237,20 @@ impl<'a> Iterator for LedgerIterator<'a> {
             }
             IterState::UpdateProposals(iter) => match iter.next() {
                 None => {
-                    self.state = IterState::MultisigAccounts(self.ledger.multisig.iter_accounts());
+                    self.state = IterState::TreasuryTransferProposals(
+                        self.ledger.updates.treasury_transfers.iter(),
+                    );
                     self.next()
                 }
                 Some(x) => Some(Entry::UpdateProposal(x)),
             },
+            IterState::TreasuryTransferProposals(iter) => match iter.next() {
+                None => {
+                    self.state = IterState::MultisigAccounts(self.ledger.multisig.iter_accounts());
+                    self.next()
+                }
+                Some(x) => Some(Entry::TreasuryTransferProposal(x)),
+            },
             IterState::MultisigAccounts(iter) => match iter.next() {
                 None => {
                     self.state =
@@ -237,11 +282,27 @@ impl<'a> Iterator for LedgerIterator<'a> {
             },
             IterState::LeaderParticipations(iter) => match iter.next() {
                 None => {
-                    self.state = IterState::Done;
+                    self.state = IterState::RewardsHistory(self.ledger.rewards_history.iter());
                     self.next()
                 }
                 Some(x) => Some(Entry::LeaderParticipation(x)),
             },
+            IterState::RewardsHistory(iter) => match iter.next() {
+                None => {
+                    self.state = IterState::StakeSnapshots(self.ledger.stake_snapshots.iter());
+                    self.next()
+                }
+                Some(x) => Some(Entry::RewardsHistory(x)),
+            },
+            IterState::StakeSnapshots(iter) => match iter.next() {
+                None => {
+                    self.state = IterState::Done;
+                    self.next()
+                }
+                Some((epoch, distribution)) => {
+                    Some(Entry::StakeSnapshot((epoch, distribution.as_ref())))
+                }
+            },
             IterState::Done => None,
         }
     }
@@ -256,94 +317,197 @@ impl Ledger {
     }
 }
 
-impl<'a> std::iter::FromIterator<Entry<'a>> for Result<Ledger, Error> {
-    fn from_iter<I: IntoIterator<Item = Entry<'a>>>(iter: I) -> Self {
-        let mut utxos = std::collections::HashMap::new();
-        let mut oldutxos = std::collections::HashMap::new();
-        let mut accounts = vec![];
-        let mut config_params = crate::fragment::ConfigParams::new();
-        let mut updates = update::UpdateState::new();
-        let mut multisig_accounts = vec![];
-        let mut multisig_declarations = vec![];
-        let delegation = PoolsState::new();
-        let mut globals = None;
-        let mut pots = Pots::zero();
-        let mut leaders_log = LeadersParticipationRecord::new();
+pub(super) type PendingOutputs<OutAddress> =
+    Option<(FragmentId, Vec<(TransactionIndex, Output<OutAddress>)>)>;
 
-        for entry in iter {
-            match entry {
-                Entry::Globals(globals2) => {
-                    globals = Some(globals2);
-                    // FIXME: check duplicate
-                }
-                Entry::Utxo(entry) => {
-                    utxos
-                        .entry(entry.fragment_id)
-                        .or_insert(vec![])
-                        .push((entry.output_index, entry.output.clone()));
-                }
-                Entry::OldUtxo(entry) => {
-                    oldutxos
-                        .entry(entry.fragment_id)
-                        .or_insert(vec![])
-                        .push((entry.output_index, entry.output.clone()));
-                }
-                Entry::Account((account_id, account_state)) => {
-                    accounts.push((account_id.clone(), account_state.clone()));
-                }
-                Entry::ConfigParam(param) => {
-                    config_params.push(param.clone());
-                }
-                Entry::UpdateProposal((proposal_id, proposal_state)) => {
-                    updates
-                        .proposals
-                        .insert(proposal_id.clone(), proposal_state.clone());
-                }
-                Entry::MultisigAccount((account_id, account_state)) => {
-                    multisig_accounts.push((account_id.clone(), account_state.clone()));
-                }
-                Entry::MultisigDeclaration((id, decl)) => {
-                    multisig_declarations.push((id.clone(), decl.clone()));
-                }
-                Entry::StakePool((pool_id, pool_state)) => {
-                    delegation
-                        .stake_pools
-                        .insert(pool_id.clone(), pool_state.clone())
-                        .unwrap();
-                }
-                Entry::Pot(ent) => pots.set_from_entry(&ent),
-                Entry::LeaderParticipation((pool_id, pool_participation)) => leaders_log
-                    .set_for(pool_id.clone(), *pool_participation)
-                    .unwrap(),
+/// Build a ledger from its entries, one entry at a time.
+///
+/// The entries are inserted in the ledger structures as they come, so a
+/// ledger can be restored from a stream without keeping all the entries in
+/// memory. The outputs of a transaction are expected to be consecutive, as
+/// yielded by the ledger iterator.
+pub(crate) struct LedgerLoader {
+    utxos: utxo::Ledger<Address>,
+    pending_utxos: PendingOutputs<Address>,
+    oldutxos: utxo::Ledger<legacy::OldAddress>,
+    pending_oldutxos: PendingOutputs<legacy::OldAddress>,
+    accounts: account::Ledger,
+    config_params: crate::fragment::ConfigParams,
+    updates: update::UpdateState,
+    multisig: multisig::Ledger,
+    delegation: PoolsState,
+    globals: Option<Globals>,
+    pots: Pots,
+    leaders_log: LeadersParticipationRecord,
+    rewards_history: RewardsHistory,
+    stake_snapshots: StakeSnapshots,
+}
+
+pub(super) fn push_output<OutAddress: Clone + std::hash::Hash + Eq>(
+    ledger: &mut utxo::Ledger<OutAddress>,
+    pending: &mut PendingOutputs<OutAddress>,
+    entry: utxo::Entry<'_, OutAddress>,
+) -> Result<(), Error> {
+    match pending {
+        Some((fragment_id, outputs)) if *fragment_id == entry.fragment_id => {
+            outputs.push((entry.output_index, entry.output.clone()));
+            Ok(())
+        }
+        _ => {
+            flush_outputs(ledger, pending)?;
+            *pending = Some((
+                entry.fragment_id,
+                vec![(entry.output_index, entry.output.clone())],
+            ));
+            Ok(())
+        }
+    }
+}
+
+pub(super) fn flush_outputs<OutAddress: Clone + std::hash::Hash + Eq>(
+    ledger: &mut utxo::Ledger<OutAddress>,
+    pending: &mut PendingOutputs<OutAddress>,
+) -> Result<(), Error> {
+    if let Some((fragment_id, outputs)) = pending.take() {
+        *ledger = ledger.add(&fragment_id, &outputs)?;
+    }
+    Ok(())
+}
+
+impl LedgerLoader {
+    pub fn new() -> Self {
+        LedgerLoader {
+            utxos: utxo::Ledger::new(),
+            pending_utxos: None,
+            oldutxos: utxo::Ledger::new(),
+            pending_oldutxos: None,
+            accounts: account::Ledger::new(),
+            config_params: crate::fragment::ConfigParams::new(),
+            updates: update::UpdateState::new(),
+            multisig: multisig::Ledger::new(),
+            delegation: PoolsState::new(),
+            globals: None,
+            pots: Pots::zero(),
+            leaders_log: LeadersParticipationRecord::new(),
+            rewards_history: RewardsHistory::new(),
+            stake_snapshots: StakeSnapshots::new(),
+        }
+    }
+
+    pub fn push(&mut self, entry: Entry<'_>) -> Result<(), Error> {
+        match entry {
+            Entry::Globals(globals) => {
+                self.globals = Some(globals);
+                // FIXME: check duplicate
+            }
+            Entry::Utxo(entry) => push_output(&mut self.utxos, &mut self.pending_utxos, entry)?,
+            Entry::OldUtxo(entry) => {
+                push_output(&mut self.oldutxos, &mut self.pending_oldutxos, entry)?
+            }
+            Entry::Account((account_id, account_state)) => {
+                self.accounts = self
+                    .accounts
+                    .set_state(account_id.clone(), account_state.clone());
+            }
+            Entry::ConfigParam(param) => {
+                self.config_params.push(param);
+            }
+            Entry::UpdateProposal((proposal_id, proposal_state)) => {
+                self.updates
+                    .proposals
+                    .insert(proposal_id.clone(), proposal_state.clone());
             }
+            Entry::TreasuryTransferProposal((proposal_id, proposal_state)) => {
+                self.updates
+                    .treasury_transfers
+                    .insert(proposal_id.clone(), proposal_state.clone());
+            }
+            Entry::MultisigAccount((account_id, account_state)) => {
+                self.multisig.accounts = self
+                    .multisig
+                    .accounts
+                    .set_state(account_id.clone(), account_state.clone());
+            }
+            Entry::MultisigDeclaration((id, decl)) => {
+                self.multisig.declarations = self
+                    .multisig
+                    .declarations
+                    .insert(id.clone(), decl.clone())
+                    .map_err(|_| multisig::LedgerError::AlreadyExist)?;
+            }
+            Entry::StakePool((pool_id, pool_state)) => {
+                self.delegation.stake_pools = self
+                    .delegation
+                    .stake_pools
+                    .insert(pool_id.clone(), pool_state.clone())
+                    .map_err(|_| PoolError::AlreadyExists(pool_id.clone()))?;
+            }
+            Entry::Pot(ent) => self.pots.set_from_entry(&ent),
+            Entry::LeaderParticipation((pool_id, pool_participation)) => self
+                .leaders_log
+                .set_for(pool_id.clone(), *pool_participation)
+                .map_err(|_| Error::DuplicatedLeaderParticipation {
+                    pool_id: pool_id.clone(),
+                })?,
+            Entry::RewardsHistory((epoch, history)) => self
+                .rewards_history
+                .set_for(*epoch, history.clone())
+                .map_err(|_| Error::DuplicatedRewardsHistory { epoch: *epoch })?,
+            Entry::StakeSnapshot((epoch, distribution)) => self
+                .stake_snapshots
+                .set_for(*epoch, distribution.clone())
+                .map_err(|_| Error::DuplicatedStakeSnapshot { epoch: *epoch })?,
         }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<Ledger, Error> {
+        flush_outputs(&mut self.utxos, &mut self.pending_utxos)?;
+        flush_outputs(&mut self.oldutxos, &mut self.pending_oldutxos)?;
 
-        let globals = globals.ok_or(Error::IncompleteLedger)?;
+        let globals = self.globals.ok_or(Error::IncompleteLedger)?;
+
+        // derived from the accounts, the stake pools and the utxos
+        let stake_aggregates =
+            StakeAggregates::new_with(&self.accounts, &self.delegation, &self.utxos);
 
         Ok(Ledger {
-            utxos: utxos.into_iter().collect(),
-            oldutxos: oldutxos.into_iter().collect(),
-            accounts: accounts.into_iter().collect(),
-            settings: setting::Settings::new().apply(&config_params)?,
-            updates,
-            multisig: multisig::Ledger::restore(multisig_accounts, multisig_declarations),
-            delegation,
+            utxos: self.utxos,
+            oldutxos: self.oldutxos,
+            accounts: self.accounts,
+            settings: setting::Settings::new().apply(&self.config_params)?,
+            updates: self.updates,
+            multisig: self.multisig,
+            delegation: self.delegation,
             static_params: Arc::new(globals.static_params),
             date: globals.date,
             chain_length: globals.chain_length,
             era: globals.era,
-            pots: pots,
-            leaders_log,
+            pots: self.pots,
+            leaders_log: self.leaders_log,
+            rewards_history: self.rewards_history,
+            stake_snapshots: self.stake_snapshots,
+            stake_aggregates,
         })
     }
 }
 
+impl<'a> std::iter::FromIterator<Entry<'a>> for Result<Ledger, Error> {
+    fn from_iter<I: IntoIterator<Item = Entry<'a>>>(iter: I) -> Self {
+        let mut loader = LedgerLoader::new();
+        for entry in iter {
+            loader.push(entry)?;
+        }
+        loader.finish()
+    }
+}
+
 #[cfg(any(test, feature = "property-test-api"))]
 mod tests {
     use super::*;
     use crate::{
         ledger::{Entry, Ledger},
-        testing::{ConfigBuilder, LedgerBuilder},
+        testing::{ConfigBuilder, LedgerBuilder, StakePoolBuilder},
         value::Value,
     };
 
@@ -408,6 +572,12 @@ mod tests {
                         id, state.proposal, state.proposal_date, state.votes
                     );
                 }
+                Entry::TreasuryTransferProposal((id, state)) => {
+                    println!(
+                        "TreasuryTransferProposal {} {} {} {:?}",
+                        id, state.proposal.transfer, state.proposal_date, state.votes
+                    );
+                }
                 Entry::MultisigAccount((id, state)) => {
                     println!(
                         "MultisigAccount {} {} {:?} {}",
@@ -438,6 +608,23 @@ mod tests {
                 Entry::LeaderParticipation((pool_id, pool_record)) => {
                     println!("LeaderParticipation {} {}", pool_id, pool_record);
                 }
+                Entry::RewardsHistory((epoch, history)) => {
+                    println!(
+                        "RewardsHistory {} {} {} {}",
+                        epoch,
+                        history.accounts().count(),
+                        history.multisig_accounts().count(),
+                        history.stake_pools().count(),
+                    );
+                }
+                Entry::StakeSnapshot((epoch, distribution)) => {
+                    println!(
+                        "StakeSnapshot {} {} {}",
+                        epoch,
+                        distribution.to_pools.len(),
+                        distribution.total_stake(),
+                    );
+                }
             }
         }
     }
@@ -458,4 +645,36 @@ mod tests {
 
         assert!(ledger == ledger2)
     }
+
+    #[test]
+    pub fn duplicated_entries_are_errors() {
+        let pool_id = StakePoolBuilder::new().build().id();
+        let history = EpochRewardsHistory::new(true);
+        let mut loader = LedgerLoader::new();
+
+        loader
+            .push(Entry::LeaderParticipation((&pool_id, &1)))
+            .unwrap();
+        assert_eq!(
+            loader.push(Entry::LeaderParticipation((&pool_id, &2))),
+            Err(Error::DuplicatedLeaderParticipation {
+                pool_id: pool_id.clone()
+            })
+        );
+
+        loader.push(Entry::RewardsHistory((&3, &history))).unwrap();
+        assert_eq!(
+            loader.push(Entry::RewardsHistory((&3, &history))),
+            Err(Error::DuplicatedRewardsHistory { epoch: 3 })
+        );
+
+        let distribution = StakeDistribution::empty();
+        loader
+            .push(Entry::StakeSnapshot((&4, &distribution)))
+            .unwrap();
+        assert_eq!(
+            loader.push(Entry::StakeSnapshot((&4, &distribution))),
+            Err(Error::DuplicatedStakeSnapshot { epoch: 4 })
+        );
+    }
 }
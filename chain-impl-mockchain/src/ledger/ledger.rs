@@ -5,6 +5,9 @@ use super::check::{self, TxVerifyError};
 use super::leaderlog::LeadersParticipationRecord;
 use super::pots::Pots;
 use super::reward_info::{EpochRewardsInfo, RewardsInfoParameters};
+use super::rewards_history::{EpochRewardsHistory, RewardsHistory};
+use super::stake_snapshots::StakeSnapshots;
+use super::verification::VerifiedWitnesses;
 use crate::certificate::PoolId;
 use crate::chaineval::HeaderContentEvalContext;
 use crate::chaintypes::{ChainLength, ConsensusType, HeaderId};
@@ -14,12 +17,16 @@ use crate::fee::{FeeAlgorithm, LinearFee};
 use crate::fragment::{BlockContentHash, BlockContentSize, Contents, Fragment, FragmentId};
 use crate::rewards;
 use crate::setting::ActiveSlotsCoeffError;
-use crate::stake::{PercentStake, PoolError, PoolStakeInformation, PoolsState, StakeDistribution};
+use crate::stake::{
+    PercentStake, PoolError, PoolStakeInformation, PoolsState, Stake, StakeAggregates,
+    StakeDistribution, StakeDistributionMismatch,
+};
 use crate::transaction::*;
 use crate::treasury::Treasury;
 use crate::value::*;
-use crate::{account, certificate, legacy, multisig, setting, stake, update, utxo};
+use crate::{account, certificate, legacy, multisig, setting, update, utxo};
 use chain_addr::{Address, Discrimination, Kind};
+use chain_core::property;
 use chain_crypto::Verification;
 use chain_time::Epoch as TimeEpoch;
 use chain_time::{SlotDuration, TimeEra, TimeFrame, Timeline};
@@ -79,6 +86,9 @@ pub struct Ledger {
     pub(crate) era: TimeEra,
     pub(crate) pots: Pots,
     pub(crate) leaders_log: LeadersParticipationRecord,
+    pub(crate) rewards_history: RewardsHistory,
+    pub(crate) stake_snapshots: StakeSnapshots,
+    pub(crate) stake_aggregates: StakeAggregates,
 }
 
 // Dummy implementation of Debug for Ledger
@@ -138,8 +148,14 @@ pub enum Block0Error {
     HasUpdateProposal,
     #[error("Update vote fragments are not valid in the block0")]
     HasUpdateVote,
+    #[error("Treasury transfer proposal fragments are not valid in the block0")]
+    HasTreasuryTransferProposal,
+    #[error("Stake pool vote fragments are not valid in the block0")]
+    HasPoolUpdateVote,
     #[error("Pool management are not valid in the block0")]
     HasPoolManagement,
+    #[error("Metadata transactions are not valid in the block0")]
+    HasMetadata,
 }
 
 pub type OutputOldAddress = Output<legacy::OldAddress>;
@@ -183,6 +199,13 @@ pub enum Error {
     },
     #[error("Transaction malformed")]
     TransactionMalformed(#[from] TxVerifyError),
+    #[error("Metadata of {size} bytes is bigger than the maximum of {max} bytes")]
+    MetadataTooBig { size: usize, max: u32 },
+    #[error("Transaction is not valid at {date}, validity interval is {validity:?}")]
+    TransactionNotValidAtDate {
+        date: BlockDate,
+        validity: ValidityInterval,
+    },
     #[error("Error while computing the fees")]
     FeeCalculationError(#[from] ValueError),
     #[error("Praos active slot coefficient invalid: {error}")]
@@ -240,6 +263,12 @@ pub enum Error {
     },
     #[error("Ledger cannot be reconstructed from serialized state because of missing entries")]
     IncompleteLedger,
+    #[error("Leader participation of the pool {pool_id} is present more than once")]
+    DuplicatedLeaderParticipation { pool_id: PoolId },
+    #[error("Rewards history of the epoch {epoch} is present more than once")]
+    DuplicatedRewardsHistory { epoch: Epoch },
+    #[error("Stake snapshot of the epoch {epoch} is present more than once")]
+    DuplicatedStakeSnapshot { epoch: Epoch },
     #[error("Ledger pot value invalid: {error}")]
     PotValueInvalid { error: ValueError },
     #[error("Pool registration with no owner")]
@@ -295,6 +324,9 @@ impl Ledger {
             era,
             pots,
             leaders_log: LeadersParticipationRecord::new(),
+            rewards_history: RewardsHistory::new(),
+            stake_snapshots: StakeSnapshots::new(),
+            stake_aggregates: StakeAggregates::new(),
         }
     }
 
@@ -329,6 +361,9 @@ impl Ledger {
                     }
                     ConfigParam::SlotDuration(d) => {
                         slot_duration = Some(*d);
+                        // also tracked in the settings, as the time frame of
+                        // the leader schedule is derived from them
+                        regular_ents.push(param.clone());
                     }
                     ConfigParam::SlotsPerEpoch(n) => {
                         slots_per_epoch = Some(*n);
@@ -403,6 +438,12 @@ impl Ledger {
                 Fragment::UpdateVote(_) => {
                     return Err(Error::Block0(Block0Error::HasUpdateVote));
                 }
+                Fragment::TreasuryTransferProposal(_) => {
+                    return Err(Error::Block0(Block0Error::HasTreasuryTransferProposal));
+                }
+                Fragment::PoolUpdateVote(_) => {
+                    return Err(Error::Block0(Block0Error::HasPoolUpdateVote));
+                }
                 Fragment::OwnerStakeDelegation(_) => {
                     return Err(Error::Block0(Block0Error::HasOwnerStakeDelegation));
                 }
@@ -422,10 +463,21 @@ impl Ledger {
                 Fragment::PoolUpdate(_) => {
                     return Err(Error::Block0(Block0Error::HasPoolManagement));
                 }
+                Fragment::MultisigDeclaration(tx) => {
+                    let tx = tx.as_slice();
+                    check::valid_block0_cert_transaction(&tx)?;
+                    ledger = ledger.apply_multisig_declaration(&tx.payload().into_payload())?;
+                }
+                Fragment::Metadata(_) => {
+                    return Err(Error::Block0(Block0Error::HasMetadata));
+                }
             }
         }
 
         ledger.validate_utxo_total_value()?;
+        ledger.stake_snapshots = ledger
+            .stake_snapshots
+            .record(0, ledger.get_stake_distribution());
         Ok(ledger)
     }
 
@@ -437,6 +489,9 @@ impl Ledger {
     ///
     /// * Reset the leaders log
     /// * Distribute the contribution (rewards + fees) to pools and their delegatees
+    ///
+    /// The `distribution` is expected to be the one the leaders of the ending epoch
+    /// were elected with, see `epoch_stake_distribution`.
     pub fn distribute_rewards<'a>(
         &'a self,
         distribution: &StakeDistribution,
@@ -445,6 +500,8 @@ impl Ledger {
     ) -> Result<(Self, EpochRewardsInfo), Error> {
         let mut new_ledger = self.clone();
         let mut rewards_info = EpochRewardsInfo::new(rewards_info_params);
+        let retention = self.settings.rewards_history_retention;
+        let mut rewards_history = EpochRewardsHistory::new(retention > 0);
 
         if self.leaders_log.total() == 0 {
             return Ok((new_ledger, rewards_info));
@@ -517,11 +574,29 @@ impl Ledger {
 
             let total_blocks = leaders_log.total();
             let reward_unit = total_reward.split_in(total_blocks);
+            let total_stake = distribution.get_total_stake();
+            let mut pools_total_reward = Value::zero();
 
             for (pool_id, pool_blocks) in leaders_log.iter() {
+                let pool_distribution = distribution.to_pools.get(pool_id);
+
+                // either split the reward by the number of blocks produced, or weight
+                // the pool's stake share by its performance (produced over expected blocks)
+                let pool_total_reward_uncapped =
+                    match ledger_params.reward_params.expected_epoch_blocks {
+                        None => reward_unit.parts.scale(*pool_blocks).unwrap(),
+                        Some(expected_epoch_blocks) => rewards::performance_weighted(
+                            total_reward,
+                            *pool_blocks,
+                            pool_distribution.map_or(Stake::zero(), |pd| pd.stake.total),
+                            total_stake,
+                            expected_epoch_blocks,
+                        ),
+                    };
+                pools_total_reward = (pools_total_reward + pool_total_reward_uncapped)?;
+
                 // possibly cap the reward for a given pool.
                 // if this is capped, then the overflow amount is send to treasury
-                let pool_total_reward_uncapped = reward_unit.parts.scale(*pool_blocks).unwrap();
                 let pool_total_reward = match pool_capper {
                     None => pool_total_reward_uncapped,
                     Some(pool_cap) => {
@@ -532,10 +607,31 @@ impl Ledger {
                     }
                 };
 
-                match distribution.to_pools.get(pool_id) {
+                // cap the reward of a saturated pool to what it would get at the
+                // saturation point. the overflow amount is also send to treasury
+                let pool_total_reward = match (
+                    ledger_params.reward_params.pool_saturation_target,
+                    pool_distribution,
+                ) {
+                    (Some(target), Some(pool_distribution)) => {
+                        let actual_pool_total = rewards::saturation_cap(
+                            pool_total_reward,
+                            pool_distribution.stake.total,
+                            total_stake,
+                            target,
+                        );
+                        let forfeited = (pool_total_reward - actual_pool_total).unwrap();
+                        new_ledger.pots.treasury_add(forfeited)?;
+                        actual_pool_total
+                    }
+                    _ => pool_total_reward,
+                };
+
+                match pool_distribution {
                     Some(pool_distribution) => {
                         new_ledger.distribute_poolid_rewards(
                             &mut rewards_info,
+                            &mut rewards_history,
                             epoch,
                             &pool_id,
                             pool_total_reward,
@@ -549,9 +645,11 @@ impl Ledger {
                 }
             }
 
-            if reward_unit.remaining > Value::zero() {
-                // if anything remaining, put it in treasury
-                new_ledger.pots.treasury_add(reward_unit.remaining)?;
+            // if anything remaining (splitting leftover or rewards not earned
+            // by underperforming pools), put it in treasury
+            let remaining = (total_reward - pools_total_reward)?;
+            if remaining > Value::zero() {
+                new_ledger.pots.treasury_add(remaining)?;
             }
         }
 
@@ -559,12 +657,29 @@ impl Ledger {
             (new_ledger.pots.treasury_value() - treasury_initial_value).unwrap();
         rewards_info.set_treasury(treasury_added_value);
 
+        new_ledger.rewards_history =
+            new_ledger
+                .rewards_history
+                .record(epoch, rewards_history, retention);
+
         Ok((new_ledger, rewards_info))
     }
 
+    /// Distribute the rewards of the ending epoch with the stake distribution
+    /// its leaders were elected with, see `distribute_rewards`.
+    pub fn distribute_epoch_rewards(
+        &self,
+        ledger_params: &LedgerParameters,
+        rewards_info_params: RewardsInfoParameters,
+    ) -> Result<(Self, EpochRewardsInfo), Error> {
+        let distribution = self.epoch_stake_distribution(self.date.epoch);
+        self.distribute_rewards(&distribution, ledger_params, rewards_info_params)
+    }
+
     fn distribute_poolid_rewards(
         &mut self,
         reward_info: &mut EpochRewardsInfo,
+        rewards_history: &mut EpochRewardsHistory,
         epoch: Epoch,
         pool_id: &PoolId,
         total_reward: Value,
@@ -581,6 +696,7 @@ impl Ledger {
         let distr = rewards::tax_cut(total_reward, &reg.rewards).unwrap();
 
         reward_info.set_stake_pool(pool_id, distr.taxed, distr.after_tax);
+        rewards_history.set_stake_pool(pool_id, distr.taxed, distr.after_tax);
         self.delegation
             .stake_pool_set_rewards(pool_id, epoch, distr.taxed, distr.after_tax)?;
 
@@ -594,9 +710,28 @@ impl Ledger {
                         distr.taxed,
                         (),
                     )?;
+                    self.update_account_stake(&single_account)?;
                     reward_info.add_to_account(&single_account, distr.taxed);
+                    rewards_history.add_to_account(&single_account, distr.taxed);
+                }
+                AccountIdentifier::Multi(multi_account) => {
+                    match self
+                        .multisig
+                        .add_rewards_to_account(&multi_account, epoch, distr.taxed)
+                    {
+                        Ok(multisig) => {
+                            self.multisig = multisig;
+                            reward_info.add_to_multisig_account(&multi_account, distr.taxed);
+                            rewards_history.add_to_multisig_account(&multi_account, distr.taxed);
+                        }
+                        // a multisig account cannot be created without its declaration,
+                        // so the rewards of an undeclared account go to the treasury
+                        Err(multisig::LedgerError::DoesntExist) => {
+                            self.pots.treasury_add(distr.taxed)?
+                        }
+                        Err(error) => return Err(error.into()),
+                    }
                 }
-                AccountIdentifier::Multi(_multi_account) => unimplemented!(),
             },
             None => {
                 if reg.owners.len() > 1 {
@@ -606,7 +741,9 @@ impl Ledger {
                         self.accounts =
                             self.accounts
                                 .add_rewards_to_account(&id, epoch, splitted.parts, ())?;
+                        self.update_account_stake(&id)?;
                         reward_info.add_to_account(&id, splitted.parts);
+                        rewards_history.add_to_account(&id, splitted.parts);
                     }
                     // pool owners 0 get potentially an extra sweetener of value 1 to #owners - 1
                     if splitted.remaining > Value::zero() {
@@ -617,14 +754,18 @@ impl Ledger {
                             splitted.remaining,
                             (),
                         )?;
+                        self.update_account_stake(&id)?;
                         reward_info.add_to_account(&id, splitted.remaining);
+                        rewards_history.add_to_account(&id, splitted.remaining);
                     }
                 } else {
                     let id = reg.owners[0].clone().into();
                     self.accounts =
                         self.accounts
                             .add_rewards_to_account(&id, epoch, distr.taxed, ())?;
+                    self.update_account_stake(&id)?;
                     reward_info.add_to_account(&id, distr.taxed);
+                    rewards_history.add_to_account(&id, distr.taxed);
                 }
             }
         }
@@ -639,7 +780,9 @@ impl Ledger {
                 self.accounts = self
                     .accounts
                     .add_rewards_to_account(account, epoch, r, ())?;
+                self.update_account_stake(account)?;
                 reward_info.add_to_account(account, r);
+                rewards_history.add_to_account(account, r);
             }
         }
 
@@ -650,67 +793,124 @@ impl Ledger {
         Ok(())
     }
 
-    /// Try to apply messages to a State, and return the new State if successful
-    pub fn apply_block(
+    /// Check the block contents and metadata against the current state,
+    /// returning every failed check.
+    pub(super) fn check_block(
         &self,
         ledger_params: &LedgerParameters,
         contents: &Contents,
         metadata: &HeaderContentEvalContext,
-    ) -> Result<Self, Error> {
-        let mut new_ledger = self.clone();
-
-        new_ledger.chain_length = self.chain_length.increase();
-
+    ) -> Vec<Error> {
+        let mut errors = Vec::new();
         let (content_hash, content_size) = contents.compute_hash_size();
 
         if content_size > ledger_params.block_content_max_size {
-            return Err(Error::InvalidContentSize {
+            errors.push(Error::InvalidContentSize {
                 actual: content_size,
                 max: ledger_params.block_content_max_size,
             });
         }
 
         if content_hash != metadata.content_hash {
-            return Err(Error::InvalidContentHash {
+            errors.push(Error::InvalidContentHash {
                 actual: content_hash,
                 expected: metadata.content_hash,
             });
         }
 
         // Check if the metadata (date/heigth) check out compared to the current state
-        if metadata.chain_length != new_ledger.chain_length {
-            return Err(Error::WrongChainLength {
+        let chain_length = self.chain_length.increase();
+        if metadata.chain_length != chain_length {
+            errors.push(Error::WrongChainLength {
                 actual: metadata.chain_length,
-                expected: new_ledger.chain_length,
+                expected: chain_length,
             });
         }
 
-        if metadata.block_date <= new_ledger.date {
-            return Err(Error::NonMonotonicDate {
+        if metadata.block_date <= self.date {
+            errors.push(Error::NonMonotonicDate {
                 block_date: metadata.block_date,
-                chain_date: new_ledger.date,
+                chain_date: self.date,
             });
         }
 
-        // double check that if we had an epoch transition, distribute_rewards has been called
+        errors
+    }
+
+    /// Start a new block: tally the votes and process the update proposals,
+    /// before the fragments of the block get applied.
+    pub(super) fn begin_block(&self, metadata: &HeaderContentEvalContext) -> Result<Self, Error> {
+        let mut new_ledger = self.clone();
+
+        new_ledger.chain_length = self.chain_length.increase();
+
         if metadata.block_date.epoch > new_ledger.date.epoch {
-            if self.leaders_log.total() > 0 {
-                panic!("internal error: apply_block called after epoch transition, but distribute_rewards has not been called")
+            // Tally the stake pools votes with the stake distribution of the ending
+            // epoch, so stake moved right before the epoch boundary has no say
+            if new_ledger.updates.has_pool_votes() {
+                let distribution = new_ledger.epoch_stake_distribution(new_ledger.date.epoch);
+                new_ledger.updates = new_ledger.updates.tally_pool_votes(&distribution);
             }
+
+            new_ledger.stake_snapshots = new_ledger.stake_snapshots.record(
+                metadata.block_date.epoch,
+                new_ledger.get_stake_distribution(),
+            );
         }
 
         // Process Update proposals if needed
-        let (updates, settings) = new_ledger.updates.process_proposals(
+        let (updates, settings, treasury_transfers) = new_ledger.updates.process_proposals(
             new_ledger.settings,
             new_ledger.date,
             metadata.block_date,
         )?;
         new_ledger.updates = updates;
         new_ledger.settings = settings;
+        for (proposal_id, proposal) in treasury_transfers {
+            new_ledger = new_ledger.apply_treasury_transfer(proposal_id, &proposal)?;
+        }
+
+        Ok(new_ledger)
+    }
+
+    /// Try to apply messages to a State, and return the new State if successful
+    pub fn apply_block(
+        &self,
+        ledger_params: &LedgerParameters,
+        contents: &Contents,
+        metadata: &HeaderContentEvalContext,
+    ) -> Result<Self, Error> {
+        if let Some(error) = self
+            .check_block(ledger_params, contents, metadata)
+            .into_iter()
+            .next()
+        {
+            return Err(error);
+        }
+
+        // double check that if we had an epoch transition, distribute_rewards has been called
+        if metadata.block_date.epoch > self.date.epoch {
+            if self.leaders_log.total() > 0 {
+                panic!("internal error: apply_block called after epoch transition, but distribute_rewards has not been called")
+            }
+        }
+
+        // with parallel verification the signatures are checked ahead, on a thread pool
+        #[cfg(feature = "parallel-verification")]
+        let verified_witnesses = self.verify_witnesses(contents);
+        #[cfg(not(feature = "parallel-verification"))]
+        let verified_witnesses = VerifiedWitnesses::default();
+
+        let mut new_ledger = self.begin_block(metadata)?;
 
         // Apply all the fragments
         for content in contents.iter() {
-            new_ledger = new_ledger.apply_fragment(ledger_params, content, metadata.block_date)?;
+            new_ledger = new_ledger.apply_fragment_verified(
+                ledger_params,
+                content,
+                metadata.block_date,
+                &verified_witnesses,
+            )?;
         }
 
         // Update the ledger metadata related to eval context
@@ -741,6 +941,23 @@ impl Ledger {
         ledger_params: &LedgerParameters,
         content: &Fragment,
         block_date: BlockDate,
+    ) -> Result<Self, Error> {
+        self.apply_fragment_verified(
+            ledger_params,
+            content,
+            block_date,
+            &VerifiedWitnesses::default(),
+        )
+    }
+
+    /// Same as `apply_fragment`, skipping the signature checks of the
+    /// witnesses already verified
+    pub(super) fn apply_fragment_verified(
+        &self,
+        ledger_params: &LedgerParameters,
+        content: &Fragment,
+        block_date: BlockDate,
+        verified_witnesses: &VerifiedWitnesses,
     ) -> Result<Self, Error> {
         let mut new_ledger = self.clone();
 
@@ -750,14 +967,21 @@ impl Ledger {
             Fragment::OldUtxoDeclaration(_) => return Err(Error::Block0OnlyFragmentReceived),
             Fragment::Transaction(tx) => {
                 let tx = tx.as_slice();
-                let (new_ledger_, _fee) =
-                    new_ledger.apply_transaction(&fragment_id, &tx, &ledger_params)?;
+                let (new_ledger_, _fee) = new_ledger.apply_transaction_verified(
+                    &fragment_id,
+                    &tx,
+                    &ledger_params,
+                    verified_witnesses,
+                )?;
                 new_ledger = new_ledger_;
             }
             Fragment::OwnerStakeDelegation(tx) => {
                 let tx = tx.as_slice();
-                let (new_ledger_, _fee) =
-                    new_ledger.apply_owner_stake_delegation(&tx, &ledger_params)?;
+                let (new_ledger_, _fee) = new_ledger.apply_owner_stake_delegation_verified(
+                    &tx,
+                    &ledger_params,
+                    verified_witnesses,
+                )?;
                 new_ledger = new_ledger_;
             }
             Fragment::StakeDelegation(tx) => {
@@ -783,14 +1007,24 @@ impl Ledger {
                     return Err(Error::StakeDelegationSignatureFailed);
                 }
 
-                let (new_ledger_, _fee) =
-                    new_ledger.apply_transaction(&fragment_id, &tx, &ledger_params)?;
+                let (new_ledger_, _fee) = new_ledger.apply_transaction_verified(
+                    &fragment_id,
+                    &tx,
+                    &ledger_params,
+                    verified_witnesses,
+                )?;
                 new_ledger = new_ledger_.apply_stake_delegation(&payload)?;
             }
             Fragment::PoolRegistration(tx) => {
                 let tx = tx.as_slice();
-                let (new_ledger_, _fee) =
-                    new_ledger.apply_transaction(&fragment_id, &tx, &ledger_params)?;
+                let deposit = new_ledger.settings.pool_deposit;
+                let (new_ledger_, _fee) = new_ledger.apply_transaction_with_deposit(
+                    &fragment_id,
+                    &tx,
+                    &ledger_params,
+                    deposit,
+                    verified_witnesses,
+                )?;
                 new_ledger = new_ledger_.apply_pool_registration_signcheck(
                     &tx.payload().into_payload(),
                     &tx.transaction_binding_auth_data(),
@@ -800,8 +1034,12 @@ impl Ledger {
             Fragment::PoolRetirement(tx) => {
                 let tx = tx.as_slice();
 
-                let (new_ledger_, _fee) =
-                    new_ledger.apply_transaction(&fragment_id, &tx, &ledger_params)?;
+                let (new_ledger_, _fee) = new_ledger.apply_transaction_verified(
+                    &fragment_id,
+                    &tx,
+                    &ledger_params,
+                    verified_witnesses,
+                )?;
                 new_ledger = new_ledger_.apply_pool_retirement(
                     &tx.payload().into_payload(),
                     &tx.transaction_binding_auth_data(),
@@ -811,8 +1049,12 @@ impl Ledger {
             Fragment::PoolUpdate(tx) => {
                 let tx = tx.as_slice();
 
-                let (new_ledger_, _fee) =
-                    new_ledger.apply_transaction(&fragment_id, &tx, &ledger_params)?;
+                let (new_ledger_, _fee) = new_ledger.apply_transaction_verified(
+                    &fragment_id,
+                    &tx,
+                    &ledger_params,
+                    verified_witnesses,
+                )?;
                 new_ledger = new_ledger_.apply_pool_update(
                     &tx.payload().into_payload(),
                     &tx.transaction_binding_auth_data(),
@@ -832,25 +1074,102 @@ impl Ledger {
                 }
                 new_ledger = new_ledger.apply_update_vote(&vote)?;
             }
+            Fragment::TreasuryTransferProposal(proposal) => {
+                if true {
+                    return Err(Error::UpdateNotAllowedYet);
+                }
+                new_ledger = new_ledger.apply_treasury_transfer_proposal(
+                    fragment_id,
+                    &proposal,
+                    block_date,
+                )?;
+            }
+            Fragment::PoolUpdateVote(vote) => {
+                if true {
+                    return Err(Error::UpdateNotAllowedYet);
+                }
+                new_ledger = new_ledger.apply_pool_update_vote(&vote)?;
+            }
+            Fragment::MultisigDeclaration(tx) => {
+                let tx = tx.as_slice();
+                let (new_ledger_, _fee) = new_ledger.apply_transaction_verified(
+                    &fragment_id,
+                    &tx,
+                    &ledger_params,
+                    verified_witnesses,
+                )?;
+                new_ledger =
+                    new_ledger_.apply_multisig_declaration(&tx.payload().into_payload())?;
+            }
+            Fragment::Metadata(tx) => {
+                let tx = tx.as_slice();
+                check::valid_metadata_size(tx.metadata(), &new_ledger.settings)?;
+                let (new_ledger_, _fee) = new_ledger.apply_transaction_verified(
+                    &fragment_id,
+                    &tx,
+                    &ledger_params,
+                    verified_witnesses,
+                )?;
+                new_ledger = new_ledger_;
+            }
         }
 
         Ok(new_ledger)
     }
 
     pub fn apply_transaction<'a, Extra>(
+        self,
+        fragment_id: &FragmentId,
+        tx: &TransactionSlice<'a, Extra>,
+        dyn_params: &LedgerParameters,
+    ) -> Result<(Self, Value), Error>
+    where
+        Extra: Payload,
+        LinearFee: FeeAlgorithm,
+    {
+        self.apply_transaction_verified(fragment_id, tx, dyn_params, &VerifiedWitnesses::default())
+    }
+
+    fn apply_transaction_verified<'a, Extra>(
+        self,
+        fragment_id: &FragmentId,
+        tx: &TransactionSlice<'a, Extra>,
+        dyn_params: &LedgerParameters,
+        verified_witnesses: &VerifiedWitnesses,
+    ) -> Result<(Self, Value), Error>
+    where
+        Extra: Payload,
+        LinearFee: FeeAlgorithm,
+    {
+        self.apply_transaction_with_deposit(
+            fragment_id,
+            tx,
+            dyn_params,
+            Value::zero(),
+            verified_witnesses,
+        )
+    }
+
+    /// Apply a transaction whose inputs also pay a deposit, on top of the
+    /// fee. The caller is responsible for locking the deposit.
+    fn apply_transaction_with_deposit<'a, Extra>(
         mut self,
         fragment_id: &FragmentId,
         tx: &TransactionSlice<'a, Extra>,
         dyn_params: &LedgerParameters,
+        deposit: Value,
+        verified_witnesses: &VerifiedWitnesses,
     ) -> Result<(Self, Value), Error>
     where
         Extra: Payload,
         LinearFee: FeeAlgorithm,
     {
         check::valid_transaction_ios_number(tx)?;
+        check::valid_transaction_date(tx, self.date)?;
         let fee = calculate_fee(tx, dyn_params);
-        tx.verify_strictly_balanced(fee)?;
-        self = self.apply_tx_inputs(tx)?;
+        let fee_and_deposit = (fee + deposit).map_err(BalanceError::OutputsTotalFailed)?;
+        tx.verify_strictly_balanced(fee_and_deposit)?;
+        self = self.apply_tx_inputs(tx, verified_witnesses)?;
         self = self.apply_tx_outputs(*fragment_id, tx.outputs())?;
         self = self.apply_tx_fee(fee)?;
         Ok((self, fee))
@@ -878,6 +1197,53 @@ impl Ledger {
         Ok(self)
     }
 
+    pub fn apply_pool_update_vote(
+        mut self,
+        vote: &update::SignedPoolUpdateVote,
+    ) -> Result<Self, Error> {
+        let reg = self.delegation.stake_pool_get(&vote.vote.pool_id)?;
+        self.updates = self.updates.apply_pool_vote(vote, reg, &self.settings)?;
+        Ok(self)
+    }
+
+    pub fn apply_treasury_transfer_proposal(
+        mut self,
+        proposal_id: update::UpdateProposalId,
+        proposal: &update::SignedTreasuryTransferProposal,
+        cur_date: BlockDate,
+    ) -> Result<Self, Error> {
+        // the transfer is checked now, so that it can't fail once accepted
+        let transfer = &proposal.proposal.proposal.transfer;
+        check::valid_output_value(transfer)?;
+        if transfer.address.discrimination() != self.static_params.discrimination {
+            return Err(Error::InvalidDiscrimination);
+        }
+
+        self.updates = self.updates.apply_treasury_transfer_proposal(
+            proposal_id,
+            proposal,
+            &self.settings,
+            cur_date,
+        )?;
+        Ok(self)
+    }
+
+    /// Pay an accepted treasury transfer. If the treasury doesn't hold
+    /// enough value anymore, the transfer is dropped instead of failing
+    /// the block, as the proposal has already been accepted.
+    fn apply_treasury_transfer(
+        mut self,
+        proposal_id: update::UpdateProposalId,
+        proposal: &update::TreasuryTransferProposal,
+    ) -> Result<Self, Error> {
+        let transfer = &proposal.transfer;
+        if self.pots.treasury_value() < transfer.value {
+            return Ok(self);
+        }
+        self.pots.treasury_withdraw(transfer.value)?;
+        self.apply_outputs(proposal_id, std::iter::once(transfer.clone()))
+    }
+
     pub fn apply_pool_registration_signcheck<'a>(
         self,
         cert: &certificate::PoolRegistration,
@@ -891,16 +1257,39 @@ impl Ledger {
             return Err(Error::PoolRetirementSignatureFailed);
         }
 
-        self.apply_pool_registration(cert)
+        let deposit = self.settings.pool_deposit;
+        self.apply_pool_registration_with_deposit(cert, deposit)
     }
 
     pub fn apply_pool_registration(
+        self,
+        cert: &certificate::PoolRegistration,
+    ) -> Result<Self, Error> {
+        self.apply_pool_registration_with_deposit(cert, Value::zero())
+    }
+
+    /// Register a stake pool, locking the deposit paid by its registration
+    /// transaction until the pool retires
+    fn apply_pool_registration_with_deposit(
         mut self,
         cert: &certificate::PoolRegistration,
+        deposit: Value,
     ) -> Result<Self, Error> {
         check::valid_pool_registration_certificate(cert)?;
 
-        self.delegation = self.delegation.register_stake_pool(cert.clone())?;
+        self.delegation = self
+            .delegation
+            .register_stake_pool_with_deposit(cert.clone(), deposit)?;
+        self.stake_aggregates = self.stake_aggregates.register_pool(&cert.to_id());
+        self.pots.lock_deposit(deposit)?;
+        Ok(self)
+    }
+
+    pub fn apply_multisig_declaration(
+        mut self,
+        cert: &certificate::MultisigDeclaration,
+    ) -> Result<Self, Error> {
+        self.multisig = self.multisig.add_account(&cert.declaration)?;
         Ok(self)
     }
 
@@ -913,15 +1302,58 @@ impl Ledger {
         check::valid_pool_retirement_certificate(auth_cert)?;
         check::valid_pool_signature(&sig)?;
 
-        let reg = self.delegation.stake_pool_get(&auth_cert.pool_id)?;
-        if sig.verify(reg, bad) == Verification::Failed {
+        let state = self
+            .delegation
+            .stake_pool_get_state(&auth_cert.pool_id)?
+            .clone();
+        if sig.verify(&state.registration, bad) == Verification::Failed {
             return Err(Error::PoolRetirementSignatureFailed);
         }
 
         self.delegation = self.delegation.deregister_stake_pool(&auth_cert.pool_id)?;
+        self.stake_aggregates = self.stake_aggregates.retire_pool(&auth_cert.pool_id);
+        self.refund_pool_deposit(&state.registration, state.deposit)?;
         Ok(self)
     }
 
+    /// Return the deposit of a retired pool to its reward account, or to its
+    /// owners, as for the pool rewards
+    fn refund_pool_deposit(
+        &mut self,
+        reg: &certificate::PoolRegistration,
+        deposit: Value,
+    ) -> Result<(), Error> {
+        if deposit == Value::zero() {
+            return Ok(());
+        }
+        self.pots.release_deposit(deposit)?;
+        match &reg.reward_account {
+            Some(AccountIdentifier::Single(account)) => {
+                self.add_value_or_create_account(account, deposit)?;
+            }
+            Some(AccountIdentifier::Multi(account)) => {
+                // a multisig account cannot be created without its declaration,
+                // so the deposit of an undeclared account goes to the treasury
+                if self.multisig.exists(account) {
+                    self.multisig = self.multisig.add_value(account, deposit)?;
+                } else {
+                    self.pots.treasury_add(deposit)?;
+                }
+            }
+            None => {
+                let splitted = deposit.split_in(reg.owners.len() as u32);
+                for owner in &reg.owners {
+                    self.add_value_or_create_account(&owner.clone().into(), splitted.parts)?;
+                }
+                if splitted.remaining > Value::zero() {
+                    let id = reg.owners[0].clone().into();
+                    self.add_value_or_create_account(&id, splitted.remaining)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn apply_pool_update<'a>(
         mut self,
         auth_cert: &certificate::PoolUpdate,
@@ -970,13 +1402,23 @@ impl Ledger {
             .to_single_account()
             .ok_or(Error::AccountIdentifierInvalid)?;
         self.accounts = self.accounts.set_delegation(&account_key, delegation)?;
+        self.update_account_stake(&account_key)?;
         Ok(self)
     }
 
     pub fn apply_owner_stake_delegation<'a>(
+        self,
+        tx: &TransactionSlice<'a, certificate::OwnerStakeDelegation>,
+        dyn_params: &LedgerParameters,
+    ) -> Result<(Self, Value), Error> {
+        self.apply_owner_stake_delegation_verified(tx, dyn_params, &VerifiedWitnesses::default())
+    }
+
+    fn apply_owner_stake_delegation_verified<'a>(
         mut self,
         tx: &TransactionSlice<'a, certificate::OwnerStakeDelegation>,
         dyn_params: &LedgerParameters,
+        verified_witnesses: &VerifiedWitnesses,
     ) -> Result<(Self, Value), Error> {
         let sign_data_hash = tx.transaction_sign_data_hash();
 
@@ -1012,11 +1454,13 @@ impl Ledger {
                     &account_id,
                     witness,
                     value,
+                    verified_witnesses,
                 )?;
                 self.accounts = single.set_delegation(
                     &account_id,
                     tx.payload().into_payload().get_delegation_type(),
                 )?;
+                self.update_account_stake(&account_id)?;
             }
             MatchingIdentifierWitness::Multi(account_id, witness) => {
                 let multi = input_multi_account_verify(
@@ -1026,6 +1470,7 @@ impl Ledger {
                     &account_id,
                     witness,
                     value,
+                    verified_witnesses,
                 )?;
                 self.multisig = multi.set_delegation(
                     &account_id,
@@ -1038,7 +1483,42 @@ impl Ledger {
     }
 
     pub fn get_stake_distribution(&self) -> StakeDistribution {
-        stake::get_distribution(&self.accounts, &self.delegation, &self.utxos)
+        self.stake_aggregates.distribution(&self.delegation)
+    }
+
+    /// Check the incrementally maintained stake distribution against a full
+    /// recompute from the accounts and the utxos.
+    ///
+    /// This is costly, and is meant to be used in tests.
+    pub fn check_stake_distribution(&self) -> Result<(), StakeDistributionMismatch> {
+        self.stake_aggregates
+            .check(&self.accounts, &self.delegation, &self.utxos)
+    }
+
+    /// Update the stake aggregates after a change to the value or the
+    /// delegation of an account
+    fn update_account_stake(&mut self, account: &account::Identifier) -> Result<(), Error> {
+        let state = self.accounts.get_state(account)?;
+        self.stake_aggregates =
+            self.stake_aggregates
+                .set_account(account, state.delegation(), state.value());
+        Ok(())
+    }
+
+    /// The stake distribution the leaders of `epoch` are elected with, and
+    /// rewarded for: the stake at the end of `epoch - 2`.
+    ///
+    /// Falls back to the current stake distribution when no snapshot is
+    /// available for this epoch.
+    pub fn epoch_stake_distribution(&self, epoch: Epoch) -> StakeDistribution {
+        match self.stake_snapshots.for_leader_election(epoch) {
+            Some(distribution) => distribution.clone(),
+            None => self.get_stake_distribution(),
+        }
+    }
+
+    pub fn stake_snapshots(&self) -> &StakeSnapshots {
+        &self.stake_snapshots
     }
 
     /// access the ledger static parameters
@@ -1051,13 +1531,17 @@ impl Ledger {
     }
 
     pub fn get_ledger_parameters(&self) -> LedgerParameters {
+        let mut reward_params = self.settings.to_reward_params();
+        reward_params.expected_epoch_blocks = self
+            .settings
+            .expected_epoch_blocks(self.era.slots_per_epoch());
         LedgerParameters {
             fees: *self.settings.linear_fees,
             treasury_tax: self
                 .settings
                 .treasury_params
                 .unwrap_or_else(|| rewards::TaxType::zero()),
-            reward_params: self.settings.to_reward_params(),
+            reward_params,
             block_content_max_size: self.settings.block_content_max_size,
             epoch_stability_depth: self.settings.epoch_stability_depth,
             fees_goes_to: self.settings.fees_goes_to,
@@ -1082,6 +1566,29 @@ impl Ledger {
         self.utxos.iter()
     }
 
+    /// Maintain an index of the UTXOs by address, for `utxos_by_address`.
+    ///
+    /// The index is kept along the following ledgers, but it is not part of
+    /// the ledger serialization and has to be enabled again on recovery.
+    pub fn with_utxo_address_index(mut self) -> Self {
+        self.utxos = self.utxos.with_address_index();
+        self
+    }
+
+    pub fn without_utxo_address_index(mut self) -> Self {
+        self.utxos = self.utxos.without_address_index();
+        self
+    }
+
+    /// Get the UTXOs of an address, or `None` if the ledger doesn't
+    /// maintain the address index
+    pub fn utxos_by_address<'a>(
+        &'a self,
+        address: &Address,
+    ) -> Option<Vec<utxo::Entry<'a, Address>>> {
+        self.utxos.get_by_address(address)
+    }
+
     pub fn chain_length(&self) -> ChainLength {
         self.chain_length
     }
@@ -1133,12 +1640,18 @@ impl Ledger {
     fn apply_tx_inputs<'a, Extra: Payload>(
         mut self,
         tx: &TransactionSlice<'a, Extra>,
+        verified_witnesses: &VerifiedWitnesses,
     ) -> Result<Self, Error> {
         let sign_data_hash = tx.transaction_sign_data_hash();
         for (input, witness) in tx.inputs_and_witnesses().iter() {
             match input.to_enum() {
                 InputEnum::UtxoInput(utxo) => {
-                    self = self.apply_input_to_utxo(&sign_data_hash, &utxo, &witness)?
+                    self = self.apply_input_to_utxo(
+                        &sign_data_hash,
+                        &utxo,
+                        &witness,
+                        verified_witnesses,
+                    )?
                 }
                 InputEnum::AccountInput(account_id, value) => {
                     match match_identifier_witness(&account_id, &witness)? {
@@ -1150,7 +1663,9 @@ impl Ledger {
                                 &account_id,
                                 witness,
                                 value,
-                            )?
+                                verified_witnesses,
+                            )?;
+                            self.update_account_stake(&account_id)?;
                         }
                         MatchingIdentifierWitness::Multi(account_id, witness) => {
                             self.multisig = input_multi_account_verify(
@@ -1160,6 +1675,7 @@ impl Ledger {
                                 &account_id,
                                 witness,
                                 value,
+                                verified_witnesses,
                             )?
                         }
                     }
@@ -1170,12 +1686,19 @@ impl Ledger {
     }
 
     fn apply_tx_outputs<'a>(
-        mut self,
+        self,
         fragment_id: FragmentId,
         outputs: OutputsSlice<'a>,
     ) -> Result<Self, Error> {
+        self.apply_outputs(fragment_id, outputs.iter())
+    }
+
+    fn apply_outputs<I>(mut self, fragment_id: FragmentId, outputs: I) -> Result<Self, Error>
+    where
+        I: Iterator<Item = Output<Address>>,
+    {
         let mut new_utxos = Vec::new();
-        for (index, output) in outputs.iter().enumerate() {
+        for (index, output) in outputs.enumerate() {
             check::valid_output_value(&output)?;
 
             if output.address.discrimination() != self.static_params.discrimination {
@@ -1183,6 +1706,7 @@ impl Ledger {
             }
             match output.address.kind() {
                 Kind::Single(_) => {
+                    self.stake_aggregates = self.stake_aggregates.add_utxo(&output);
                     new_utxos.push((index as u8, output.clone()));
                 }
                 Kind::Group(_, account_id) => {
@@ -1191,7 +1715,9 @@ impl Ledger {
                     if !self.accounts.exists(&account_id) {
                         self.accounts =
                             self.accounts.add_account(&account_id, Value::zero(), ())?;
+                        self.update_account_stake(&account_id)?;
                     }
+                    self.stake_aggregates = self.stake_aggregates.add_utxo(&output);
                     new_utxos.push((index as u8, output.clone()));
                 }
                 Kind::Account(identifier) => {
@@ -1223,7 +1749,7 @@ impl Ledger {
             }
             Err(error) => return Err(error.into()),
         };
-        Ok(())
+        self.update_account_stake(account)
     }
 
     fn apply_tx_fee(mut self, fee: Value) -> Result<Self, Error> {
@@ -1236,6 +1762,7 @@ impl Ledger {
         sign_data_hash: &TransactionSignDataHash,
         utxo: &UtxoPointer,
         witness: &Witness,
+        verified_witnesses: &VerifiedWitnesses,
     ) -> Result<Self, Error> {
         match witness {
             Witness::Account(_) => Err(Error::ExpectingUtxoWitness),
@@ -1268,8 +1795,12 @@ impl Ledger {
                     sign_data_hash,
                     WitnessUtxoVersion::Legacy,
                 );
-                let verified = signature.verify(&pk, &data_to_verify);
-                if verified == chain_crypto::Verification::Failed {
+                if !verified_witnesses.contains(
+                    data_to_verify.as_ref(),
+                    pk.as_ref(),
+                    signature.as_ref(),
+                ) && signature.verify(&pk, &data_to_verify) == Verification::Failed
+                {
                     return Err(Error::OldUtxoInvalidSignature {
                         utxo: utxo.clone(),
                         output: associated_output.clone(),
@@ -1283,6 +1814,7 @@ impl Ledger {
                 let (new_utxos, associated_output) =
                     self.utxos.remove(&utxo.transaction_id, utxo.output_index)?;
                 self.utxos = new_utxos;
+                self.stake_aggregates = self.stake_aggregates.remove_utxo(&associated_output);
                 if utxo.value != associated_output.value {
                     return Err(Error::UtxoValueNotMatching {
                         expected: utxo.value,
@@ -1295,11 +1827,13 @@ impl Ledger {
                     sign_data_hash,
                     WitnessUtxoVersion::Normal,
                 );
-                let verified = signature.verify(
-                    &associated_output.address.public_key().unwrap(),
-                    &data_to_verify,
-                );
-                if verified == chain_crypto::Verification::Failed {
+                let pk = associated_output.address.public_key().unwrap();
+                if !verified_witnesses.contains(
+                    data_to_verify.as_ref(),
+                    pk.as_ref(),
+                    signature.as_ref(),
+                ) && signature.verify(pk, &data_to_verify) == Verification::Failed
+                {
                     return Err(Error::UtxoInvalidSignature {
                         utxo: utxo.clone(),
                         output: associated_output.clone(),
@@ -1311,6 +1845,11 @@ impl Ledger {
         }
     }
 
+    /// access the per epoch history of rewards received by accounts and stake pools
+    pub fn rewards_history(&self) -> &RewardsHistory {
+        &self.rewards_history
+    }
+
     pub fn remaining_rewards(&self) -> Value {
         self.pots.rewards
     }
@@ -1379,14 +1918,17 @@ fn input_single_account_verify<'a>(
     account: &account::Identifier,
     witness: &'a account::Witness,
     value: Value,
+    verified_witnesses: &VerifiedWitnesses,
 ) -> Result<account::Ledger, Error> {
     // .remove_value() check if there's enough value and if not, returns a Err.
     let (new_ledger, spending_counter) = ledger.remove_value(&account, value)?;
     ledger = new_ledger;
 
     let tidsc = WitnessAccountData::new(block0_hash, sign_data_hash, &spending_counter);
-    let verified = witness.verify(&account.clone().into(), &tidsc);
-    if verified == chain_crypto::Verification::Failed {
+    let pk = account.as_ref();
+    if !verified_witnesses.contains(tidsc.as_ref(), pk.as_ref(), witness.as_ref())
+        && witness.verify(pk, &tidsc) == Verification::Failed
+    {
         return Err(Error::AccountInvalidSignature {
             account: account.clone(),
             witness: Witness::Account(witness.clone()),
@@ -1402,12 +1944,16 @@ fn input_multi_account_verify<'a>(
     account: &multisig::Identifier,
     witness: &'a multisig::Witness,
     value: Value,
+    verified_witnesses: &VerifiedWitnesses,
 ) -> Result<multisig::Ledger, Error> {
     // .remove_value() check if there's enough value and if not, returns a Err.
     let (new_ledger, declaration, spending_counter) = ledger.remove_value(&account, value)?;
 
     let data_to_verify = WitnessMultisigData::new(&block0_hash, sign_data_hash, &spending_counter);
-    if witness.verify(declaration, &data_to_verify) != true {
+    let witness_bytes = property::Serialize::serialize_as_vec(witness).unwrap();
+    if !verified_witnesses.contains(data_to_verify.as_ref(), account.as_ref(), &witness_bytes)
+        && !witness.verify(declaration, &data_to_verify)
+    {
         return Err(Error::MultisigInvalidSignature {
             multisig: account.clone(),
             witness: Witness::Multisig(witness.clone()),
@@ -1432,15 +1978,16 @@ mod tests {
         testing::{
             address::ArbitraryAddressDataValueVec,
             builders::{
+                pool_owner_sign,
                 witness_builder::{make_witness, make_witnesses},
-                TestTx, TestTxBuilder,
+                StakePoolBuilder, TestTx, TestTxBuilder,
             },
             data::{AddressData, AddressDataValue},
-            ledger::{ConfigBuilder, LedgerBuilder},
+            ledger::{ConfigBuilder, LedgerBuilder, TestLedger},
             verifiers::LedgerStateVerifier,
             TestGen,
         },
-        transaction::Witness,
+        transaction::{Metadata, Witness},
     };
     use chain_addr::Discrimination;
     use quickcheck::{Arbitrary, Gen, TestResult};
@@ -1604,6 +2151,7 @@ mod tests {
             &id,
             &witness,
             value_to_sub,
+            &VerifiedWitnesses::default(),
         );
 
         TestResult::from_bool(result.is_err())
@@ -1632,6 +2180,7 @@ mod tests {
             &id,
             &to_account_witness(&signed_tx.witnesses().iter().next().unwrap()),
             value_to_sub,
+            &VerifiedWitnesses::default(),
         );
         assert!(result.is_ok())
     }
@@ -1670,6 +2219,7 @@ mod tests {
             &id,
             &to_account_witness(&signed_tx.witnesses().iter().next().unwrap()),
             value_to_sub,
+            &VerifiedWitnesses::default(),
         );
         assert!(result.is_err())
     }
@@ -1705,6 +2255,7 @@ mod tests {
             &id,
             &to_account_witness(&signed_tx.witnesses().iter().next().unwrap()),
             value_to_sub,
+            &VerifiedWitnesses::default(),
         );
         assert!(result.is_err())
     }
@@ -1734,6 +2285,7 @@ mod tests {
             &non_existing_account.public_key().into(),
             &to_account_witness(&signed_tx.witnesses().iter().next().unwrap()),
             value_to_sub,
+            &VerifiedWitnesses::default(),
         );
         assert!(result.is_err())
     }
@@ -1751,7 +2303,12 @@ mod tests {
             .unwrap();
 
         let inner_ledger: Ledger = test_ledger.into();
-        let result = inner_ledger.apply_input_to_utxo(&sign_data_hash, &utxo_pointer, &witness);
+        let result = inner_ledger.apply_input_to_utxo(
+            &sign_data_hash,
+            &utxo_pointer,
+            &witness,
+            &VerifiedWitnesses::default(),
+        );
         match (witness, result) {
             (Witness::OldUtxo(..), Ok(_)) => TestResult::error("expecting error, but got success"),
             (Witness::OldUtxo(..), Err(_)) => TestResult::passed(),
@@ -1788,6 +2345,7 @@ mod tests {
             &sign_data_hash,
             &utxo_pointer,
             &signed_tx.witnesses().iter().next().unwrap(),
+            &VerifiedWitnesses::default(),
         );
         assert!(result.is_ok())
     }
@@ -1812,6 +2370,7 @@ mod tests {
             &sign_data_hash,
             &utxo_pointer,
             &signed_tx.witnesses().iter().next().unwrap(),
+            &VerifiedWitnesses::default(),
         );
         assert!(result.is_err())
     }
@@ -2386,6 +2945,266 @@ mod tests {
             .total_value_is(&Value(1));
     }
 
+    fn apply_transaction_with_validity(
+        ledger_date: BlockDate,
+        validity: ValidityInterval,
+    ) -> Result<(), Error> {
+        let faucet = AddressDataValue::utxo(Discrimination::Test, Value(1));
+        let reciever = AddressDataValue::account(Discrimination::Test, Value(1));
+        let mut test_ledger = LedgerBuilder::from_config(ConfigBuilder::new(0))
+            .faucet(&faucet)
+            .build()
+            .unwrap();
+        test_ledger.set_date(ledger_date);
+
+        let fragment = TestTxBuilder::new(&test_ledger.block0_hash)
+            .with_validity(validity)
+            .move_all_funds(&mut test_ledger, &faucet, &reciever)
+            .get_fragment();
+        test_ledger.apply_transaction(fragment)
+    }
+
+    #[test]
+    pub fn test_internal_apply_transaction_in_validity_interval() {
+        let date = BlockDate {
+            epoch: 1,
+            slot_id: 5,
+        };
+        assert!(apply_transaction_with_validity(date, ValidityInterval::until(date)).is_ok());
+        assert!(
+            apply_transaction_with_validity(date, ValidityInterval::new(Some(date), None)).is_ok()
+        );
+    }
+
+    #[test]
+    pub fn test_internal_apply_transaction_expired() {
+        let date = BlockDate {
+            epoch: 1,
+            slot_id: 5,
+        };
+        let validity = ValidityInterval::until(BlockDate {
+            epoch: 1,
+            slot_id: 4,
+        });
+        assert_eq!(
+            apply_transaction_with_validity(date, validity),
+            Err(Error::TransactionNotValidAtDate { date, validity })
+        );
+    }
+
+    #[test]
+    pub fn test_internal_apply_transaction_not_yet_valid() {
+        let date = BlockDate {
+            epoch: 1,
+            slot_id: 5,
+        };
+        let validity = ValidityInterval::new(Some(date.next_epoch()), None);
+        assert_eq!(
+            apply_transaction_with_validity(date, validity),
+            Err(Error::TransactionNotValidAtDate { date, validity })
+        );
+    }
+
+    fn apply_metadata_transaction(
+        metadata_size: usize,
+        output_value: Value,
+    ) -> Result<TestLedger, Error> {
+        let faucet = AddressDataValue::account(Discrimination::Test, Value(100));
+        let reciever = AddressData::utxo(Discrimination::Test);
+        let mut test_ledger = LedgerBuilder::from_config(
+            ConfigBuilder::new(0)
+                .with_fee(LinearFee::new(0, 0, 0))
+                .with_metadata_max_size(16)
+                .with_per_metadata_byte_fee(2),
+        )
+        .faucet(&faucet)
+        .build()
+        .unwrap();
+
+        let metadata = Metadata::new(vec![1; metadata_size]).unwrap();
+        let tx_builder = TxBuilder::new().set_payload(&metadata).set_ios(
+            &[faucet.make_input(None)],
+            &[reciever.make_output(&output_value)],
+        );
+        let witness = make_witness(
+            &test_ledger.block0_hash,
+            &faucet.clone().into(),
+            &tx_builder.get_auth_data_for_witness().hash(),
+        );
+        let tx = tx_builder.set_witnesses(&[witness]).set_payload_auth(&());
+
+        test_ledger.apply_fragment(&Fragment::Metadata(tx), test_ledger.date())?;
+        Ok(test_ledger)
+    }
+
+    #[test]
+    pub fn test_apply_metadata_transaction_charges_metadata_size() {
+        let test_ledger = apply_metadata_transaction(10, Value(80)).unwrap();
+        LedgerStateVerifier::new(test_ledger.into())
+            .pots()
+            .has_fee_equals_to(&Value(20));
+    }
+
+    #[test]
+    pub fn test_apply_metadata_transaction_without_metadata_fee() {
+        assert!(matches!(
+            apply_metadata_transaction(10, Value(100)),
+            Err(Error::TransactionBalanceInvalid(_))
+        ));
+    }
+
+    #[test]
+    pub fn test_apply_metadata_transaction_too_big() {
+        assert_eq!(
+            apply_metadata_transaction(17, Value(66)).err(),
+            Some(Error::MetadataTooBig { size: 17, max: 16 })
+        );
+    }
+
+    fn apply_transaction_with_fragment_size_fee(
+        fee_adjustment: impl Fn(Value) -> Value,
+    ) -> (Value, Result<TestLedger, Error>) {
+        let faucet = AddressDataValue::account(Discrimination::Test, Value(1_000));
+        let reciever = AddressData::utxo(Discrimination::Test);
+        let mut linear_fee = LinearFee::new(0, 0, 0);
+        linear_fee.per_fragment_byte(1);
+        let mut test_ledger = LedgerBuilder::from_config(
+            ConfigBuilder::new(0)
+                .with_fee(LinearFee::new(0, 0, 0))
+                .with_per_fragment_byte_fee(1),
+        )
+        .faucet(&faucet)
+        .build()
+        .unwrap();
+
+        let mut ios = InputOutputBuilder::empty();
+        ios.add_input(&faucet.make_input(None)).unwrap();
+        ios.add_output(reciever.address.clone(), Value::zero())
+            .unwrap();
+        let fee = fee_adjustment(ios.estimate_fee(NoExtra.payload_data().borrow(), &linear_fee));
+
+        let tx_builder = TxBuilder::new().set_payload(&NoExtra).set_ios(
+            &[faucet.make_input(None)],
+            &[reciever.make_output(&(Value(1_000) - fee).unwrap())],
+        );
+        let witness = make_witness(
+            &test_ledger.block0_hash,
+            &faucet.clone().into(),
+            &tx_builder.get_auth_data_for_witness().hash(),
+        );
+        let tx = tx_builder.set_witnesses(&[witness]).set_payload_auth(&());
+
+        let result = test_ledger
+            .apply_transaction(Fragment::Transaction(tx))
+            .map(|_| test_ledger);
+        (fee, result)
+    }
+
+    #[test]
+    pub fn test_apply_transaction_charges_fragment_size() {
+        let (fee, test_ledger) = apply_transaction_with_fragment_size_fee(|fee| fee);
+        assert!(fee > Value::zero());
+        LedgerStateVerifier::new(test_ledger.unwrap().into())
+            .pots()
+            .has_fee_equals_to(&fee);
+    }
+
+    #[test]
+    pub fn test_apply_transaction_without_fragment_size_fee() {
+        let (_, test_ledger) =
+            apply_transaction_with_fragment_size_fee(|fee| (fee - Value(1)).unwrap());
+        assert!(matches!(
+            test_ledger,
+            Err(Error::TransactionBalanceInvalid(_))
+        ));
+    }
+
+    #[test]
+    pub fn test_apply_pool_registration_with_estimated_fee() {
+        let owner = AddressDataValue::account(Discrimination::Test, Value(1_000));
+        let mut linear_fee = LinearFee::new(0, 0, 0);
+        linear_fee.per_fragment_byte(1);
+        let mut test_ledger = LedgerBuilder::from_config(
+            ConfigBuilder::new(0)
+                .with_fee(LinearFee::new(0, 0, 0))
+                .with_per_fragment_byte_fee(1),
+        )
+        .faucet(&owner)
+        .build()
+        .unwrap();
+        let registration = StakePoolBuilder::new()
+            .with_owners(vec![owner.public_key()])
+            .build()
+            .info();
+        let date = test_ledger.date();
+        let validity = ValidityInterval::new(Some(date), Some(date.next_epoch()));
+
+        let mut ios = InputOutputBuilder::empty();
+        ios.set_validity(validity);
+        ios.add_input(&owner.make_input(None)).unwrap();
+        let fee = ios.estimate_fee(registration.payload_data().borrow(), &linear_fee);
+
+        let tx_builder = TxBuilder::new()
+            .set_payload(&registration)
+            .set_validity(validity)
+            .set_ios(&[owner.make_input_with_value(None, &fee)], &[]);
+        let witness = make_witness(
+            &test_ledger.block0_hash,
+            &owner.clone().into(),
+            &tx_builder.get_auth_data_for_witness().hash(),
+        );
+        let tx_builder = tx_builder.set_witnesses(&[witness]);
+        let signature = pool_owner_sign(&[owner.private_key()], &tx_builder);
+        let fragment = Fragment::PoolRegistration(tx_builder.set_payload_auth(&signature));
+
+        assert_eq!(fee, Value(fragment.to_raw().as_ref().len() as u64));
+        test_ledger.apply_fragment(&fragment, date).unwrap();
+        LedgerStateVerifier::new(test_ledger.into())
+            .pots()
+            .has_fee_equals_to(&fee);
+    }
+
+    #[test]
+    pub fn utxos_by_address_follows_transactions() {
+        let faucet = AddressDataValue::utxo(Discrimination::Test, Value(100));
+        let receiver = AddressDataValue::utxo(Discrimination::Test, Value(100));
+        let mut test_ledger =
+            LedgerBuilder::from_config(ConfigBuilder::new(0).with_fee(LinearFee::new(0, 0, 0)))
+                .faucet(&faucet)
+                .build()
+                .unwrap();
+        assert!(test_ledger
+            .ledger
+            .utxos_by_address(&faucet.address())
+            .is_none());
+
+        test_ledger.ledger = test_ledger.ledger.with_utxo_address_index();
+        assert_eq!(
+            test_ledger
+                .ledger
+                .utxos_by_address(&faucet.address())
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let fragment = TestTxBuilder::new(&test_ledger.block0_hash)
+            .move_all_funds(&mut test_ledger, &faucet, &receiver)
+            .get_fragment();
+        test_ledger.apply_transaction(fragment).unwrap();
+        assert!(test_ledger
+            .ledger
+            .utxos_by_address(&faucet.address())
+            .unwrap()
+            .is_empty());
+        let utxos = test_ledger
+            .ledger
+            .utxos_by_address(&receiver.address())
+            .unwrap();
+        assert_eq!(utxos.len(), 1);
+        assert_eq!(utxos[0].output.value, Value(100));
+    }
+
     #[test]
     pub fn test_internal_apply_transaction_wrong_witness_type() {
         let faucet = AddressDataValue::utxo(Discrimination::Test, Value(1));
@@ -1,4 +1,5 @@
 pub mod check;
+mod diff;
 mod info;
 pub mod iter;
 mod leaderlog;
@@ -6,12 +7,22 @@ pub mod ledger;
 mod pots;
 pub mod recovery;
 mod reward_info;
+mod rewards_history;
+mod rewards_preview;
+mod stake_snapshots;
+mod validation;
+mod verification;
 
+pub use diff::LedgerDiff;
 pub use iter::*;
 pub use leaderlog::LeadersParticipationRecord;
 pub use ledger::*;
 pub use pots::Pots;
 pub use reward_info::{EpochRewardsInfo, RewardsInfoParameters};
+pub use rewards_history::{EpochRewardsHistory, RewardsHistory};
+pub use stake_snapshots::StakeSnapshots;
+pub use validation::{BlockValidationReport, FragmentValidationError};
+pub use verification::VerifiedWitnesses;
 
 cfg_if! {
    if #[cfg(test)] {
@@ -10,6 +10,8 @@ pub struct Pots {
     pub(crate) fees: Value,
     pub(crate) treasury: Treasury,
     pub(crate) rewards: Value,
+    /// deposits locked by the registered stake pools
+    pub(crate) deposits: Value,
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -17,6 +19,7 @@ pub enum Entry {
     Fees(Value),
     Treasury(Value),
     Rewards(Value),
+    Deposits(Value),
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -24,6 +27,7 @@ pub enum EntryType {
     Fees,
     Treasury,
     Rewards,
+    Deposits,
 }
 
 impl Entry {
@@ -32,6 +36,7 @@ impl Entry {
             Entry::Fees(v) => *v,
             Entry::Treasury(v) => *v,
             Entry::Rewards(v) => *v,
+            Entry::Deposits(v) => *v,
         }
     }
 
@@ -40,6 +45,7 @@ impl Entry {
             Entry::Fees(_) => EntryType::Fees,
             Entry::Treasury(_) => EntryType::Treasury,
             Entry::Rewards(_) => EntryType::Rewards,
+            Entry::Deposits(_) => EntryType::Deposits,
         }
     }
 }
@@ -48,6 +54,7 @@ pub enum IterState {
     Fees,
     Treasury,
     Rewards,
+    Deposits,
     Done,
 }
 
@@ -72,9 +79,13 @@ impl<'a> Iterator for Entries<'a> {
                 Some(Entry::Treasury(self.pots.treasury.value()))
             }
             IterState::Rewards => {
-                self.it = IterState::Done;
+                self.it = IterState::Deposits;
                 Some(Entry::Rewards(self.pots.rewards))
             }
+            IterState::Deposits => {
+                self.it = IterState::Done;
+                Some(Entry::Deposits(self.pots.deposits))
+            }
             IterState::Done => None,
         }
     }
@@ -98,6 +109,7 @@ impl Pots {
             fees: Value::zero(),
             treasury: Treasury::initial(Value::zero()),
             rewards: Value::zero(),
+            deposits: Value::zero(),
         }
     }
 
@@ -123,6 +135,20 @@ impl Pots {
         Ok(())
     }
 
+    /// Lock a stake pool deposit
+    pub fn lock_deposit(&mut self, deposit: Value) -> Result<(), Error> {
+        self.deposits =
+            (self.deposits + deposit).map_err(|error| Error::PotValueInvalid { error })?;
+        Ok(())
+    }
+
+    /// Release a stake pool deposit, to be refunded
+    pub fn release_deposit(&mut self, deposit: Value) -> Result<(), Error> {
+        self.deposits =
+            (self.deposits - deposit).map_err(|error| Error::PotValueInvalid { error })?;
+        Ok(())
+    }
+
     /// Draw rewards from the pot
     #[must_use]
     pub fn draw_reward(&mut self, expected_reward: Value) -> Value {
@@ -144,6 +170,11 @@ impl Pots {
         self.treasury.add(value)
     }
 
+    /// Withdraw from treasury
+    pub fn treasury_withdraw(&mut self, value: Value) -> Result<(), Error> {
+        self.treasury.withdraw(value)
+    }
+
     /// Get the value in the treasury
     pub fn fees_value(&self) -> Value {
         self.fees
@@ -154,11 +185,17 @@ impl Pots {
         self.treasury.value()
     }
 
+    /// Get the value of the locked deposits
+    pub fn deposits_value(&self) -> Value {
+        self.deposits
+    }
+
     pub fn set_from_entry(&mut self, e: &Entry) {
         match e {
             Entry::Fees(v) => self.fees = *v,
             Entry::Treasury(v) => self.treasury = Treasury::initial(*v),
             Entry::Rewards(v) => self.rewards = *v,
+            Entry::Deposits(v) => self.deposits = *v,
         }
     }
 }
@@ -176,6 +213,7 @@ mod tests {
                 fees: Arbitrary::arbitrary(g),
                 treasury: Arbitrary::arbitrary(g),
                 rewards: Arbitrary::arbitrary(g),
+                deposits: Arbitrary::arbitrary(g),
             }
         }
     }
@@ -201,6 +239,9 @@ mod tests {
                 Entry::Rewards(rewards) => {
                     assert_eq!(pots.rewards, rewards);
                 }
+                Entry::Deposits(deposits) => {
+                    assert_eq!(pots.deposits, deposits);
+                }
             }
         }
         TestResult::passed()
@@ -28,15 +28,26 @@
 //! * Flag the end of packing
 //!
 //! For deserializing:
-//! * Load all serialized `Entry` into a `Vec`
-//! * Use the `from_iter` ledger method to load it from the newly created vector.
+//! * Unpack each serialized `Entry` one at a time
+//! * Push it into the ledger being restored, until the end flag is found.
 //!
 //! Notice that the `ledger::iter::Entry` type holds references to the data types but when loading
 //! them from the serialized object we need to hold them. That is why we use the `EntryOwned` type
 //! instead for deserializing. This data is then cloned as necessary into the final deserialized ledger.
+//!
+//! A ledger snapshot can also be kept up to date without serializing the whole ledger again: a
+//! snapshot is a serialized ledger followed by any number of deltas, each one packed with
+//! `pack_delta` from the changes between two ledger states (see `Ledger::diff`). Restoring the
+//! snapshot with `restore_snapshot` loads the base ledger and applies the deltas in order.
+//!
+//! In a delta, the UTXOs, accounts, multisig accounts and declarations and stake pools are packed
+//! as the changes between both ledgers. The globals and the pots are always packed, and the update
+//! proposals, leaders participation, rewards history and stake snapshots are packed fully when
+//! they changed.
 
+use super::iter::{flush_outputs, push_output, LedgerLoader};
 use super::pots;
-use super::{Entry, EntryOwned};
+use super::{Entry, EntryOwned, LeadersParticipationRecord, RewardsHistory, StakeSnapshots};
 use crate::account::AccountAlg;
 use crate::accounting::account::{
     AccountState, DelegationRatio, DelegationType, LastRewards, SpendingCounter,
@@ -45,16 +56,23 @@ use crate::certificate::{PoolId, PoolRegistration};
 use crate::chaintypes::ConsensusVersion;
 use crate::config::ConfigParam;
 use crate::date::BlockDate;
-use crate::fee::{LinearFee, PerCertificateFee};
+use crate::fee::{LinearFee, PerCertificateFee, PerExtendedCertificateFee};
 use crate::fragment::{ConfigParams, FragmentId};
 use crate::header::{ChainLength, HeaderId};
 use crate::key::{serialize_public_key, BftLeaderId};
-use crate::ledger::{Globals, Ledger, LedgerStaticParameters};
+use crate::ledger::{EpochRewardsHistory, Globals, Ledger, LedgerStaticParameters};
 use crate::legacy;
 use crate::multisig::{DeclElement, Declaration};
-use crate::stake::{PoolLastRewards, PoolState};
+use crate::stake::Stake;
+use crate::stake::{
+    PoolLastRewards, PoolStakeDistribution, PoolStakeInformation, PoolState, StakeAggregates,
+    StakeDistribution,
+};
 use crate::transaction::Output;
-use crate::update::{UpdateProposal, UpdateProposalId, UpdateProposalState, UpdateVoterId};
+use crate::update::{
+    StakeVoteTally, TreasuryTransferProposal, TreasuryTransferProposalState, UpdateProposal,
+    UpdateProposalId, UpdateProposalState, UpdateState, UpdateVoterId,
+};
 use crate::value::Value;
 use crate::{config, key, multisig, utxo};
 use chain_addr::{Address, Discrimination};
@@ -64,10 +82,10 @@ use chain_crypto::AsymmetricPublicKey;
 use chain_ser::deser::{Deserialize, Serialize};
 use chain_ser::packer::Codec;
 use chain_time::era::{pack_time_era, unpack_time_era};
-use std::collections::HashSet;
+use imhamt::Diff;
+use std::collections::{BTreeMap, HashSet};
 use std::convert::TryFrom;
 use std::io::{Read, Write};
-use std::iter::FromIterator;
 use std::sync::Arc;
 
 fn pack_pool_id<W: std::io::Write>(
@@ -365,6 +383,9 @@ fn pack_linear_fee<W: std::io::Write>(
     codec.put_u64(linear_fee.coefficient)?;
     codec.put_u64(linear_fee.certificate)?;
     pack_per_certificate_fee(&linear_fee.per_certificate_fees, codec)?;
+    pack_per_extended_certificate_fee(&linear_fee.per_extended_certificate_fees, codec)?;
+    codec.put_u64(linear_fee.per_metadata_byte)?;
+    codec.put_u64(linear_fee.per_fragment_byte)?;
     Ok(())
 }
 
@@ -375,11 +396,17 @@ fn unpack_linear_fee<R: std::io::BufRead>(
     let coefficient = codec.get_u64()?;
     let certificate = codec.get_u64()?;
     let per_certificate_fees = unpack_per_certificate_fee(codec)?;
+    let per_extended_certificate_fees = unpack_per_extended_certificate_fee(codec)?;
+    let per_metadata_byte = codec.get_u64()?;
+    let per_fragment_byte = codec.get_u64()?;
     Ok(LinearFee {
         constant,
         coefficient,
         certificate,
         per_certificate_fees,
+        per_extended_certificate_fees,
+        per_metadata_byte,
+        per_fragment_byte,
     })
 }
 
@@ -422,6 +449,34 @@ fn unpack_per_certificate_fee<R: std::io::BufRead>(
     })
 }
 
+fn pack_per_extended_certificate_fee<W: std::io::Write>(
+    per_extended_certificate_fee: &PerExtendedCertificateFee,
+    codec: &mut Codec<W>,
+) -> Result<(), std::io::Error> {
+    for fee in &[
+        per_extended_certificate_fee.certificate_pool_retirement,
+        per_extended_certificate_fee.certificate_pool_update,
+        per_extended_certificate_fee.certificate_multisig_declaration,
+    ] {
+        codec.put_u64(fee.map(|v| v.get()).unwrap_or(0))?;
+    }
+    Ok(())
+}
+
+fn unpack_per_extended_certificate_fee<R: std::io::BufRead>(
+    codec: &mut Codec<R>,
+) -> Result<PerExtendedCertificateFee, std::io::Error> {
+    let certificate_pool_retirement = std::num::NonZeroU64::new(codec.get_u64()?);
+    let certificate_pool_update = std::num::NonZeroU64::new(codec.get_u64()?);
+    let certificate_multisig_declaration = std::num::NonZeroU64::new(codec.get_u64()?);
+
+    Ok(PerExtendedCertificateFee {
+        certificate_pool_retirement,
+        certificate_pool_update,
+        certificate_multisig_declaration,
+    })
+}
+
 fn pack_config_params<W: std::io::Write>(
     config_params: &ConfigParams,
     codec: &mut Codec<W>,
@@ -526,6 +581,10 @@ fn pack_pot_entry<W: std::io::Write>(
             codec.put_u8(2)?;
             codec.put_u64(value.0)?;
         }
+        pots::Entry::Deposits(value) => {
+            codec.put_u8(3)?;
+            codec.put_u64(value.0)?;
+        }
     }
     Ok(())
 }
@@ -537,6 +596,7 @@ fn unpack_pot_entry<R: std::io::BufRead>(
         0 => Ok(pots::Entry::Fees(Value(codec.get_u64()?))),
         1 => Ok(pots::Entry::Treasury(Value(codec.get_u64()?))),
         2 => Ok(pots::Entry::Rewards(Value(codec.get_u64()?))),
+        3 => Ok(pots::Entry::Deposits(Value(codec.get_u64()?))),
         code => Err(std::io::Error::new(
             std::io::ErrorKind::InvalidData,
             format!("Invalid Entry type code {}", code),
@@ -642,6 +702,7 @@ fn pack_pool_state<W: std::io::Write>(
 ) -> Result<(), std::io::Error> {
     pack_pool_last_rewards(&pool_state.last_rewards, codec)?;
     pack_pool_registration(&pool_state.registration, codec)?;
+    codec.put_u64(pool_state.deposit.0)?;
     Ok(())
 }
 
@@ -650,13 +711,125 @@ fn unpack_pool_state<R: std::io::BufRead>(
 ) -> Result<PoolState, std::io::Error> {
     let last_rewards = unpack_pool_last_rewards(codec)?;
     let registration = Arc::new(unpack_pool_registration(codec)?);
+    let deposit = Value(codec.get_u64()?);
 
     Ok(PoolState {
         last_rewards,
         registration,
+        deposit,
     })
 }
 
+fn pack_epoch_rewards_history<W: std::io::Write>(
+    history: &EpochRewardsHistory,
+    codec: &mut Codec<W>,
+) -> Result<(), std::io::Error> {
+    codec.put_u64(history.accounts.size() as u64)?;
+    for (identifier, value) in history.accounts() {
+        pack_account_identifier(identifier, codec)?;
+        codec.put_u64(value.0)?;
+    }
+    codec.put_u64(history.multisig_accounts.size() as u64)?;
+    for (identifier, value) in history.multisig_accounts() {
+        pack_multisig_identifier(identifier, codec)?;
+        codec.put_u64(value.0)?;
+    }
+    codec.put_u64(history.stake_pools.size() as u64)?;
+    for (pool_id, (value_taxed, value_for_stakers)) in history.stake_pools() {
+        pack_pool_id(pool_id, codec)?;
+        codec.put_u64(value_taxed.0)?;
+        codec.put_u64(value_for_stakers.0)?;
+    }
+    Ok(())
+}
+
+fn unpack_epoch_rewards_history<R: std::io::BufRead>(
+    codec: &mut Codec<R>,
+) -> Result<EpochRewardsHistory, std::io::Error> {
+    let mut history = EpochRewardsHistory::new(true);
+    let accounts_size = codec.get_u64()?;
+    for _ in 0..accounts_size {
+        let identifier = unpack_account_identifier(codec)?;
+        history.add_to_account(&identifier, Value(codec.get_u64()?));
+    }
+    let multisig_accounts_size = codec.get_u64()?;
+    for _ in 0..multisig_accounts_size {
+        let identifier = unpack_multisig_identifier(codec)?;
+        history.add_to_multisig_account(&identifier, Value(codec.get_u64()?));
+    }
+    let stake_pools_size = codec.get_u64()?;
+    for _ in 0..stake_pools_size {
+        let pool_id = unpack_pool_id(codec)?;
+        let value_taxed = Value(codec.get_u64()?);
+        let value_for_stakers = Value(codec.get_u64()?);
+        history.set_stake_pool(&pool_id, value_taxed, value_for_stakers);
+    }
+    Ok(history)
+}
+
+fn pack_stake_distribution<W: std::io::Write>(
+    distribution: &StakeDistribution,
+    codec: &mut Codec<W>,
+) -> Result<(), std::io::Error> {
+    codec.put_u64(distribution.unassigned.into())?;
+    codec.put_u64(distribution.dangling.into())?;
+    codec.put_u64(distribution.to_pools.len() as u64)?;
+    for (pool_id, pool_info) in distribution.to_pools.iter() {
+        pack_pool_id(pool_id, codec)?;
+        match &pool_info.registration {
+            None => codec.put_u8(0)?,
+            Some(registration) => {
+                codec.put_u8(1)?;
+                pack_pool_registration(registration, codec)?;
+            }
+        }
+        codec.put_u64(pool_info.stake.total.into())?;
+        codec.put_u64(pool_info.stake.accounts.len() as u64)?;
+        for (identifier, stake) in pool_info.stake.iter() {
+            pack_account_identifier(identifier, codec)?;
+            codec.put_u64((*stake).into())?;
+        }
+    }
+    Ok(())
+}
+
+fn unpack_stake_distribution<R: std::io::BufRead>(
+    codec: &mut Codec<R>,
+) -> Result<StakeDistribution, std::io::Error> {
+    let mut distribution = StakeDistribution::empty();
+    distribution.unassigned = Stake(codec.get_u64()?);
+    distribution.dangling = Stake(codec.get_u64()?);
+    let pools_size = codec.get_u64()?;
+    for _ in 0..pools_size {
+        let pool_id = unpack_pool_id(codec)?;
+        let registration = match codec.get_u8()? {
+            0 => None,
+            1 => Some(Arc::new(unpack_pool_registration(codec)?)),
+            code => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Unrecognized code {} for pool registration", code),
+                ))
+            }
+        };
+        let mut stake = PoolStakeDistribution::new();
+        stake.total = Stake(codec.get_u64()?);
+        let accounts_size = codec.get_u64()?;
+        for _ in 0..accounts_size {
+            let identifier = unpack_account_identifier(codec)?;
+            stake.accounts.insert(identifier, Stake(codec.get_u64()?));
+        }
+        distribution.to_pools.insert(
+            pool_id,
+            PoolStakeInformation {
+                registration,
+                stake,
+            },
+        );
+    }
+    Ok(distribution)
+}
+
 fn pack_update_proposal_state<W: std::io::Write>(
     update_proposal_state: &UpdateProposalState,
     codec: &mut Codec<W>,
@@ -665,11 +838,17 @@ fn pack_update_proposal_state<W: std::io::Write>(
     pack_block_date(&update_proposal_state.proposal_date, codec)?;
     codec.put_u64(update_proposal_state.votes.len() as u64)?;
     {
-        let mut codec = Codec::new(codec);
+        let mut codec = Codec::new(&mut *codec);
         for e in &update_proposal_state.votes {
             e.serialize(&mut codec)?;
         }
     }
+    codec.put_u64(update_proposal_state.pool_votes.len() as u64)?;
+    for (pool_id, approve) in &update_proposal_state.pool_votes {
+        pack_pool_id(pool_id, codec)?;
+        codec.put_u8(*approve as u8)?;
+    }
+    pack_stake_vote_tally(&update_proposal_state.tally, codec)?;
     Ok(())
 }
 
@@ -681,16 +860,92 @@ fn unpack_update_proposal_state<R: std::io::BufRead>(
     let total_votes = codec.get_u64()?;
     let mut votes: HashSet<UpdateVoterId> = HashSet::new();
     {
-        let mut codec = Codec::new(codec);
+        let mut codec = Codec::new(&mut *codec);
         for _ in 0..total_votes {
             let id = UpdateVoterId::deserialize(&mut codec)?;
             votes.insert(id);
         }
     }
+    let total_pool_votes = codec.get_u64()?;
+    let mut pool_votes = BTreeMap::new();
+    for _ in 0..total_pool_votes {
+        let pool_id = unpack_pool_id(codec)?;
+        let approve = match codec.get_u8()? {
+            0 => false,
+            1 => true,
+            code => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Invalid pool vote choice {}", code),
+                ))
+            }
+        };
+        pool_votes.insert(pool_id, approve);
+    }
+    let tally = unpack_stake_vote_tally(codec)?;
     Ok(UpdateProposalState {
         proposal,
         proposal_date,
         votes,
+        pool_votes,
+        tally,
+    })
+}
+
+fn pack_stake_vote_tally<W: std::io::Write>(
+    tally: &StakeVoteTally,
+    codec: &mut Codec<W>,
+) -> Result<(), std::io::Error> {
+    codec.put_u64(tally.approve.into())?;
+    codec.put_u64(tally.reject.into())?;
+    codec.put_u64(tally.total.into())?;
+    Ok(())
+}
+
+fn unpack_stake_vote_tally<R: std::io::BufRead>(
+    codec: &mut Codec<R>,
+) -> Result<StakeVoteTally, std::io::Error> {
+    Ok(StakeVoteTally {
+        approve: Stake(codec.get_u64()?),
+        reject: Stake(codec.get_u64()?),
+        total: Stake(codec.get_u64()?),
+    })
+}
+
+fn pack_treasury_transfer_proposal_state<W: std::io::Write>(
+    proposal_state: &TreasuryTransferProposalState,
+    codec: &mut Codec<W>,
+) -> Result<(), std::io::Error> {
+    pack_output(&proposal_state.proposal.transfer, &mut pack_address, codec)?;
+    pack_block_date(&proposal_state.proposal_date, codec)?;
+    codec.put_u64(proposal_state.votes.len() as u64)?;
+    {
+        let mut codec = Codec::new(codec);
+        for e in &proposal_state.votes {
+            e.serialize(&mut codec)?;
+        }
+    }
+    Ok(())
+}
+
+fn unpack_treasury_transfer_proposal_state<R: std::io::BufRead>(
+    codec: &mut Codec<R>,
+) -> Result<TreasuryTransferProposalState, std::io::Error> {
+    let transfer = unpack_output(&mut unpack_address, codec)?;
+    let proposal_date = unpack_block_date(codec)?;
+    let total_votes = codec.get_u64()?;
+    let mut votes: HashSet<UpdateVoterId> = HashSet::new();
+    {
+        let mut codec = Codec::new(codec);
+        for _ in 0..total_votes {
+            let id = UpdateVoterId::deserialize(&mut codec)?;
+            votes.insert(id);
+        }
+    }
+    Ok(TreasuryTransferProposalState {
+        proposal: TreasuryTransferProposal { transfer },
+        proposal_date,
+        votes,
     })
 }
 
@@ -833,6 +1088,9 @@ enum EntrySerializeCode {
     StakePool = 9,
     LeaderParticipation = 10,
     SerializationEnd = 11,
+    RewardsHistory = 12,
+    TreasuryTransferProposal = 13,
+    StakeSnapshot = 14,
 }
 
 impl EntrySerializeCode {
@@ -850,6 +1108,9 @@ impl EntrySerializeCode {
             9 => Some(EntrySerializeCode::StakePool),
             10 => Some(EntrySerializeCode::LeaderParticipation),
             11 => Some(EntrySerializeCode::SerializationEnd),
+            12 => Some(EntrySerializeCode::RewardsHistory),
+            13 => Some(EntrySerializeCode::TreasuryTransferProposal),
+            14 => Some(EntrySerializeCode::StakeSnapshot),
             _ => None,
         }
     }
@@ -890,6 +1151,11 @@ fn pack_entry<W: std::io::Write>(
             pack_update_proposal_id(proposal_id, codec)?;
             pack_update_proposal_state(proposal_state, codec)?;
         }
+        Entry::TreasuryTransferProposal((proposal_id, proposal_state)) => {
+            codec.put_u8(EntrySerializeCode::TreasuryTransferProposal as u8)?;
+            pack_update_proposal_id(proposal_id, codec)?;
+            pack_treasury_transfer_proposal_state(proposal_state, codec)?;
+        }
         Entry::MultisigAccount((identifier, account_state)) => {
             codec.put_u8(EntrySerializeCode::MultisigAccount as u8)?;
             pack_multisig_identifier(identifier, codec)?;
@@ -910,6 +1176,16 @@ fn pack_entry<W: std::io::Write>(
             pack_digestof(pool_id, codec)?;
             codec.put_u32(**participation)?;
         }
+        Entry::RewardsHistory((epoch, history)) => {
+            codec.put_u8(EntrySerializeCode::RewardsHistory as u8)?;
+            codec.put_u32(**epoch)?;
+            pack_epoch_rewards_history(history, codec)?;
+        }
+        Entry::StakeSnapshot((epoch, distribution)) => {
+            codec.put_u8(EntrySerializeCode::StakeSnapshot as u8)?;
+            codec.put_u32(**epoch)?;
+            pack_stake_distribution(distribution, codec)?;
+        }
     }
     Ok(())
 }
@@ -944,6 +1220,14 @@ fn unpack_entry_owned<R: std::io::BufRead>(
             let proposal_state = unpack_update_proposal_state(codec)?;
             Ok(EntryOwned::UpdateProposal((proposal_id, proposal_state)))
         }
+        EntrySerializeCode::TreasuryTransferProposal => {
+            let proposal_id = unpack_update_proposal_id(codec)?;
+            let proposal_state = unpack_treasury_transfer_proposal_state(codec)?;
+            Ok(EntryOwned::TreasuryTransferProposal((
+                proposal_id,
+                proposal_state,
+            )))
+        }
         EntrySerializeCode::MultisigAccount => {
             let identifier = unpack_multisig_identifier(codec)?;
             let account_state = unpack_account_state(codec)?;
@@ -964,26 +1248,20 @@ fn unpack_entry_owned<R: std::io::BufRead>(
             let v = codec.get_u32()?;
             Ok(EntryOwned::LeaderParticipation((pool_id, v)))
         }
+        EntrySerializeCode::RewardsHistory => {
+            let epoch = codec.get_u32()?;
+            let history = unpack_epoch_rewards_history(codec)?;
+            Ok(EntryOwned::RewardsHistory((epoch, history)))
+        }
+        EntrySerializeCode::StakeSnapshot => {
+            let epoch = codec.get_u32()?;
+            let distribution = unpack_stake_distribution(codec)?;
+            Ok(EntryOwned::StakeSnapshot((epoch, distribution)))
+        }
         EntrySerializeCode::SerializationEnd => Ok(EntryOwned::StopEntry),
     }
 }
 
-fn unpack_entries<R: std::io::BufRead>(reader: R) -> Result<Vec<EntryOwned>, std::io::Error> {
-    let mut codec = Codec::new(reader);
-    let mut res = Vec::new();
-    loop {
-        match unpack_entry_owned(&mut codec)? {
-            EntryOwned::StopEntry => {
-                break;
-            }
-            entry => {
-                res.push(entry);
-            }
-        };
-    }
-    Ok(res)
-}
-
 impl Serialize for Ledger {
     type Error = std::io::Error;
 
@@ -998,29 +1276,471 @@ impl Serialize for Ledger {
     }
 }
 
+fn ledger_error(e: crate::ledger::Error) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Other, format!("{}", e))
+}
+
 impl Deserialize for Ledger {
     type Error = std::io::Error;
 
     fn deserialize<R: std::io::BufRead>(reader: R) -> Result<Self, Self::Error> {
-        let owned_entries = unpack_entries(reader)?;
-        let entries = owned_entries
-            .iter()
-            .map(|entry_owned| entry_owned.to_entry().unwrap());
-        let ledger: Result<Ledger, crate::ledger::Error> = Result::from_iter(entries);
-        match ledger {
-            Ok(l) => Ok(l),
-            Err(e) => Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("{}", e),
-            )),
+        let mut codec = Codec::new(reader);
+        let mut loader = LedgerLoader::new();
+        loop {
+            match unpack_entry_owned(&mut codec)? {
+                EntryOwned::StopEntry => break,
+                entry => loader
+                    .push(entry.to_entry().unwrap())
+                    .map_err(ledger_error)?,
+            }
+        }
+        loader.finish().map_err(ledger_error)
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum DeltaSerializeCode {
+    Globals = 0,
+    Pot = 1,
+    UtxoAdded = 2,
+    UtxoRemoved = 3,
+    OldUtxoRemoved = 4,
+    AccountSet = 5,
+    AccountRemoved = 6,
+    ConfigParam = 7,
+    UpdatesReset = 8,
+    UpdateProposal = 9,
+    TreasuryTransferProposal = 10,
+    MultisigAccountSet = 11,
+    MultisigAccountRemoved = 12,
+    MultisigDeclarationSet = 13,
+    MultisigDeclarationRemoved = 14,
+    StakePoolSet = 15,
+    StakePoolRemoved = 16,
+    LeadersParticipationReset = 17,
+    LeaderParticipation = 18,
+    RewardsHistoryReset = 19,
+    RewardsHistory = 20,
+    DeltaEnd = 21,
+    StakeSnapshotsReset = 22,
+    StakeSnapshot = 23,
+}
+
+impl DeltaSerializeCode {
+    pub fn from_u8(n: u8) -> Option<Self> {
+        match n {
+            0 => Some(DeltaSerializeCode::Globals),
+            1 => Some(DeltaSerializeCode::Pot),
+            2 => Some(DeltaSerializeCode::UtxoAdded),
+            3 => Some(DeltaSerializeCode::UtxoRemoved),
+            4 => Some(DeltaSerializeCode::OldUtxoRemoved),
+            5 => Some(DeltaSerializeCode::AccountSet),
+            6 => Some(DeltaSerializeCode::AccountRemoved),
+            7 => Some(DeltaSerializeCode::ConfigParam),
+            8 => Some(DeltaSerializeCode::UpdatesReset),
+            9 => Some(DeltaSerializeCode::UpdateProposal),
+            10 => Some(DeltaSerializeCode::TreasuryTransferProposal),
+            11 => Some(DeltaSerializeCode::MultisigAccountSet),
+            12 => Some(DeltaSerializeCode::MultisigAccountRemoved),
+            13 => Some(DeltaSerializeCode::MultisigDeclarationSet),
+            14 => Some(DeltaSerializeCode::MultisigDeclarationRemoved),
+            15 => Some(DeltaSerializeCode::StakePoolSet),
+            16 => Some(DeltaSerializeCode::StakePoolRemoved),
+            17 => Some(DeltaSerializeCode::LeadersParticipationReset),
+            18 => Some(DeltaSerializeCode::LeaderParticipation),
+            19 => Some(DeltaSerializeCode::RewardsHistoryReset),
+            20 => Some(DeltaSerializeCode::RewardsHistory),
+            21 => Some(DeltaSerializeCode::DeltaEnd),
+            22 => Some(DeltaSerializeCode::StakeSnapshotsReset),
+            23 => Some(DeltaSerializeCode::StakeSnapshot),
+            _ => None,
+        }
+    }
+}
+
+fn pack_utxo_pointer<OutputAddress, W: std::io::Write>(
+    entry: &utxo::Entry<'_, OutputAddress>,
+    codec: &mut Codec<W>,
+) -> Result<(), std::io::Error> {
+    codec.put_bytes(entry.fragment_id.as_ref())?;
+    codec.put_u8(entry.output_index)?;
+    Ok(())
+}
+
+fn unpack_utxo_pointer<R: std::io::BufRead>(
+    codec: &mut Codec<R>,
+) -> Result<(FragmentId, u8), std::io::Error> {
+    let mut fragment_id_bytes: [u8; 32] = [0; 32];
+    codec.read_exact(&mut fragment_id_bytes)?;
+    let fragment_id = FragmentId::from_bytes(fragment_id_bytes);
+    let output_index = codec.get_u8()?;
+    Ok((fragment_id, output_index))
+}
+
+/// Pack the changes between the `old` and the `new` ledger states.
+///
+/// Applying the delta on `old` with `apply_delta` gives back `new`.
+pub fn pack_delta<W: std::io::Write>(
+    old: &Ledger,
+    new: &Ledger,
+    writer: W,
+) -> Result<(), std::io::Error> {
+    let diff = old.diff(new);
+    let mut codec = Codec::new(writer);
+
+    codec.put_u8(DeltaSerializeCode::Globals as u8)?;
+    pack_globals(
+        &Globals {
+            date: new.date,
+            chain_length: new.chain_length,
+            static_params: (*new.static_params).clone(),
+            era: new.era.clone(),
+        },
+        &mut codec,
+    )?;
+    for entry in new.pots.entries() {
+        codec.put_u8(DeltaSerializeCode::Pot as u8)?;
+        pack_pot_entry(&entry, &mut codec)?;
+    }
+
+    for entry in diff.utxos_removed.iter() {
+        codec.put_u8(DeltaSerializeCode::UtxoRemoved as u8)?;
+        pack_utxo_pointer(entry, &mut codec)?;
+    }
+    for entry in diff.utxos_added.iter() {
+        codec.put_u8(DeltaSerializeCode::UtxoAdded as u8)?;
+        pack_utxo_entry(entry, &mut pack_address, &mut codec)?;
+    }
+    for entry in diff.old_utxos_removed.iter() {
+        codec.put_u8(DeltaSerializeCode::OldUtxoRemoved as u8)?;
+        pack_utxo_pointer(entry, &mut codec)?;
+    }
+
+    for change in diff.accounts.iter() {
+        match change {
+            Diff::Added(identifier, account_state)
+            | Diff::Changed(identifier, _, account_state) => {
+                codec.put_u8(DeltaSerializeCode::AccountSet as u8)?;
+                pack_account_identifier(identifier, &mut codec)?;
+                pack_account_state(account_state, &mut codec)?;
+            }
+            Diff::Removed(identifier, _) => {
+                codec.put_u8(DeltaSerializeCode::AccountRemoved as u8)?;
+                pack_account_identifier(identifier, &mut codec)?;
+            }
+        }
+    }
+
+    for config_param in diff.settings.iter() {
+        codec.put_u8(DeltaSerializeCode::ConfigParam as u8)?;
+        pack_config_param(config_param, &mut codec)?;
+    }
+
+    if old.updates != new.updates {
+        codec.put_u8(DeltaSerializeCode::UpdatesReset as u8)?;
+        for (proposal_id, proposal_state) in new.updates.proposals.iter() {
+            codec.put_u8(DeltaSerializeCode::UpdateProposal as u8)?;
+            pack_update_proposal_id(proposal_id, &mut codec)?;
+            pack_update_proposal_state(proposal_state, &mut codec)?;
+        }
+        for (proposal_id, proposal_state) in new.updates.treasury_transfers.iter() {
+            codec.put_u8(DeltaSerializeCode::TreasuryTransferProposal as u8)?;
+            pack_update_proposal_id(proposal_id, &mut codec)?;
+            pack_treasury_transfer_proposal_state(proposal_state, &mut codec)?;
+        }
+    }
+
+    for change in diff.multisig_accounts.iter() {
+        match change {
+            Diff::Added(identifier, account_state)
+            | Diff::Changed(identifier, _, account_state) => {
+                codec.put_u8(DeltaSerializeCode::MultisigAccountSet as u8)?;
+                pack_multisig_identifier(identifier, &mut codec)?;
+                pack_account_state(account_state, &mut codec)?;
+            }
+            Diff::Removed(identifier, _) => {
+                codec.put_u8(DeltaSerializeCode::MultisigAccountRemoved as u8)?;
+                pack_multisig_identifier(identifier, &mut codec)?;
+            }
+        }
+    }
+    for change in diff.multisig_declarations.iter() {
+        match change {
+            Diff::Added(identifier, declaration) | Diff::Changed(identifier, _, declaration) => {
+                codec.put_u8(DeltaSerializeCode::MultisigDeclarationSet as u8)?;
+                pack_multisig_identifier(identifier, &mut codec)?;
+                pack_declaration(declaration, &mut codec)?;
+            }
+            Diff::Removed(identifier, _) => {
+                codec.put_u8(DeltaSerializeCode::MultisigDeclarationRemoved as u8)?;
+                pack_multisig_identifier(identifier, &mut codec)?;
+            }
+        }
+    }
+
+    for change in diff.pools.iter() {
+        match change {
+            Diff::Added(pool_id, pool_state) | Diff::Changed(pool_id, _, pool_state) => {
+                codec.put_u8(DeltaSerializeCode::StakePoolSet as u8)?;
+                pack_digestof(pool_id, &mut codec)?;
+                pack_pool_state(pool_state, &mut codec)?;
+            }
+            Diff::Removed(pool_id, _) => {
+                codec.put_u8(DeltaSerializeCode::StakePoolRemoved as u8)?;
+                pack_digestof(pool_id, &mut codec)?;
+            }
+        }
+    }
+
+    if old.leaders_log != new.leaders_log {
+        codec.put_u8(DeltaSerializeCode::LeadersParticipationReset as u8)?;
+        for (pool_id, participation) in new.leaders_log.iter() {
+            codec.put_u8(DeltaSerializeCode::LeaderParticipation as u8)?;
+            pack_digestof(pool_id, &mut codec)?;
+            codec.put_u32(*participation)?;
+        }
+    }
+
+    if old.rewards_history != new.rewards_history {
+        codec.put_u8(DeltaSerializeCode::RewardsHistoryReset as u8)?;
+        for (epoch, history) in new.rewards_history.iter() {
+            codec.put_u8(DeltaSerializeCode::RewardsHistory as u8)?;
+            codec.put_u32(*epoch)?;
+            pack_epoch_rewards_history(history, &mut codec)?;
+        }
+    }
+
+    if old.stake_snapshots != new.stake_snapshots {
+        codec.put_u8(DeltaSerializeCode::StakeSnapshotsReset as u8)?;
+        for (epoch, distribution) in new.stake_snapshots.iter() {
+            codec.put_u8(DeltaSerializeCode::StakeSnapshot as u8)?;
+            codec.put_u32(*epoch)?;
+            pack_stake_distribution(distribution, &mut codec)?;
+        }
+    }
+
+    codec.put_u8(DeltaSerializeCode::DeltaEnd as u8)?;
+    Ok(())
+}
+
+/// Apply a delta packed with `pack_delta` on the ledger it was computed from.
+pub fn apply_delta<R: std::io::BufRead>(
+    ledger: &Ledger,
+    reader: R,
+) -> Result<Ledger, std::io::Error> {
+    let mut ledger = apply_delta_changes(ledger, reader)?;
+    ledger.stake_aggregates =
+        StakeAggregates::new_with(&ledger.accounts, &ledger.delegation, &ledger.utxos);
+    Ok(ledger)
+}
+
+/// Apply a delta without updating the stake aggregates, which have to be
+/// recomputed once the changes are applied.
+fn apply_delta_changes<R: std::io::BufRead>(
+    ledger: &Ledger,
+    reader: R,
+) -> Result<Ledger, std::io::Error> {
+    let mut codec = Codec::new(reader);
+    let mut ledger = ledger.clone();
+    let mut pending_utxos = None;
+    let mut config_params = ConfigParams::new();
+
+    loop {
+        let code_u8 = codec.get_u8()?;
+        let code = DeltaSerializeCode::from_u8(code_u8).ok_or(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Error reading delta, not recognized type code {}", code_u8),
+        ))?;
+        match code {
+            DeltaSerializeCode::Globals => {
+                let globals = unpack_globals(&mut codec)?;
+                ledger.date = globals.date;
+                ledger.chain_length = globals.chain_length;
+                ledger.static_params = Arc::new(globals.static_params);
+                ledger.era = globals.era;
+            }
+            DeltaSerializeCode::Pot => ledger.pots.set_from_entry(&unpack_pot_entry(&mut codec)?),
+            DeltaSerializeCode::UtxoAdded => {
+                let entry = unpack_utxo_entry_owned(&mut unpack_address, &mut codec)?;
+                let entry = utxo::Entry {
+                    fragment_id: entry.fragment_id,
+                    output_index: entry.output_index,
+                    output: &entry.output,
+                };
+                push_output(&mut ledger.utxos, &mut pending_utxos, entry).map_err(ledger_error)?;
+            }
+            DeltaSerializeCode::UtxoRemoved => {
+                let (fragment_id, index) = unpack_utxo_pointer(&mut codec)?;
+                ledger.utxos = ledger
+                    .utxos
+                    .remove(&fragment_id, index)
+                    .map_err(|e| ledger_error(e.into()))?
+                    .0;
+            }
+            DeltaSerializeCode::OldUtxoRemoved => {
+                let (fragment_id, index) = unpack_utxo_pointer(&mut codec)?;
+                ledger.oldutxos = ledger
+                    .oldutxos
+                    .remove(&fragment_id, index)
+                    .map_err(|e| ledger_error(e.into()))?
+                    .0;
+            }
+            DeltaSerializeCode::AccountSet => {
+                let identifier = unpack_account_identifier(&mut codec)?;
+                let account_state = unpack_account_state(&mut codec)?;
+                ledger.accounts = ledger.accounts.set_state(identifier, account_state);
+            }
+            DeltaSerializeCode::AccountRemoved => {
+                let identifier = unpack_account_identifier(&mut codec)?;
+                ledger.accounts = ledger
+                    .accounts
+                    .remove_state(&identifier)
+                    .map_err(|e| ledger_error(e.into()))?;
+            }
+            DeltaSerializeCode::ConfigParam => {
+                config_params.push(unpack_config_param(&mut codec)?);
+            }
+            DeltaSerializeCode::UpdatesReset => {
+                ledger.updates = UpdateState::new();
+            }
+            DeltaSerializeCode::UpdateProposal => {
+                let proposal_id = unpack_update_proposal_id(&mut codec)?;
+                let proposal_state = unpack_update_proposal_state(&mut codec)?;
+                ledger.updates.proposals.insert(proposal_id, proposal_state);
+            }
+            DeltaSerializeCode::TreasuryTransferProposal => {
+                let proposal_id = unpack_update_proposal_id(&mut codec)?;
+                let proposal_state = unpack_treasury_transfer_proposal_state(&mut codec)?;
+                ledger
+                    .updates
+                    .treasury_transfers
+                    .insert(proposal_id, proposal_state);
+            }
+            DeltaSerializeCode::MultisigAccountSet => {
+                let identifier = unpack_multisig_identifier(&mut codec)?;
+                let account_state = unpack_account_state(&mut codec)?;
+                ledger.multisig.accounts = ledger
+                    .multisig
+                    .accounts
+                    .set_state(identifier, account_state);
+            }
+            DeltaSerializeCode::MultisigAccountRemoved => {
+                let identifier = unpack_multisig_identifier(&mut codec)?;
+                ledger.multisig.accounts = ledger
+                    .multisig
+                    .accounts
+                    .remove_state(&identifier)
+                    .map_err(|e| ledger_error(multisig::LedgerError::from(e).into()))?;
+            }
+            DeltaSerializeCode::MultisigDeclarationSet => {
+                let identifier = unpack_multisig_identifier(&mut codec)?;
+                let declaration = unpack_declaration(&mut codec)?;
+                ledger.multisig.declarations = ledger
+                    .multisig
+                    .declarations
+                    .insert_or_update_simple(identifier, declaration.clone(), |_| {
+                        Some(declaration)
+                    });
+            }
+            DeltaSerializeCode::MultisigDeclarationRemoved => {
+                let identifier = unpack_multisig_identifier(&mut codec)?;
+                ledger.multisig.declarations = ledger
+                    .multisig
+                    .declarations
+                    .remove(&identifier)
+                    .map_err(|_| ledger_error(multisig::LedgerError::DoesntExist.into()))?;
+            }
+            DeltaSerializeCode::StakePoolSet => {
+                let pool_id = unpack_digestof(&mut codec)?;
+                let pool_state = unpack_pool_state(&mut codec)?;
+                ledger.delegation.stake_pools = ledger
+                    .delegation
+                    .stake_pools
+                    .insert_or_update_simple(pool_id, pool_state.clone(), |_| Some(pool_state));
+            }
+            DeltaSerializeCode::StakePoolRemoved => {
+                let pool_id = unpack_digestof(&mut codec)?;
+                ledger.delegation = ledger
+                    .delegation
+                    .deregister_stake_pool(&pool_id)
+                    .map_err(|e| ledger_error(e.into()))?;
+            }
+            DeltaSerializeCode::LeadersParticipationReset => {
+                ledger.leaders_log = LeadersParticipationRecord::new();
+            }
+            DeltaSerializeCode::LeaderParticipation => {
+                let pool_id = unpack_digestof(&mut codec)?;
+                let participation = codec.get_u32()?;
+                ledger
+                    .leaders_log
+                    .set_for(pool_id, participation)
+                    .map_err(|e| {
+                        std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{:?}", e))
+                    })?;
+            }
+            DeltaSerializeCode::RewardsHistoryReset => {
+                ledger.rewards_history = RewardsHistory::new();
+            }
+            DeltaSerializeCode::RewardsHistory => {
+                let epoch = codec.get_u32()?;
+                let history = unpack_epoch_rewards_history(&mut codec)?;
+                ledger
+                    .rewards_history
+                    .set_for(epoch, history)
+                    .map_err(|e| {
+                        std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{:?}", e))
+                    })?;
+            }
+            DeltaSerializeCode::StakeSnapshotsReset => {
+                ledger.stake_snapshots = StakeSnapshots::new();
+            }
+            DeltaSerializeCode::StakeSnapshot => {
+                let epoch = codec.get_u32()?;
+                let distribution = unpack_stake_distribution(&mut codec)?;
+                ledger
+                    .stake_snapshots
+                    .set_for(epoch, distribution)
+                    .map_err(|e| {
+                        std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{:?}", e))
+                    })?;
+            }
+            DeltaSerializeCode::DeltaEnd => break,
         }
     }
+
+    flush_outputs(&mut ledger.utxos, &mut pending_utxos).map_err(ledger_error)?;
+    ledger.settings = ledger
+        .settings
+        .apply(&config_params)
+        .map_err(|e| ledger_error(e.into()))?;
+    Ok(ledger)
+}
+
+/// Restore a ledger from a snapshot: a serialized ledger followed by any
+/// number of deltas packed with `pack_delta`, up to the end of the reader.
+pub fn restore_snapshot<R: std::io::BufRead>(mut reader: R) -> Result<Ledger, std::io::Error> {
+    let mut ledger = Ledger::deserialize(&mut reader)?;
+    if reader.fill_buf()?.is_empty() {
+        return Ok(ledger);
+    }
+    while !reader.fill_buf()?.is_empty() {
+        ledger = apply_delta_changes(&ledger, &mut reader)?;
+    }
+    // the stake aggregates are only recomputed once all the deltas are applied
+    ledger.stake_aggregates =
+        StakeAggregates::new_with(&ledger.accounts, &ledger.delegation, &ledger.utxos);
+    Ok(ledger)
 }
 
 #[cfg(any(test, feature = "property-test-api"))]
 pub mod test {
     use super::*;
-    use crate::testing::{ConfigBuilder, LedgerBuilder, StakePoolBuilder};
+    use crate::testing::{
+        builders::TestTxBuilder,
+        data::AddressDataValue,
+        scenario::{prepare_scenario, wallet},
+        ConfigBuilder, LedgerBuilder, StakePoolBuilder,
+    };
     use cardano_legacy_address::Addr;
     use chain_crypto::Blake2b256;
     use quickcheck::{quickcheck, TestResult};
@@ -1172,6 +1892,7 @@ pub mod test {
             pots::Entry::Fees(Value(10)),
             pots::Entry::Rewards(Value(10)),
             pots::Entry::Treasury(Value(10)),
+            pots::Entry::Deposits(Value(10)),
         ]
         .iter()
         {
@@ -1284,6 +2005,132 @@ pub mod test {
         Ok(())
     }
 
+    #[test]
+    pub fn ledger_with_stake_pools_serialize_deserialize_bijection() -> Result<(), std::io::Error> {
+        let (test_ledger, _) = prepare_scenario()
+            .with_initials(vec![wallet("Alice")
+                .with(1_000)
+                .owns_and_delegates_to("alice_pool")])
+            .build()
+            .unwrap();
+
+        let ledger: Ledger = test_ledger.into();
+        assert_eq!(ledger.delegation.stake_pool_ids().count(), 1);
+        let mut c = std::io::Cursor::new(Vec::new());
+        ledger.serialize(&mut c)?;
+        c.set_position(0);
+        let other_ledger = Ledger::deserialize(&mut c)?;
+        assert_eq!(ledger, other_ledger);
+        Ok(())
+    }
+
+    #[test]
+    pub fn delta_applied_gives_new_ledger() -> Result<(), std::io::Error> {
+        let (mut test_ledger, controller) = prepare_scenario()
+            .with_initials(vec![
+                wallet("Alice")
+                    .with(1_000)
+                    .owns_and_delegates_to("alice_pool"),
+                wallet("Bob").with(500),
+            ])
+            .build()
+            .unwrap();
+        let mut alice = controller.wallet("Alice").unwrap();
+        let bob = controller.wallet("Bob").unwrap();
+        let alice_pool = controller.stake_pool("alice_pool").unwrap();
+
+        let old_ledger = test_ledger.ledger.clone();
+        controller
+            .transfer_funds(&alice, &bob, &mut test_ledger, 100)
+            .unwrap();
+        alice.confirm_transaction();
+        controller
+            .retire(&[&alice], &alice_pool, &mut test_ledger)
+            .unwrap();
+
+        let mut c = std::io::Cursor::new(Vec::new());
+        pack_delta(&old_ledger, &test_ledger.ledger, &mut c)?;
+        c.set_position(0);
+        let new_ledger = apply_delta(&old_ledger, &mut c)?;
+        assert_eq!(new_ledger, test_ledger.ledger);
+        Ok(())
+    }
+
+    #[test]
+    pub fn snapshot_with_deltas_restores_last_ledger() -> Result<(), std::io::Error> {
+        let faucet = AddressDataValue::utxo(Discrimination::Test, Value(1_000));
+        let receiver = AddressDataValue::utxo(Discrimination::Test, Value(0));
+        let account = AddressDataValue::account(Discrimination::Test, Value(0));
+        let mut test_ledger = LedgerBuilder::from_config(ConfigBuilder::new(0))
+            .initial_fund(&faucet)
+            .build()
+            .expect("cannot build test ledger");
+
+        let mut c = std::io::Cursor::new(Vec::new());
+        test_ledger.ledger.serialize(&mut c)?;
+
+        let mut last_ledger = test_ledger.ledger.clone();
+        for (from, to) in vec![(&faucet, &receiver), (&receiver, &account)] {
+            let fragment = TestTxBuilder::new(&test_ledger.block0_hash)
+                .move_funds(&mut test_ledger, from, to, &faucet.value)
+                .get_fragment();
+            test_ledger.apply_transaction(fragment).unwrap();
+            pack_delta(&last_ledger, &test_ledger.ledger, &mut c)?;
+            last_ledger = test_ledger.ledger.clone();
+        }
+
+        c.set_position(0);
+        let restored_ledger = restore_snapshot(&mut c)?;
+        assert_eq!(restored_ledger, test_ledger.ledger);
+        Ok(())
+    }
+
+    #[test]
+    pub fn epoch_rewards_history_pack_unpack_bijection() -> Result<(), std::io::Error> {
+        let stake_pool = StakePoolBuilder::new().build();
+        let account_id = crate::testing::data::AddressData::account(Discrimination::Test).to_id();
+        let multisig_id = crate::multisig::Identifier::from([0x1; 32]);
+        let mut history = EpochRewardsHistory::new(true);
+        history.add_to_account(&account_id, Value(10));
+        history.add_to_multisig_account(&multisig_id, Value(20));
+        history.set_stake_pool(&stake_pool.id(), Value(1), Value(29));
+
+        let mut c: Cursor<Vec<u8>> = Cursor::new(Vec::new());
+        let mut codec = Codec::new(c);
+        pack_epoch_rewards_history(&history, &mut codec)?;
+        c = codec.into_inner();
+        c.set_position(0);
+        codec = Codec::new(c);
+        let other_history = unpack_epoch_rewards_history(&mut codec)?;
+        assert!(history == other_history);
+        Ok(())
+    }
+
+    #[test]
+    pub fn stake_distribution_pack_unpack_bijection() -> Result<(), std::io::Error> {
+        let (test_ledger, _) = prepare_scenario()
+            .with_initials(vec![
+                wallet("Alice")
+                    .with(1_000)
+                    .owns_and_delegates_to("alice_pool"),
+                wallet("Bob").with(500).delegates_to("alice_pool"),
+                wallet("Clarice").with(200),
+            ])
+            .build()
+            .unwrap();
+        let distribution = test_ledger.ledger.get_stake_distribution();
+
+        let mut c: Cursor<Vec<u8>> = Cursor::new(Vec::new());
+        let mut codec = Codec::new(c);
+        pack_stake_distribution(&distribution, &mut codec)?;
+        c = codec.into_inner();
+        c.set_position(0);
+        codec = Codec::new(c);
+        let other_distribution = unpack_stake_distribution(&mut codec)?;
+        assert_eq!(distribution, other_distribution);
+        Ok(())
+    }
+
     fn pack_unpack_bijection<T, Pack, Unpack>(
         pack_method: &mut Pack,
         unpack_method: &mut Unpack,
@@ -1359,6 +2206,14 @@ pub mod test {
             )
         }
 
+        fn per_extended_certificate_fee_pack_unpack_bijection(per_extended_certificate_fee: PerExtendedCertificateFee) -> TestResult {
+            pack_unpack_bijection(
+                &mut pack_per_extended_certificate_fee,
+                &mut unpack_per_extended_certificate_fee,
+                &per_extended_certificate_fee
+            )
+        }
+
         fn linear_fee_pack_unpack_bijection(linear_fee: LinearFee) -> TestResult {
             pack_unpack_bijection(
                 &mut pack_linear_fee,
@@ -1414,5 +2269,13 @@ pub mod test {
                 &update_proposal_state
             )
         }
+
+        fn treasury_transfer_proposal_state_pack_unpack_bijection(proposal_state: TreasuryTransferProposalState) -> TestResult {
+            pack_unpack_bijection(
+                &mut pack_treasury_transfer_proposal_state,
+                &mut unpack_treasury_transfer_proposal_state,
+                &proposal_state
+            )
+        }
     }
 }
@@ -0,0 +1,243 @@
+use crate::certificate::PoolId;
+use crate::date::Epoch;
+use crate::value::Value;
+use crate::{account, multisig};
+use imhamt::{Hamt, HamtIter, InsertError};
+use std::collections::hash_map::DefaultHasher;
+
+/// Rewards distributed during one epoch, by account and by stake pool.
+///
+/// Recording can be turned off, in which case every addition is a no-op,
+/// so the distribution code doesn't need to care whether history is kept.
+#[derive(Clone, PartialEq, Eq)]
+pub struct EpochRewardsHistory {
+    enabled: bool,
+    pub(crate) accounts: Hamt<DefaultHasher, account::Identifier, Value>,
+    pub(crate) multisig_accounts: Hamt<DefaultHasher, multisig::Identifier, Value>,
+    /// Amount taxed by the pool and amount distributed to its delegators
+    pub(crate) stake_pools: Hamt<DefaultHasher, PoolId, (Value, Value)>,
+}
+
+impl EpochRewardsHistory {
+    /// new empty epoch history, recording only if `enabled`
+    pub fn new(enabled: bool) -> Self {
+        EpochRewardsHistory {
+            enabled,
+            accounts: Hamt::new(),
+            multisig_accounts: Hamt::new(),
+            stake_pools: Hamt::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn add_to_account(&mut self, account: &account::Identifier, value: Value) {
+        if self.enabled {
+            self.accounts = self
+                .accounts
+                .insert_or_update_simple(account.clone(), value, |v| Some((*v + value).unwrap()));
+        }
+    }
+
+    pub fn add_to_multisig_account(&mut self, account: &multisig::Identifier, value: Value) {
+        if self.enabled {
+            self.multisig_accounts =
+                self.multisig_accounts
+                    .insert_or_update_simple(account.clone(), value, |v| {
+                        Some((*v + value).unwrap())
+                    });
+        }
+    }
+
+    pub fn set_stake_pool(&mut self, pool: &PoolId, taxed: Value, distributed: Value) {
+        if self.enabled {
+            self.stake_pools = self.stake_pools.insert_or_update_simple(
+                pool.clone(),
+                (taxed, distributed),
+                |_| Some((taxed, distributed)),
+            );
+        }
+    }
+
+    pub fn account(&self, account: &account::Identifier) -> Option<Value> {
+        self.accounts.lookup(account).copied()
+    }
+
+    pub fn multisig_account(&self, account: &multisig::Identifier) -> Option<Value> {
+        self.multisig_accounts.lookup(account).copied()
+    }
+
+    pub fn stake_pool(&self, pool: &PoolId) -> Option<(Value, Value)> {
+        self.stake_pools.lookup(pool).copied()
+    }
+
+    pub fn accounts<'a>(&'a self) -> HamtIter<'a, account::Identifier, Value> {
+        self.accounts.iter()
+    }
+
+    pub fn multisig_accounts<'a>(&'a self) -> HamtIter<'a, multisig::Identifier, Value> {
+        self.multisig_accounts.iter()
+    }
+
+    pub fn stake_pools<'a>(&'a self) -> HamtIter<'a, PoolId, (Value, Value)> {
+        self.stake_pools.iter()
+    }
+}
+
+/// Per epoch history of the rewards received by accounts and stake pools.
+///
+/// Only the rewards of the last `retention` epochs are kept, the older
+/// epochs being dropped as new ones get recorded.
+#[derive(Clone, PartialEq, Eq)]
+pub struct RewardsHistory {
+    epochs: Hamt<DefaultHasher, Epoch, EpochRewardsHistory>,
+}
+
+impl Default for RewardsHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RewardsHistory {
+    /// new empty rewards history
+    pub fn new() -> Self {
+        Self {
+            epochs: Hamt::new(),
+        }
+    }
+
+    /// Record the rewards of a given epoch, and drop all the epochs
+    /// falling out of the retention window.
+    ///
+    /// With a retention of zero, nothing is kept.
+    pub fn record(&self, epoch: Epoch, history: EpochRewardsHistory, retention: u32) -> Self {
+        let mut epochs = self.epochs.clone();
+        let expired: Vec<Epoch> = epochs
+            .iter()
+            .map(|(e, _)| *e)
+            .filter(|e| *e as u64 + retention as u64 <= epoch as u64)
+            .collect();
+        for e in expired {
+            epochs = epochs.remove(&e).unwrap();
+        }
+        if retention > 0 {
+            epochs = epochs.insert_or_update_simple(epoch, history.clone(), |_| Some(history));
+        }
+        Self { epochs }
+    }
+
+    /// Set the rewards of an epoch.
+    ///
+    /// if the epoch already exists, then it returns an insert error.
+    /// This should only be used related to the iterator construction,
+    pub fn set_for(
+        &mut self,
+        epoch: Epoch,
+        history: EpochRewardsHistory,
+    ) -> Result<(), InsertError> {
+        self.epochs = self.epochs.insert(epoch, history)?;
+        Ok(())
+    }
+
+    /// Get the rewards distributed during a specific epoch
+    pub fn epoch(&self, epoch: Epoch) -> Option<&EpochRewardsHistory> {
+        self.epochs.lookup(&epoch)
+    }
+
+    /// Rewards received by an account, ordered by epoch
+    pub fn account(&self, account: &account::Identifier) -> Vec<(Epoch, Value)> {
+        self.collect(|history| history.account(account))
+    }
+
+    /// Rewards received by a multisig account, ordered by epoch
+    pub fn multisig_account(&self, account: &multisig::Identifier) -> Vec<(Epoch, Value)> {
+        self.collect(|history| history.multisig_account(account))
+    }
+
+    /// Rewards (taxed, distributed) of a stake pool, ordered by epoch
+    pub fn stake_pool(&self, pool: &PoolId) -> Vec<(Epoch, (Value, Value))> {
+        self.collect(|history| history.stake_pool(pool))
+    }
+
+    /// Iterate over all the recorded epochs, in no particular order
+    pub fn iter<'a>(&'a self) -> HamtIter<'a, Epoch, EpochRewardsHistory> {
+        self.epochs.iter()
+    }
+
+    fn collect<T, F>(&self, f: F) -> Vec<(Epoch, T)>
+    where
+        F: Fn(&EpochRewardsHistory) -> Option<T>,
+    {
+        let mut res: Vec<(Epoch, T)> = self
+            .epochs
+            .iter()
+            .filter_map(|(epoch, history)| f(history).map(|v| (*epoch, v)))
+            .collect();
+        res.sort_by_key(|(epoch, _)| *epoch);
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::builders::StakePoolBuilder;
+    use crate::testing::data::AddressData;
+    use chain_addr::Discrimination;
+
+    fn history_for(account: &account::Identifier, value: Value) -> EpochRewardsHistory {
+        let mut history = EpochRewardsHistory::new(true);
+        history.add_to_account(account, value);
+        history
+    }
+
+    #[test]
+    pub fn disabled_epoch_history_is_empty() {
+        let account = AddressData::account(Discrimination::Test).to_id();
+        let pool_id = StakePoolBuilder::new().build().id();
+        let mut history = EpochRewardsHistory::new(false);
+        history.add_to_account(&account, Value(10));
+        history.set_stake_pool(&pool_id, Value(1), Value(2));
+
+        assert_eq!(history.accounts().count(), 0);
+        assert_eq!(history.stake_pools().count(), 0);
+    }
+
+    #[test]
+    pub fn account_rewards_are_summed_within_epoch() {
+        let account = AddressData::account(Discrimination::Test).to_id();
+        let mut history = history_for(&account, Value(10));
+        history.add_to_account(&account, Value(5));
+
+        assert_eq!(history.account(&account), Some(Value(15)));
+    }
+
+    #[test]
+    pub fn record_keeps_only_retention_window() {
+        let account = AddressData::account(Discrimination::Test).to_id();
+        let mut rewards_history = RewardsHistory::new();
+        for epoch in 1..=5 {
+            rewards_history =
+                rewards_history.record(epoch, history_for(&account, Value(epoch as u64)), 3);
+        }
+
+        assert_eq!(
+            rewards_history.account(&account),
+            vec![(3, Value(3)), (4, Value(4)), (5, Value(5))]
+        );
+        assert!(rewards_history.epoch(2).is_none());
+    }
+
+    #[test]
+    pub fn record_with_zero_retention_drops_everything() {
+        let account = AddressData::account(Discrimination::Test).to_id();
+        let rewards_history = RewardsHistory::new()
+            .record(1, history_for(&account, Value(1)), 2)
+            .record(2, history_for(&account, Value(2)), 0);
+
+        assert_eq!(rewards_history.iter().count(), 0);
+    }
+}
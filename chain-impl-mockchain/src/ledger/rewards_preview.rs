@@ -0,0 +1,54 @@
+use super::ledger::{Error, Ledger, LedgerParameters};
+use super::reward_info::{EpochRewardsInfo, RewardsInfoParameters};
+use crate::date::Epoch;
+use crate::stake::StakeDistribution;
+
+impl Ledger {
+    /// Compute the rewards that would be distributed at the end of the
+    /// current epoch, without modifying the ledger.
+    ///
+    /// The stake distribution and the ledger parameters don't have to match
+    /// the ones of the ledger, which allow to evaluate hypothetical settings
+    /// (e.g. a different pool tax or treasury tax) before submitting them.
+    pub fn preview_rewards(
+        &self,
+        distribution: &StakeDistribution,
+        ledger_params: &LedgerParameters,
+    ) -> Result<EpochRewardsInfo, Error> {
+        self.distribute_rewards(
+            distribution,
+            ledger_params,
+            RewardsInfoParameters::report_all(),
+        )
+        .map(|(_, rewards_info)| rewards_info)
+    }
+
+    /// Project the rewards distribution over the next `nb_epochs` epochs.
+    ///
+    /// The leader participation of the current epoch and the given stake
+    /// distribution are assumed to stay constant for every projected epoch,
+    /// so only the reward contribution (following its compounding type) and
+    /// the remaining rewards pot evolve from one epoch to the next.
+    pub fn project_rewards(
+        &self,
+        distribution: &StakeDistribution,
+        ledger_params: &LedgerParameters,
+        nb_epochs: u32,
+    ) -> Result<Vec<(Epoch, EpochRewardsInfo)>, Error> {
+        let mut projection = Vec::with_capacity(nb_epochs as usize);
+        let mut ledger = self.clone();
+        for _ in 0..nb_epochs {
+            let epoch = ledger.date.epoch + 1;
+            let (mut next, rewards_info) = ledger.distribute_rewards(
+                distribution,
+                ledger_params,
+                RewardsInfoParameters::report_all(),
+            )?;
+            next.leaders_log = self.leaders_log.clone();
+            next.date.epoch = epoch;
+            projection.push((epoch, rewards_info));
+            ledger = next;
+        }
+        Ok(projection)
+    }
+}
@@ -0,0 +1,137 @@
+use crate::date::Epoch;
+use crate::stake::StakeDistribution;
+use imhamt::{Hamt, HamtIter, InsertError};
+use std::collections::hash_map::DefaultHasher;
+use std::sync::Arc;
+
+/// Number of snapshots kept: the one the leaders of the current epoch were
+/// elected with, and the one the leaders of the next epoch will be elected with.
+const SNAPSHOTS_KEPT: usize = 2;
+
+/// Stake distributions captured at the start of the last epochs.
+///
+/// A snapshot is taken from the ledger state just before the first block of
+/// an epoch, so it holds the stake at the end of the previous epoch. The
+/// leaders of an epoch are elected (and rewarded) with the stake of two epochs
+/// back, so stake moved right before an epoch boundary cannot influence the
+/// upcoming epoch.
+#[derive(Clone, PartialEq, Eq)]
+pub struct StakeSnapshots {
+    epochs: Hamt<DefaultHasher, Epoch, Arc<StakeDistribution>>,
+}
+
+impl Default for StakeSnapshots {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StakeSnapshots {
+    /// new empty snapshots
+    pub fn new() -> Self {
+        Self {
+            epochs: Hamt::new(),
+        }
+    }
+
+    /// Record the stake distribution at the start of the given epoch, and
+    /// drop the snapshots that are not needed anymore.
+    pub fn record(&self, epoch: Epoch, distribution: StakeDistribution) -> Self {
+        let mut epochs =
+            self.epochs
+                .insert_or_update_simple(epoch, Arc::new(distribution.clone()), |_| {
+                    Some(Arc::new(distribution))
+                });
+        let mut recorded: Vec<Epoch> = epochs.iter().map(|(e, _)| *e).collect();
+        recorded.sort_unstable();
+        let expired = recorded.len().saturating_sub(SNAPSHOTS_KEPT);
+        for e in &recorded[..expired] {
+            epochs = epochs.remove(e).unwrap();
+        }
+        Self { epochs }
+    }
+
+    /// Set the snapshot of an epoch.
+    ///
+    /// if the epoch already exists, then it returns an insert error.
+    /// This should only be used related to the iterator construction,
+    pub fn set_for(
+        &mut self,
+        epoch: Epoch,
+        distribution: StakeDistribution,
+    ) -> Result<(), InsertError> {
+        self.epochs = self.epochs.insert(epoch, Arc::new(distribution))?;
+        Ok(())
+    }
+
+    /// Get the snapshot taken at the start of a specific epoch
+    pub fn epoch(&self, epoch: Epoch) -> Option<&StakeDistribution> {
+        self.epochs
+            .lookup(&epoch)
+            .map(|distribution| distribution.as_ref())
+    }
+
+    /// Get the stake distribution to use for the leader election of `epoch`:
+    /// the latest snapshot taken at the start of `epoch - 1` or before.
+    ///
+    /// The first epochs use the snapshot of the block0.
+    pub fn for_leader_election(&self, epoch: Epoch) -> Option<&StakeDistribution> {
+        let snapshot_epoch = epoch.saturating_sub(1);
+        self.epochs
+            .iter()
+            .filter(|(e, _)| **e <= snapshot_epoch)
+            .max_by_key(|(e, _)| **e)
+            .map(|(_, distribution)| distribution.as_ref())
+    }
+
+    /// Iterate over all the recorded epochs, in no particular order
+    pub fn iter<'a>(&'a self) -> HamtIter<'a, Epoch, Arc<StakeDistribution>> {
+        self.epochs.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stake::Stake;
+    use crate::value::Value;
+
+    fn distribution(unassigned: u64) -> StakeDistribution {
+        let mut distribution = StakeDistribution::empty();
+        distribution.unassigned = Stake::from_value(Value(unassigned));
+        distribution
+    }
+
+    fn unassigned(distribution: Option<&StakeDistribution>) -> Option<u64> {
+        distribution.map(|d| d.unassigned.into())
+    }
+
+    #[test]
+    pub fn leader_election_uses_two_epochs_back() {
+        let snapshots = StakeSnapshots::new().record(0, distribution(0));
+
+        assert_eq!(unassigned(snapshots.for_leader_election(0)), Some(0));
+        assert_eq!(unassigned(snapshots.for_leader_election(1)), Some(0));
+
+        let snapshots = snapshots.record(1, distribution(1));
+        assert_eq!(unassigned(snapshots.for_leader_election(1)), Some(0));
+        assert_eq!(unassigned(snapshots.for_leader_election(2)), Some(1));
+
+        // no block during epoch 2, epoch 3 still uses the snapshot of epoch 1
+        let snapshots = snapshots.record(3, distribution(3));
+        assert_eq!(unassigned(snapshots.for_leader_election(3)), Some(1));
+        assert_eq!(unassigned(snapshots.for_leader_election(4)), Some(3));
+    }
+
+    #[test]
+    pub fn only_last_snapshots_are_kept() {
+        let snapshots = (0..5).fold(StakeSnapshots::new(), |snapshots, epoch| {
+            snapshots.record(epoch, distribution(epoch as u64))
+        });
+
+        assert_eq!(snapshots.iter().count(), SNAPSHOTS_KEPT);
+        assert!(snapshots.epoch(2).is_none());
+        assert_eq!(unassigned(snapshots.epoch(3)), Some(3));
+        assert_eq!(unassigned(snapshots.epoch(4)), Some(4));
+    }
+}
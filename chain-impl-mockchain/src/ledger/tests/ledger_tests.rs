@@ -17,8 +17,10 @@ use crate::{
         ledger::{ConfigBuilder, LedgerBuilder},
         TestGen,
     },
+    value::Value,
 };
 use chain_addr::Discrimination;
+use imhamt::Diff;
 use quickcheck::TestResult;
 use quickcheck_macros::quickcheck;
 
@@ -240,6 +242,7 @@ pub fn wrong_fragment_at_block0(fragment: Fragment) -> TestResult {
         Fragment::Transaction(_) => return TestResult::discard(),
         Fragment::StakeDelegation(_) => return TestResult::discard(),
         Fragment::PoolRegistration(_) => return TestResult::discard(),
+        Fragment::MultisigDeclaration(_) => return TestResult::discard(),
         _ => (),
     };
 
@@ -255,3 +258,47 @@ pub fn wrong_fragment_at_block0(fragment: Fragment) -> TestResult {
 
     TestResult::from_bool(Ledger::new(header_id, vec![&Fragment::Initial(ie), &fragment]).is_err())
 }
+
+#[test]
+pub fn diff_of_same_ledger_is_empty() {
+    let faucet = AddressDataValue::account(Discrimination::Test, Value(1_000));
+    let ledger = LedgerBuilder::from_config(ConfigBuilder::new(0))
+        .initial_fund(&faucet)
+        .build()
+        .unwrap();
+    assert!(ledger.ledger.diff(&ledger.ledger).is_empty());
+}
+
+#[test]
+pub fn diff_after_transaction() {
+    let faucet = AddressDataValue::utxo(Discrimination::Test, Value(1_000));
+    let receiver = AddressDataValue::account(Discrimination::Test, Value(0));
+    let mut ledger = LedgerBuilder::from_config(ConfigBuilder::new(0))
+        .initial_fund(&faucet)
+        .build()
+        .unwrap();
+    let old_ledger = ledger.ledger.clone();
+    let fragment = TestTxBuilder::new(&ledger.block0_hash)
+        .move_funds(&mut ledger, &faucet, &receiver, &faucet.value)
+        .get_fragment();
+    ledger.apply_transaction(fragment).unwrap();
+
+    let diff = old_ledger.diff(&ledger.ledger);
+    assert!(diff.utxos_added.is_empty());
+    assert_eq!(diff.utxos_removed.len(), 1);
+    assert_eq!(
+        diff.utxos_removed[0].output.address,
+        faucet.address_data.address()
+    );
+    assert_eq!(diff.accounts.len(), 1);
+    match diff.accounts[0] {
+        Diff::Added(id, state) => {
+            assert_eq!(id, &receiver.address_data.to_id());
+            assert_eq!(state.value, faucet.value);
+        }
+        ref other => panic!("unexpected account change: {:?}", other),
+    }
+    assert!(diff.pools.is_empty());
+    assert!(diff.pots.is_none());
+    assert!(diff.settings.iter().next().is_none());
+}
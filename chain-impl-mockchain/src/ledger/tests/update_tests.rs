@@ -1,16 +1,32 @@
 use crate::{
     block::Block,
     chaintypes::HeaderId,
+    config::ConfigParam,
     date::BlockDate,
     fragment::Contents,
     header::{BlockVersion, HeaderBuilderNew},
     ledger::ledger::Ledger,
+    rewards::Ratio,
+    stake::Stake,
     testing::arbitrary::update_proposal::UpdateProposalData,
-    testing::{ConfigBuilder, LedgerBuilder},
+    testing::{
+        builders::{build_pool_vote, build_proposal},
+        data::{AddressData, LeaderPair},
+        scenario::{prepare_scenario, wallet},
+        ConfigBuilder, LedgerBuilder, TestGen,
+    },
+    transaction::Output,
+    update::{
+        SignedTreasuryTransferProposal, SignedUpdateVote, TreasuryTransferProposal,
+        TreasuryTransferProposalWithProposer, UpdateVote,
+    },
+    value::Value,
 };
+use chain_addr::Discrimination;
 use chain_crypto::{Ed25519, SecretKey};
 use quickcheck::TestResult;
 use quickcheck_macros::quickcheck;
+use std::num::NonZeroU64;
 
 #[quickcheck]
 pub fn ledger_adopt_settings_from_update_proposal(
@@ -80,6 +96,184 @@ pub fn ledger_adopt_settings_from_update_proposal(
         }
 }
 
+#[test]
+pub fn ledger_pays_accepted_treasury_transfer() {
+    let leader = TestGen::leader_pair();
+    let receiver = AddressData::account(Discrimination::Test);
+    let cb = ConfigBuilder::new(0)
+        .with_leaders(&vec![leader.id()])
+        .with_treasury(Value(1000));
+
+    let testledger = LedgerBuilder::from_config(cb)
+        .build()
+        .expect("cannot build test ledger");
+    let mut ledger = testledger.ledger;
+
+    let proposal = SignedTreasuryTransferProposal {
+        proposal: TreasuryTransferProposalWithProposer {
+            proposal: TreasuryTransferProposal {
+                transfer: Output::from_address(receiver.address.clone(), Value(400)),
+            },
+            proposer_id: leader.id(),
+        },
+    };
+    let proposal_id = TestGen::hash();
+    let vote = SignedUpdateVote {
+        vote: UpdateVote {
+            proposal_id,
+            voter_id: leader.id(),
+        },
+    };
+
+    let date = ledger.date();
+    ledger = ledger
+        .apply_treasury_transfer_proposal(proposal_id, &proposal, date)
+        .unwrap()
+        .apply_update_vote(&vote)
+        .unwrap();
+    assert_eq!(ledger.pots.treasury_value(), Value(1000));
+
+    let block = build_block(
+        &ledger,
+        testledger.block0_hash,
+        date.next_epoch(),
+        &leader.leader_key,
+    );
+    let header_meta = block.header.to_content_eval_context();
+    ledger = ledger
+        .apply_block(
+            &ledger.get_ledger_parameters(),
+            &block.contents,
+            &header_meta,
+        )
+        .unwrap();
+
+    assert_eq!(ledger.pots.treasury_value(), Value(600));
+    assert_eq!(
+        ledger
+            .accounts()
+            .get_state(&receiver.to_id())
+            .unwrap()
+            .value,
+        Value(400)
+    );
+    assert!(ledger.updates.treasury_transfers.is_empty());
+}
+
+/// Alice's pool holds 1000 of stake and Bob's pool 300. Each pool votes on a
+/// proposal to change the block content max size, Alice moves
+/// `moved_to_bob` of her stake to Bob, then the epoch changes.
+fn stake_pools_vote_on_proposal(
+    alice_approves: Option<bool>,
+    bob_approves: Option<bool>,
+    moved_to_bob: u64,
+) -> Ledger {
+    let leaders: Vec<LeaderPair> = TestGen::leaders_pairs().take(2).collect();
+    let (mut test_ledger, controller) = prepare_scenario()
+        .with_config(
+            ConfigBuilder::new(0)
+                .with_leaders(&leaders.iter().map(|leader| leader.id()).collect())
+                .with_stake_voting_quorum(Ratio {
+                    numerator: 1,
+                    denominator: NonZeroU64::new(2).unwrap(),
+                }),
+        )
+        .with_initials(vec![
+            wallet("Alice")
+                .with(1_000)
+                .owns_and_delegates_to("alice_pool"),
+            wallet("Bob").with(300).owns_and_delegates_to("bob_pool"),
+        ])
+        .build()
+        .unwrap();
+    if moved_to_bob > 0 {
+        let alice = controller.wallet("Alice").unwrap();
+        let bob = controller.wallet("Bob").unwrap();
+        controller
+            .transfer_funds(&alice, &bob, &mut test_ledger, moved_to_bob)
+            .unwrap();
+    }
+    let mut ledger = test_ledger.ledger;
+
+    let proposal = build_proposal(
+        leaders[0].id(),
+        vec![ConfigParam::BlockContentMaxSize(1_000)],
+    );
+    let proposal_id = TestGen::hash();
+    let date = ledger.date();
+    ledger = ledger
+        .apply_update_proposal(proposal_id, &proposal, date)
+        .unwrap();
+
+    let votes = vec![
+        ("Alice", "alice_pool", alice_approves),
+        ("Bob", "bob_pool", bob_approves),
+    ];
+    for (owner, pool, approve) in votes {
+        if let Some(approve) = approve {
+            let owner = controller.wallet(owner).unwrap();
+            let pool = controller.stake_pool(pool).unwrap();
+            let vote = build_pool_vote(proposal_id, pool.id(), approve, &[owner.private_key()]);
+            ledger = ledger.apply_pool_update_vote(&vote).unwrap();
+        }
+    }
+
+    let block = build_block(
+        &ledger,
+        test_ledger.block0_hash,
+        date.next_epoch(),
+        &leaders[0].leader_key,
+    );
+    let header_meta = block.header.to_content_eval_context();
+    ledger
+        .apply_block(
+            &ledger.get_ledger_parameters(),
+            &block.contents,
+            &header_meta,
+        )
+        .unwrap()
+}
+
+#[test]
+pub fn ledger_adopt_settings_accepted_by_stake_pools() {
+    let ledger = stake_pools_vote_on_proposal(Some(true), Some(false), 0);
+
+    assert_eq!(ledger.settings.block_content_max_size, 1_000);
+    assert!(ledger.updates.proposals.is_empty());
+}
+
+#[test]
+pub fn ledger_rejects_settings_without_stake_voting_quorum() {
+    let ledger = stake_pools_vote_on_proposal(None, Some(true), 0);
+
+    assert_ne!(ledger.settings.block_content_max_size, 1_000);
+    let proposal = ledger.updates.proposals.values().next().unwrap();
+    assert_eq!(proposal.tally.approve, Stake(300));
+    assert_eq!(proposal.tally.reject, Stake(0));
+    assert_eq!(proposal.tally.total, Stake(1_300));
+}
+
+#[test]
+pub fn ledger_rejects_settings_refused_by_stake_pools() {
+    let ledger = stake_pools_vote_on_proposal(Some(false), Some(true), 0);
+
+    assert_ne!(ledger.settings.block_content_max_size, 1_000);
+    let proposal = ledger.updates.proposals.values().next().unwrap();
+    assert_eq!(proposal.tally.approve, Stake(300));
+    assert_eq!(proposal.tally.reject, Stake(1_000));
+}
+
+#[test]
+pub fn ledger_tallies_pool_votes_with_the_epoch_stake() {
+    // with the live stake, Bob's pool would hold 1100 and Alice's 200
+    let ledger = stake_pools_vote_on_proposal(Some(false), Some(true), 800);
+
+    assert_ne!(ledger.settings.block_content_max_size, 1_000);
+    let proposal = ledger.updates.proposals.values().next().unwrap();
+    assert_eq!(proposal.tally.approve, Stake(300));
+    assert_eq!(proposal.tally.reject, Stake(1_000));
+}
+
 fn build_block(
     ledger: &Ledger,
     block0_hash: HeaderId,
@@ -0,0 +1,178 @@
+use super::ledger::{Error, Ledger, LedgerParameters};
+use crate::chaineval::HeaderContentEvalContext;
+use crate::fragment::{Contents, FragmentId};
+
+/// A fragment of a block which failed to apply
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FragmentValidationError {
+    /// position of the fragment in the block contents
+    pub index: usize,
+    pub fragment_id: FragmentId,
+    pub error: Error,
+}
+
+/// Every issue found in a block, as computed by `Ledger::validate_block`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BlockValidationReport {
+    /// issues of the block as a whole: contents size and hash, chain length,
+    /// date and processing of the update proposals
+    pub block_errors: Vec<Error>,
+    pub fragment_errors: Vec<FragmentValidationError>,
+}
+
+impl BlockValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.block_errors.is_empty() && self.fragment_errors.is_empty()
+    }
+}
+
+impl Ledger {
+    /// Validate a block without stopping at the first error, to diagnose why
+    /// a block is rejected by `apply_block`.
+    ///
+    /// The fragments are applied in order on a scratch ledger, a failing
+    /// fragment is reported and skipped, so the following fragments are
+    /// validated as if it was not part of the block.
+    pub fn validate_block(
+        &self,
+        ledger_params: &LedgerParameters,
+        contents: &Contents,
+        metadata: &HeaderContentEvalContext,
+    ) -> BlockValidationReport {
+        let mut report = BlockValidationReport {
+            block_errors: self.check_block(ledger_params, contents, metadata),
+            fragment_errors: Vec::new(),
+        };
+
+        let mut ledger = match self.begin_block(metadata) {
+            Ok(ledger) => ledger,
+            Err(error) => {
+                report.block_errors.push(error);
+                let mut ledger = self.clone();
+                ledger.chain_length = self.chain_length.increase();
+                ledger
+            }
+        };
+
+        for (index, fragment) in contents.iter().enumerate() {
+            match ledger.apply_fragment(ledger_params, fragment, metadata.block_date) {
+                Ok(new_ledger) => ledger = new_ledger,
+                Err(error) => report.fragment_errors.push(FragmentValidationError {
+                    index,
+                    fragment_id: fragment.hash(),
+                    error,
+                }),
+            }
+        }
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chaintypes::ChainLength,
+        fee::LinearFee,
+        fragment::{ContentsBuilder, Fragment},
+        testing::{
+            builders::TestTxBuilder,
+            data::AddressDataValue,
+            ledger::{ConfigBuilder, LedgerBuilder, TestLedger},
+        },
+        value::Value,
+    };
+    use chain_addr::Discrimination;
+
+    fn ledger() -> (TestLedger, Vec<AddressDataValue>) {
+        let faucets = vec![
+            AddressDataValue::utxo(Discrimination::Test, Value(100)),
+            AddressDataValue::utxo(Discrimination::Test, Value(100)),
+        ];
+        let test_ledger =
+            LedgerBuilder::from_config(ConfigBuilder::new(0).with_fee(LinearFee::new(0, 0, 0)))
+                .faucets(&faucets)
+                .build()
+                .unwrap();
+        (test_ledger, faucets)
+    }
+
+    fn metadata(
+        test_ledger: &TestLedger,
+        contents: &Contents,
+        chain_length: ChainLength,
+    ) -> HeaderContentEvalContext {
+        HeaderContentEvalContext {
+            block_date: test_ledger.date().next(test_ledger.era()),
+            chain_length,
+            content_hash: contents.compute_hash(),
+            gp_content: None,
+        }
+    }
+
+    fn transfer(test_ledger: &mut TestLedger, source: &AddressDataValue) -> Fragment {
+        let destination = AddressDataValue::utxo(Discrimination::Test, source.value);
+        TestTxBuilder::new(&test_ledger.block0_hash)
+            .move_all_funds(test_ledger, source, &destination)
+            .get_fragment()
+    }
+
+    #[test]
+    fn valid_block_report() {
+        let (mut test_ledger, faucets) = ledger();
+        let mut contents = ContentsBuilder::new();
+        contents.push(transfer(&mut test_ledger, &faucets[0]));
+        let contents: Contents = contents.into();
+        let metadata = metadata(
+            &test_ledger,
+            &contents,
+            test_ledger.chain_length().increase(),
+        );
+
+        let report =
+            test_ledger
+                .ledger
+                .validate_block(&test_ledger.parameters, &contents, &metadata);
+        assert!(report.is_valid());
+        assert!(test_ledger
+            .ledger
+            .apply_block(&test_ledger.parameters, &contents, &metadata)
+            .is_ok());
+    }
+
+    #[test]
+    fn report_every_failure() {
+        let (mut test_ledger, faucets) = ledger();
+        let first = transfer(&mut test_ledger, &faucets[0]);
+        let second = transfer(&mut test_ledger, &faucets[1]);
+        let mut contents = ContentsBuilder::new();
+        contents.push_many(vec![first.clone(), first.clone(), second]);
+        let contents: Contents = contents.into();
+        // wrong chain length
+        let metadata = metadata(&test_ledger, &contents, test_ledger.chain_length());
+
+        let report =
+            test_ledger
+                .ledger
+                .validate_block(&test_ledger.parameters, &contents, &metadata);
+        assert_eq!(
+            report.block_errors,
+            vec![Error::WrongChainLength {
+                actual: test_ledger.chain_length(),
+                expected: test_ledger.chain_length().increase(),
+            }]
+        );
+        // the double spend is reported, and the following fragment still applies
+        assert_eq!(report.fragment_errors.len(), 1);
+        assert_eq!(report.fragment_errors[0].index, 1);
+        assert_eq!(report.fragment_errors[0].fragment_id, first.hash());
+        assert_eq!(
+            test_ledger
+                .ledger
+                .apply_block(&test_ledger.parameters, &contents, &metadata)
+                .err(),
+            Some(report.block_errors[0].clone())
+        );
+    }
+}
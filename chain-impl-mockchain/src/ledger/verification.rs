@@ -0,0 +1,370 @@
+//! Witness pre-verification
+//!
+//! The signature checks of a block are independent from each other once the
+//! signed data and the verification key of every witness are known, so they
+//! can be done ahead of applying the block, on a thread pool when the
+//! `parallel-verification` feature is enabled.
+//!
+//! The verification keys and spending counters are predicted from the ledger
+//! before the block and from the fragments preceding each witness in the
+//! block. The sequential apply only skips a signature check if it computes
+//! the same signed data, key and signature as a successful pre-verification,
+//! so a wrong prediction just falls back to the usual check.
+
+use super::ledger::Ledger;
+use crate::account::{self, SpendingCounter};
+use crate::fragment::{Contents, Fragment, FragmentId};
+use crate::key::{Hash, SpendingSignature};
+use crate::multisig;
+use crate::transaction::{
+    InputEnum, Payload, TransactionSignDataHash, TransactionSlice, Witness, WitnessAccountData,
+    WitnessMultisigData, WitnessUtxoData, WitnessUtxoVersion,
+};
+use chain_addr::{Address, Kind};
+use chain_core::property::Serialize;
+use chain_crypto::{Ed25519, PublicKey, Verification};
+use std::collections::{HashMap, HashSet};
+
+#[cfg(feature = "parallel-verification")]
+use rayon::prelude::*;
+
+/// Set of witnesses whose signature is known to be valid, as computed by
+/// `Ledger::verify_witnesses`.
+#[derive(Debug, Clone, Default)]
+pub struct VerifiedWitnesses(HashSet<Hash>);
+
+impl VerifiedWitnesses {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(super) fn contains(&self, data: &[u8], key: &[u8], signature: &[u8]) -> bool {
+        !self.0.is_empty() && self.0.contains(&check_id(data, key, signature))
+    }
+}
+
+fn check_id(data: &[u8], key: &[u8], signature: &[u8]) -> Hash {
+    let mut bytes = Vec::with_capacity(data.len() + key.len() + signature.len());
+    bytes.extend_from_slice(data);
+    bytes.extend_from_slice(key);
+    bytes.extend_from_slice(signature);
+    Hash::hash_bytes(&bytes)
+}
+
+enum WitnessCheck {
+    Utxo {
+        key: PublicKey<Ed25519>,
+        data: WitnessUtxoData,
+        signature: SpendingSignature<WitnessUtxoData>,
+    },
+    Account {
+        key: PublicKey<Ed25519>,
+        data: WitnessAccountData,
+        signature: account::Witness,
+    },
+    Multisig {
+        identifier: multisig::Identifier,
+        declaration: multisig::Declaration,
+        data: WitnessMultisigData,
+        witness: multisig::Witness,
+    },
+}
+
+impl WitnessCheck {
+    /// Check the signature, returning the identifier of the check if valid
+    fn verify(&self) -> Option<Hash> {
+        match self {
+            WitnessCheck::Utxo {
+                key,
+                data,
+                signature,
+            } => (signature.verify(key, data) == Verification::Success)
+                .then(|| check_id(data.as_ref(), key.as_ref(), signature.as_ref())),
+            WitnessCheck::Account {
+                key,
+                data,
+                signature,
+            } => (signature.verify(key, data) == Verification::Success)
+                .then(|| check_id(data.as_ref(), key.as_ref(), signature.as_ref())),
+            WitnessCheck::Multisig {
+                identifier,
+                declaration,
+                data,
+                witness,
+            } => witness.verify(declaration, data).then(|| {
+                check_id(
+                    data.as_ref(),
+                    identifier.as_ref(),
+                    &witness.serialize_as_vec().unwrap(),
+                )
+            }),
+        }
+    }
+}
+
+/// Collect the signature checks of a block, predicting the state they
+/// depend on by walking the fragments in order.
+struct WitnessChecks<'a> {
+    ledger: &'a Ledger,
+    block_outputs: HashMap<(FragmentId, u8), Address>,
+    counters: HashMap<account::Identifier, SpendingCounter>,
+    multisig_counters: HashMap<multisig::Identifier, SpendingCounter>,
+    checks: Vec<WitnessCheck>,
+}
+
+impl<'a> WitnessChecks<'a> {
+    fn new(ledger: &'a Ledger) -> Self {
+        WitnessChecks {
+            ledger,
+            block_outputs: HashMap::new(),
+            counters: HashMap::new(),
+            multisig_counters: HashMap::new(),
+            checks: Vec::new(),
+        }
+    }
+
+    fn add_fragment(&mut self, fragment: &Fragment) {
+        let fragment_id = fragment.hash();
+        match fragment {
+            Fragment::Transaction(tx) => self.add_transaction(fragment_id, &tx.as_slice()),
+            Fragment::OwnerStakeDelegation(tx) => self.add_transaction(fragment_id, &tx.as_slice()),
+            Fragment::StakeDelegation(tx) => self.add_transaction(fragment_id, &tx.as_slice()),
+            Fragment::PoolRegistration(tx) => self.add_transaction(fragment_id, &tx.as_slice()),
+            Fragment::PoolRetirement(tx) => self.add_transaction(fragment_id, &tx.as_slice()),
+            Fragment::PoolUpdate(tx) => self.add_transaction(fragment_id, &tx.as_slice()),
+            Fragment::MultisigDeclaration(tx) => self.add_transaction(fragment_id, &tx.as_slice()),
+            Fragment::Metadata(tx) => self.add_transaction(fragment_id, &tx.as_slice()),
+            Fragment::Initial(_)
+            | Fragment::OldUtxoDeclaration(_)
+            | Fragment::UpdateProposal(_)
+            | Fragment::UpdateVote(_)
+            | Fragment::TreasuryTransferProposal(_)
+            | Fragment::PoolUpdateVote(_) => {}
+        }
+    }
+
+    fn add_transaction<P: Payload>(&mut self, fragment_id: FragmentId, tx: &TransactionSlice<P>) {
+        let block0_hash = &self.ledger.static_params.block0_initial_hash;
+        let sign_data_hash = tx.transaction_sign_data_hash();
+        for (input, witness) in tx.inputs_and_witnesses().iter() {
+            let check = match (input.to_enum(), witness) {
+                (InputEnum::UtxoInput(_), Witness::OldUtxo(key, _, signature)) => {
+                    Some(WitnessCheck::Utxo {
+                        key,
+                        data: WitnessUtxoData::new(
+                            block0_hash,
+                            &sign_data_hash,
+                            WitnessUtxoVersion::Legacy,
+                        ),
+                        signature,
+                    })
+                }
+                (InputEnum::UtxoInput(utxo), Witness::Utxo(signature)) => self
+                    .utxo_key(&utxo.transaction_id, utxo.output_index)
+                    .map(|key| WitnessCheck::Utxo {
+                        key,
+                        data: WitnessUtxoData::new(
+                            block0_hash,
+                            &sign_data_hash,
+                            WitnessUtxoVersion::Normal,
+                        ),
+                        signature,
+                    }),
+                (InputEnum::AccountInput(account_id, _), Witness::Account(signature)) => {
+                    account_id.to_single_account().map(|account| {
+                        let counter = self.next_counter(&account);
+                        WitnessCheck::Account {
+                            key: account.into(),
+                            data: WitnessAccountData::new(block0_hash, &sign_data_hash, &counter),
+                            signature,
+                        }
+                    })
+                }
+                (InputEnum::AccountInput(account_id, _), Witness::Multisig(witness)) => {
+                    let identifier = account_id.to_multi_account();
+                    self.multisig_check(&sign_data_hash, identifier, witness)
+                }
+                _ => None,
+            };
+            self.checks.extend(check);
+        }
+
+        for (index, output) in tx.outputs().iter().enumerate() {
+            if let Kind::Single(_) | Kind::Group(..) = output.address.kind() {
+                self.block_outputs
+                    .insert((fragment_id, index as u8), output.address);
+            }
+        }
+    }
+
+    fn utxo_key(&self, fragment_id: &FragmentId, index: u8) -> Option<PublicKey<Ed25519>> {
+        let address = match self.block_outputs.get(&(*fragment_id, index)) {
+            Some(address) => address,
+            None => &self.ledger.utxos.get(fragment_id, &index)?.output.address,
+        };
+        address.public_key().cloned()
+    }
+
+    fn next_counter(&mut self, account: &account::Identifier) -> SpendingCounter {
+        let ledger = self.ledger;
+        let counter = self.counters.entry(account.clone()).or_insert_with(|| {
+            ledger
+                .accounts
+                .get_state(account)
+                .map(|state| state.counter)
+                .unwrap_or_else(|_| SpendingCounter::zero())
+        });
+        let current = *counter;
+        *counter = current.increment().unwrap_or(current);
+        current
+    }
+
+    fn multisig_check(
+        &mut self,
+        sign_data_hash: &TransactionSignDataHash,
+        identifier: multisig::Identifier,
+        witness: multisig::Witness,
+    ) -> Option<WitnessCheck> {
+        let ledger = self.ledger;
+        let declaration = ledger.multisig.get_declaration(&identifier).ok()?.clone();
+        let counter = self
+            .multisig_counters
+            .entry(identifier.clone())
+            .or_insert_with(|| {
+                ledger
+                    .multisig
+                    .get_state(&identifier)
+                    .map(|state| state.counter)
+                    .unwrap_or_else(|_| SpendingCounter::zero())
+            });
+        let current = *counter;
+        *counter = current.increment().unwrap_or(current);
+        Some(WitnessCheck::Multisig {
+            data: WitnessMultisigData::new(
+                &ledger.static_params.block0_initial_hash,
+                sign_data_hash,
+                &current,
+            ),
+            identifier,
+            declaration,
+            witness,
+        })
+    }
+}
+
+impl Ledger {
+    /// Check the signatures of all the witnesses of a block, leaving only the
+    /// state dependent checks for `apply_block`.
+    ///
+    /// With the `parallel-verification` feature the signatures are checked on
+    /// the rayon thread pool, and `apply_block` does this on its own.
+    /// An invalid signature is not an error here, it is left out of the result
+    /// and reported when applying the block.
+    pub fn verify_witnesses(&self, contents: &Contents) -> VerifiedWitnesses {
+        let mut checks = WitnessChecks::new(self);
+        for fragment in contents.iter() {
+            checks.add_fragment(fragment);
+        }
+
+        #[cfg(feature = "parallel-verification")]
+        let checks = checks.checks.par_iter();
+        #[cfg(not(feature = "parallel-verification"))]
+        let checks = checks.checks.iter();
+
+        VerifiedWitnesses(checks.filter_map(WitnessCheck::verify).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chaineval::HeaderContentEvalContext,
+        chaintypes::HeaderId,
+        fee::LinearFee,
+        fragment::ContentsBuilder,
+        ledger::Error,
+        testing::{
+            builders::TestTxBuilder,
+            data::AddressDataValue,
+            ledger::{ConfigBuilder, LedgerBuilder, TestLedger},
+        },
+        value::Value,
+    };
+    use chain_addr::Discrimination;
+
+    fn ledger(faucets: Vec<AddressDataValue>) -> TestLedger {
+        LedgerBuilder::from_config(ConfigBuilder::new(0).with_fee(LinearFee::new(0, 0, 0)))
+            .faucets(&faucets)
+            .build()
+            .unwrap()
+    }
+
+    fn transfer(
+        test_ledger: &mut TestLedger,
+        source: &AddressDataValue,
+        destination: &AddressDataValue,
+    ) -> Fragment {
+        TestTxBuilder::new(&test_ledger.block0_hash)
+            .move_funds(test_ledger, source, destination, &source.value)
+            .get_fragment()
+    }
+
+    fn apply_block(test_ledger: &TestLedger, contents: &Contents) -> Result<Ledger, Error> {
+        let metadata = HeaderContentEvalContext {
+            block_date: test_ledger.date().next(test_ledger.era()),
+            chain_length: test_ledger.chain_length().increase(),
+            content_hash: contents.compute_hash(),
+            gp_content: None,
+        };
+        test_ledger
+            .ledger
+            .apply_block(&test_ledger.parameters, contents, &metadata)
+    }
+
+    #[test]
+    fn verify_witnesses_of_dependent_fragments() {
+        let utxo = AddressDataValue::utxo(Discrimination::Test, Value(100));
+        let account = AddressDataValue::account(Discrimination::Test, Value(100));
+        let destination = AddressDataValue::utxo(Discrimination::Test, Value(50));
+        let mut test_ledger = ledger(vec![utxo.clone(), account.clone()]);
+
+        let mut half = account.clone();
+        half.value = Value(50);
+        let mut contents = ContentsBuilder::new();
+        contents.push(transfer(&mut test_ledger, &utxo, &account));
+        contents.push(transfer(&mut test_ledger, &half, &destination));
+        half.increment_spending_counter();
+        contents.push(transfer(&mut test_ledger, &half, &destination));
+        let contents: Contents = contents.into();
+
+        // the account spends twice, with the counter predicted for each input
+        assert_eq!(test_ledger.ledger.verify_witnesses(&contents).len(), 3);
+        assert!(apply_block(&test_ledger, &contents).is_ok());
+    }
+
+    #[test]
+    fn invalid_witness_is_left_to_apply() {
+        let source = AddressDataValue::utxo(Discrimination::Test, Value(100));
+        let destination = AddressDataValue::utxo(Discrimination::Test, Value(100));
+        let mut test_ledger = ledger(vec![source.clone()]);
+
+        // signed for another block0
+        let fragment = TestTxBuilder::new(&HeaderId::hash_bytes(&[0]))
+            .move_funds(&mut test_ledger, &source, &destination, &source.value)
+            .get_fragment();
+        let mut contents = ContentsBuilder::new();
+        contents.push(fragment);
+        let contents: Contents = contents.into();
+
+        assert!(test_ledger.ledger.verify_witnesses(&contents).is_empty());
+        assert!(matches!(
+            apply_block(&test_ledger, &contents),
+            Err(Error::UtxoInvalidSignature { .. })
+        ));
+    }
+}
@@ -21,6 +21,7 @@ pub mod header;
 pub mod key;
 pub mod leadership;
 pub mod ledger;
+pub mod mempool;
 pub mod multisig;
 pub mod multiverse;
 pub mod rewards;
@@ -0,0 +1,528 @@
+//! Mempool
+//!
+//! A pool of fragments waiting to be included in a block, validated against
+//! the ledger of the current tip of the chain.
+//!
+//! The pending fragments are applied in order on top of the tip ledger, so a
+//! fragment can depend on the ones received before it (spending their outputs,
+//! or spending again from the same account). Fragments spending the same
+//! utxo, or the same account state, as a pending fragment are rejected as
+//! conflicting.
+
+use crate::fragment::{Contents, ContentsBuilder, Fragment, FragmentId};
+use crate::ledger::{Error as LedgerError, Ledger, LedgerParameters};
+use crate::transaction::{
+    InputEnum, Payload, TransactionSlice, UnspecifiedAccountIdentifier, UtxoPointer,
+};
+use crate::value::Value;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use thiserror::Error;
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum Error {
+    #[error("Fragment {0} is already in the pool")]
+    AlreadyInPool(FragmentId),
+    #[error("Fragment of {size} bytes cannot fit in a block of {max} bytes")]
+    TooBig { size: usize, max: usize },
+    #[error("Fragment conflicts with the pending fragment {0}")]
+    Conflict(FragmentId),
+    #[error("Invalid fragment")]
+    Invalid(#[from] LedgerError),
+}
+
+/// A fragment accepted in the pool
+#[derive(Debug, Clone)]
+pub struct PendingFragment {
+    id: FragmentId,
+    fragment: Fragment,
+    fee: Value,
+    size: usize,
+    utxos: Vec<UtxoPointer>,
+    accounts: Vec<UnspecifiedAccountIdentifier>,
+}
+
+impl PendingFragment {
+    fn new(fragment: Fragment) -> Self {
+        let (fee, utxos, accounts) = match &fragment {
+            Fragment::Transaction(tx) => Self::spending(&tx.as_slice()),
+            Fragment::OwnerStakeDelegation(tx) => Self::spending(&tx.as_slice()),
+            Fragment::StakeDelegation(tx) => Self::spending(&tx.as_slice()),
+            Fragment::PoolRegistration(tx) => Self::spending(&tx.as_slice()),
+            Fragment::PoolRetirement(tx) => Self::spending(&tx.as_slice()),
+            Fragment::PoolUpdate(tx) => Self::spending(&tx.as_slice()),
+            Fragment::MultisigDeclaration(tx) => Self::spending(&tx.as_slice()),
+            Fragment::Metadata(tx) => Self::spending(&tx.as_slice()),
+            Fragment::Initial(_)
+            | Fragment::OldUtxoDeclaration(_)
+            | Fragment::UpdateProposal(_)
+            | Fragment::UpdateVote(_)
+            | Fragment::TreasuryTransferProposal(_)
+            | Fragment::PoolUpdateVote(_) => (Value::zero(), Vec::new(), Vec::new()),
+        };
+        PendingFragment {
+            id: fragment.hash(),
+            size: fragment.to_raw().size_bytes_plus_size(),
+            fee,
+            utxos,
+            accounts,
+            fragment,
+        }
+    }
+
+    fn spending<P: Payload>(
+        tx: &TransactionSlice<P>,
+    ) -> (Value, Vec<UtxoPointer>, Vec<UnspecifiedAccountIdentifier>) {
+        let mut utxos = Vec::new();
+        let mut accounts = Vec::new();
+        for input in tx.inputs().iter() {
+            match input.to_enum() {
+                InputEnum::UtxoInput(utxo) => utxos.push(utxo),
+                InputEnum::AccountInput(account, _) => accounts.push(account),
+            }
+        }
+        // unbalanced transactions are rejected by the ledger
+        let fee = match (tx.total_input(), tx.total_output()) {
+            (Ok(input), Ok(output)) => (input - output).unwrap_or_else(|_| Value::zero()),
+            _ => Value::zero(),
+        };
+        (fee, utxos, accounts)
+    }
+
+    pub fn id(&self) -> &FragmentId {
+        &self.id
+    }
+
+    pub fn fragment(&self) -> &Fragment {
+        &self.fragment
+    }
+
+    /// Fees paid by the fragment
+    pub fn fee(&self) -> Value {
+        self.fee
+    }
+
+    /// Size of the fragment in the contents of a block
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    fn cmp_fee_per_byte(&self, other: &Self) -> Ordering {
+        let this = self.fee.0 as u128 * other.size as u128;
+        let other = other.fee.0 as u128 * self.size as u128;
+        this.cmp(&other)
+    }
+}
+
+pub struct Mempool {
+    tip: Ledger,
+    parameters: LedgerParameters,
+    /// the tip ledger with all the pending fragments applied
+    pending: Ledger,
+    fragments: Vec<PendingFragment>,
+    ids: HashSet<FragmentId>,
+    utxo_spenders: HashMap<UtxoPointer, FragmentId>,
+    account_spenders: HashMap<UnspecifiedAccountIdentifier, FragmentId>,
+}
+
+impl Mempool {
+    pub fn new(tip: Ledger) -> Self {
+        Mempool {
+            parameters: tip.get_ledger_parameters(),
+            pending: tip.clone(),
+            tip,
+            fragments: Vec::new(),
+            ids: HashSet::new(),
+            utxo_spenders: HashMap::new(),
+            account_spenders: HashMap::new(),
+        }
+    }
+
+    pub fn tip(&self) -> &Ledger {
+        &self.tip
+    }
+
+    pub fn len(&self) -> usize {
+        self.fragments.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fragments.is_empty()
+    }
+
+    pub fn contains(&self, id: &FragmentId) -> bool {
+        self.ids.contains(id)
+    }
+
+    /// The pending fragments, in the order they were accepted
+    pub fn iter(&self) -> impl Iterator<Item = &PendingFragment> {
+        self.fragments.iter()
+    }
+
+    /// Validate the fragment on top of the tip and the pending fragments,
+    /// and add it to the pool.
+    pub fn insert(&mut self, fragment: Fragment) -> Result<FragmentId, Error> {
+        let pending = PendingFragment::new(fragment);
+        if self.contains(&pending.id) {
+            return Err(Error::AlreadyInPool(pending.id));
+        }
+        let max = self.tip.settings.block_content_max_size as usize;
+        if pending.size > max {
+            return Err(Error::TooBig {
+                size: pending.size,
+                max,
+            });
+        }
+        if let Some(spender) = pending
+            .utxos
+            .iter()
+            .find_map(|utxo| self.utxo_spenders.get(utxo))
+        {
+            return Err(Error::Conflict(*spender));
+        }
+
+        let date = self.tip.date();
+        let ledger = match self
+            .pending
+            .apply_fragment(&self.parameters, &pending.fragment, date)
+        {
+            Ok(ledger) => ledger,
+            Err(error) => {
+                // valid on the tip but not after the pending fragments: the account
+                // state it spends from has already been spent by a pending fragment
+                let spender = pending
+                    .accounts
+                    .iter()
+                    .find_map(|account| self.account_spenders.get(account));
+                return match spender {
+                    Some(spender)
+                        if self
+                            .tip
+                            .apply_fragment(&self.parameters, &pending.fragment, date)
+                            .is_ok() =>
+                    {
+                        Err(Error::Conflict(*spender))
+                    }
+                    _ => Err(Error::Invalid(error)),
+                };
+            }
+        };
+
+        self.pending = ledger;
+        for utxo in &pending.utxos {
+            self.utxo_spenders.insert(*utxo, pending.id);
+        }
+        for account in &pending.accounts {
+            self.account_spenders.insert(account.clone(), pending.id);
+        }
+        let id = pending.id;
+        self.ids.insert(id);
+        self.fragments.push(pending);
+        Ok(id)
+    }
+
+    /// Set the ledger of the new tip of the chain, and validate the pending
+    /// fragments again on top of it.
+    ///
+    /// Return the fragments evicted from the pool, because they were
+    /// included in a block, expired or depended on an evicted fragment.
+    pub fn set_tip(&mut self, tip: Ledger) -> Vec<(FragmentId, Error)> {
+        let fragments = std::mem::take(&mut self.fragments);
+        *self = Mempool::new(tip);
+        fragments
+            .into_iter()
+            .filter_map(|pending| {
+                let id = pending.id;
+                self.insert(pending.fragment).err().map(|error| (id, error))
+            })
+            .collect()
+    }
+
+    /// Select the fragments for the next block, best fee per byte first,
+    /// in an order they can be applied and fitting in the maximum size of
+    /// the block contents.
+    pub fn select_contents(&self) -> ContentsBuilder {
+        let mut candidates: Vec<&PendingFragment> = self.fragments.iter().collect();
+        candidates.sort_by(|a, b| b.cmp_fee_per_byte(a));
+
+        let date = self.tip.date();
+        let mut ledger = self.tip.clone();
+        let mut remaining = self.tip.settings.block_content_max_size as usize;
+        let mut contents = ContentsBuilder::new();
+        // a fragment depending on a pending fragment with a lower fee per byte is
+        // only applicable once that fragment is selected, so keep going while the
+        // selection progresses
+        loop {
+            let selected = candidates.len();
+            candidates.retain(|pending| {
+                if pending.size > remaining {
+                    return true;
+                }
+                match ledger.apply_fragment(&self.parameters, &pending.fragment, date) {
+                    Ok(new_ledger) => {
+                        ledger = new_ledger;
+                        remaining -= pending.size;
+                        contents.push(pending.fragment.clone());
+                        false
+                    }
+                    Err(_) => true,
+                }
+            });
+            if candidates.len() == selected {
+                break;
+            }
+        }
+        contents
+    }
+}
+
+impl From<&Mempool> for Contents {
+    fn from(mempool: &Mempool) -> Self {
+        mempool.select_contents().into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        fee::{FeeAlgorithm, LinearFee},
+        testing::{
+            builders::make_witness,
+            data::{AddressData, AddressDataValue},
+            ledger::{ConfigBuilder, LedgerBuilder, TestLedger},
+        },
+        transaction::{NoExtra, Output, TxBuilder, ValidityInterval},
+    };
+    use chain_addr::{Address, Discrimination};
+
+    fn ledger(faucets: Vec<AddressDataValue>) -> TestLedger {
+        LedgerBuilder::from_config(ConfigBuilder::new(0).with_fee(LinearFee::new(10, 1, 0)))
+            .faucets(&faucets)
+            .build()
+            .unwrap()
+    }
+
+    fn transfer(
+        test_ledger: &TestLedger,
+        source: &AddressDataValue,
+        input_value: Value,
+        nb_outputs: u8,
+        validity: ValidityInterval,
+    ) -> Fragment {
+        let fee = test_ledger.fee().calculate(None, 1, nb_outputs, 0, 0);
+        let receiver: Address = AddressData::utxo(Discrimination::Test).into();
+        let change = (input_value - fee).unwrap();
+        let outputs: Vec<_> = (0..nb_outputs)
+            .map(|i| Output {
+                address: receiver.clone(),
+                value: if i == 0 {
+                    (change - Value(nb_outputs as u64 - 1)).unwrap()
+                } else {
+                    Value(1)
+                },
+            })
+            .collect();
+        let utxo = test_ledger.find_utxo_for_address(&source.address_data());
+        let tx_builder = TxBuilder::new()
+            .set_payload(&NoExtra)
+            .set_validity(validity)
+            .set_ios(
+                &[source.make_input_with_value(utxo, &input_value)],
+                &outputs,
+            );
+        let witness = make_witness(
+            &test_ledger.block0_hash,
+            &source.address_data(),
+            &tx_builder.get_auth_data_for_witness().hash(),
+        );
+        Fragment::Transaction(tx_builder.set_witnesses(&[witness]).set_payload_auth(&()))
+    }
+
+    fn contents_ids(mempool: &Mempool) -> Vec<FragmentId> {
+        Contents::from(mempool).iter().map(Fragment::hash).collect()
+    }
+
+    #[test]
+    fn rejects_fragment_already_in_pool() {
+        let faucet = AddressDataValue::utxo(Discrimination::Test, Value(100));
+        let test_ledger = ledger(vec![faucet.clone()]);
+        let fragment = transfer(&test_ledger, &faucet, faucet.value, 1, Default::default());
+
+        let mut mempool = Mempool::new(test_ledger.ledger.clone());
+        let id = mempool.insert(fragment.clone()).unwrap();
+        assert!(mempool.contains(&id));
+        assert_eq!(mempool.insert(fragment), Err(Error::AlreadyInPool(id)));
+        assert_eq!(mempool.len(), 1);
+    }
+
+    #[test]
+    fn rejects_utxo_double_spend() {
+        let faucet = AddressDataValue::utxo(Discrimination::Test, Value(100));
+        let test_ledger = ledger(vec![faucet.clone()]);
+
+        let mut mempool = Mempool::new(test_ledger.ledger.clone());
+        let id = mempool
+            .insert(transfer(
+                &test_ledger,
+                &faucet,
+                faucet.value,
+                1,
+                Default::default(),
+            ))
+            .unwrap();
+        assert_eq!(
+            mempool.insert(transfer(
+                &test_ledger,
+                &faucet,
+                faucet.value,
+                2,
+                Default::default()
+            )),
+            Err(Error::Conflict(id))
+        );
+    }
+
+    #[test]
+    fn rejects_account_spending_counter_conflict() {
+        let mut faucet = AddressDataValue::account(Discrimination::Test, Value(1_000));
+        let test_ledger = ledger(vec![faucet.clone()]);
+
+        let mut mempool = Mempool::new(test_ledger.ledger.clone());
+        let id = mempool
+            .insert(transfer(
+                &test_ledger,
+                &faucet,
+                Value(100),
+                1,
+                Default::default(),
+            ))
+            .unwrap();
+        assert_eq!(
+            mempool.insert(transfer(
+                &test_ledger,
+                &faucet,
+                Value(100),
+                2,
+                Default::default()
+            )),
+            Err(Error::Conflict(id))
+        );
+
+        // the next spending counter applies on top of the pending fragment
+        faucet.increment_spending_counter();
+        mempool
+            .insert(transfer(
+                &test_ledger,
+                &faucet,
+                Value(100),
+                2,
+                Default::default(),
+            ))
+            .unwrap();
+        assert_eq!(mempool.len(), 2);
+    }
+
+    #[test]
+    fn rejects_invalid_fragment() {
+        let faucet = AddressDataValue::utxo(Discrimination::Test, Value(100));
+        let test_ledger = ledger(vec![faucet.clone()]);
+
+        let mut mempool = Mempool::new(test_ledger.ledger.clone());
+        assert!(matches!(
+            mempool.insert(transfer(
+                &test_ledger,
+                &faucet,
+                Value(50),
+                1,
+                Default::default()
+            )),
+            Err(Error::Invalid(_))
+        ));
+        assert!(mempool.is_empty());
+    }
+
+    #[test]
+    fn new_tip_evicts_included_and_expired_fragments() {
+        let mut account = AddressDataValue::account(Discrimination::Test, Value(1_000));
+        let utxo = AddressDataValue::utxo(Discrimination::Test, Value(100));
+        let test_ledger = ledger(vec![account.clone(), utxo.clone()]);
+        let date = test_ledger.date();
+
+        let included = transfer(&test_ledger, &account, Value(100), 1, Default::default());
+        account.increment_spending_counter();
+        let next = transfer(&test_ledger, &account, Value(100), 1, Default::default());
+        let expiring = transfer(
+            &test_ledger,
+            &utxo,
+            utxo.value,
+            1,
+            ValidityInterval::until(date),
+        );
+
+        let mut mempool = Mempool::new(test_ledger.ledger.clone());
+        let included_id = mempool.insert(included.clone()).unwrap();
+        let next_id = mempool.insert(next).unwrap();
+        let expiring_id = mempool.insert(expiring).unwrap();
+
+        let mut tip = test_ledger
+            .ledger
+            .apply_fragment(&test_ledger.parameters, &included, date)
+            .unwrap();
+        tip.date = date.next(test_ledger.era());
+        let evicted: Vec<_> = mempool.set_tip(tip).into_iter().map(|(id, _)| id).collect();
+
+        assert_eq!(evicted, vec![included_id, expiring_id]);
+        assert_eq!(
+            mempool.iter().map(|p| *p.id()).collect::<Vec<_>>(),
+            vec![next_id]
+        );
+    }
+
+    #[test]
+    fn selects_best_fee_per_byte_first() {
+        let first = AddressDataValue::utxo(Discrimination::Test, Value(100));
+        let second = AddressDataValue::utxo(Discrimination::Test, Value(100));
+        let test_ledger = ledger(vec![first.clone(), second.clone()]);
+
+        // an additional output costs less than its size
+        let cheap = transfer(&test_ledger, &first, first.value, 2, Default::default());
+        let best = transfer(&test_ledger, &second, second.value, 1, Default::default());
+
+        let mut mempool = Mempool::new(test_ledger.ledger.clone());
+        let cheap_id = mempool.insert(cheap).unwrap();
+        let best_id = mempool.insert(best).unwrap();
+        assert_eq!(contents_ids(&mempool), vec![best_id, cheap_id]);
+    }
+
+    #[test]
+    fn selects_dependencies_first() {
+        let mut account = AddressDataValue::account(Discrimination::Test, Value(1_000));
+        let test_ledger = ledger(vec![account.clone()]);
+
+        let parent = transfer(&test_ledger, &account, Value(100), 2, Default::default());
+        account.increment_spending_counter();
+        let child = transfer(&test_ledger, &account, Value(100), 1, Default::default());
+
+        let mut mempool = Mempool::new(test_ledger.ledger.clone());
+        let parent_id = mempool.insert(parent).unwrap();
+        let child_id = mempool.insert(child).unwrap();
+        assert_eq!(contents_ids(&mempool), vec![parent_id, child_id]);
+    }
+
+    #[test]
+    fn selection_fits_in_block_content_max_size() {
+        let first = AddressDataValue::utxo(Discrimination::Test, Value(100));
+        let second = AddressDataValue::utxo(Discrimination::Test, Value(100));
+        let test_ledger = ledger(vec![first.clone(), second.clone()]);
+        let cheap = transfer(&test_ledger, &first, first.value, 2, Default::default());
+        let best = transfer(&test_ledger, &second, second.value, 1, Default::default());
+
+        let mut tip = test_ledger.ledger.clone();
+        tip.settings.block_content_max_size = cheap.to_raw().size_bytes_plus_size() as u32;
+        let mut mempool = Mempool::new(tip);
+        mempool.insert(cheap).unwrap();
+        let best_id = mempool.insert(best).unwrap();
+        assert_eq!(contents_ids(&mempool), vec![best_id]);
+    }
+}
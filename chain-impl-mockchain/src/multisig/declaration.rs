@@ -1,7 +1,12 @@
 use crate::{account, key};
+use chain_core::{
+    mempack::{ReadBuf, ReadError, Readable},
+    property,
+};
 use chain_crypto::{PublicKey, Signature};
+use typed_bytes::{ByteArray, ByteBuilder};
 
-use super::index::{Index, TreeIndex, LEVEL_MAXLIMIT};
+use super::index::{Index, TreeIndex, DEPTH_MAXLIMIT, LEVEL_MAXLIMIT};
 pub use crate::transaction::WitnessMultisigData;
 use thiserror::Error;
 
@@ -101,7 +106,15 @@ impl Declaration {
         owners_to_identifier(self.threshold, &self.owners)
     }
 
+    /// Check the declaration and its sub declarations.
+    ///
+    /// Only one level of sub declarations is supported, as witnesses can
+    /// only address a depth of 2 (see `TreeIndex`).
     pub fn is_valid(&self) -> Result<(), DeclarationError> {
+        self.is_valid_at_depth(1)
+    }
+
+    fn is_valid_at_depth(&self, depth: usize) -> Result<(), DeclarationError> {
         if self.threshold < 1 || self.threshold as usize > self.owners.len() {
             return Err(DeclarationError::ThresholdInvalid);
         }
@@ -111,9 +124,55 @@ impl Declaration {
         if self.owners.len() > LEVEL_MAXLIMIT {
             return Err(DeclarationError::HasTooManyOwners);
         }
+        for owner in self.owners.iter() {
+            if let DeclElement::Sub(sub) = owner {
+                if depth >= DEPTH_MAXLIMIT {
+                    return Err(DeclarationError::SubNotImplemented);
+                }
+                sub.is_valid_at_depth(depth + 1)?;
+            }
+        }
         Ok(())
     }
 
+    // Format is:
+    // THRESHOLD (byte) #OWNERS (byte) [ 0 (byte) SUB_DECLARATION | 1 (byte) OWNER_HASH (32 bytes) ]
+    pub fn serialize_in(&self, bb: ByteBuilder<Self>) -> ByteBuilder<Self> {
+        bb.u8(self.threshold)
+            .iter8(self.owners.iter(), |bb, owner| match owner {
+                DeclElement::Sub(sub) => bb.u8(0).sub(|sb| sub.serialize_in(sb)),
+                DeclElement::Owner(hash) => bb.u8(1).bytes(hash.as_ref()),
+            })
+    }
+
+    pub fn serialize(&self) -> ByteArray<Self> {
+        self.serialize_in(ByteBuilder::new()).finalize()
+    }
+
+    fn read_at_depth<'a>(buf: &mut ReadBuf<'a>, depth: usize) -> Result<Self, ReadError> {
+        let threshold = buf.get_u8()?;
+        let nb_owners = buf.get_u8()? as usize;
+        if nb_owners > LEVEL_MAXLIMIT {
+            return Err(ReadError::SizeTooBig(nb_owners, LEVEL_MAXLIMIT));
+        }
+        let mut owners = Vec::with_capacity(nb_owners);
+        for _ in 0..nb_owners {
+            match buf.get_u8()? {
+                0 if depth < DEPTH_MAXLIMIT => {
+                    owners.push(DeclElement::Sub(Self::read_at_depth(buf, depth + 1)?))
+                }
+                0 => {
+                    return Err(ReadError::StructureInvalid(
+                        "multisig declaration nested too deeply".to_string(),
+                    ))
+                }
+                1 => owners.push(DeclElement::Owner(<[u8; 32]>::read(buf)?.into())),
+                t => return Err(ReadError::UnknownTag(t as u32)),
+            }
+        }
+        Ok(Declaration { threshold, owners })
+    }
+
     pub fn get_path(&self, ti: TreeIndex) -> Option<(&Declaration, Index)> {
         match ti {
             TreeIndex::D1(idx) => Some((self, idx)),
@@ -128,5 +187,18 @@ impl Declaration {
     }
 }
 
+impl property::Serialize for Declaration {
+    type Error = std::io::Error;
+    fn serialize<W: std::io::Write>(&self, mut writer: W) -> Result<(), Self::Error> {
+        writer.write_all(self.serialize().as_slice())
+    }
+}
+
+impl Readable for Declaration {
+    fn read<'a>(buf: &mut ReadBuf<'a>) -> Result<Self, ReadError> {
+        Self::read_at_depth(buf, 1)
+    }
+}
+
 pub type Pk = PublicKey<account::AccountAlg>;
 pub type Sig = Signature<WitnessMultisigData, account::AccountAlg>;
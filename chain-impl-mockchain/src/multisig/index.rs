@@ -2,6 +2,9 @@ use std::cmp::Ordering;
 
 pub const LEVEL_MAXLIMIT: usize = 8;
 
+/// The maximum depth of a declaration, as addressable by a `TreeIndex`
+pub const DEPTH_MAXLIMIT: usize = 2;
+
 /// The Index is really just 3 bits and has a hardbound linked to the LEVEL_MAXLIMIT
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Index(u8);
@@ -1,17 +1,18 @@
-use imhamt::{Hamt, HamtIter, InsertError, RemoveError};
+use imhamt::{Diff, Hamt, HamtIter, InsertError, RemoveError};
 use std::collections::hash_map::DefaultHasher;
 use thiserror::Error;
 
 use super::declaration::{Declaration, DeclarationError, Identifier};
-use crate::accounting::account::{self, DelegationType, Iter, SpendingCounter};
+use crate::accounting::account::{self, AccountState, DelegationType, Iter, SpendingCounter};
+use crate::date::Epoch;
 use crate::value::{Value, ValueError};
 
 #[derive(Clone, PartialEq, Eq)]
 pub struct Ledger {
     // TODO : investigate about merging the declarations and the accounts in
     // one with an extension on the account::Ledger
-    accounts: account::Ledger<Identifier, ()>,
-    declarations: Hamt<DefaultHasher, Identifier, Declaration>,
+    pub(crate) accounts: account::Ledger<Identifier, ()>,
+    pub(crate) declarations: Hamt<DefaultHasher, Identifier, Declaration>,
 }
 
 #[derive(Debug, Error, Clone, PartialEq, Eq)]
@@ -63,6 +64,24 @@ impl Ledger {
         }
     }
 
+    /// Compute the multisig accounts added, removed and changed between this
+    /// ledger and a newer one.
+    pub fn diff_accounts<'a>(
+        &'a self,
+        new: &'a Self,
+    ) -> Vec<Diff<'a, Identifier, AccountState<()>>> {
+        self.accounts.diff(&new.accounts)
+    }
+
+    /// Compute the multisig declarations added, removed and changed between
+    /// this ledger and a newer one.
+    pub fn diff_declarations<'a>(
+        &'a self,
+        new: &'a Self,
+    ) -> Vec<Diff<'a, Identifier, Declaration>> {
+        self.declarations.diff(&new.declarations)
+    }
+
     /// Add a new multisig declaration into the ledger.
     ///
     /// If the identifier is already present, error out.
@@ -99,6 +118,49 @@ impl Ledger {
         })
     }
 
+    /// Add rewards to an existing multisig account.
+    ///
+    /// Contrary to the single account ledger, the account is never created
+    /// here since it cannot exist without its declaration. If the account
+    /// doesn't exist, error out.
+    pub fn add_rewards_to_account(
+        &self,
+        identifier: &Identifier,
+        epoch: Epoch,
+        value: Value,
+    ) -> Result<Self, LedgerError> {
+        if !self.exists(identifier) {
+            return Err(LedgerError::DoesntExist);
+        }
+        let new_accounts = self
+            .accounts
+            .add_rewards_to_account(identifier, epoch, value, ())
+            .map_err(account::LedgerError::from)?;
+        Ok(Self {
+            accounts: new_accounts,
+            declarations: self.declarations.clone(),
+        })
+    }
+
+    /// check if a multisig account already exist
+    pub fn exists(&self, identifier: &Identifier) -> bool {
+        self.declarations.contains_key(identifier)
+    }
+
+    /// Get the account state of a multisig account
+    ///
+    /// If the identifier does not match any account, error out
+    pub fn get_state(&self, identifier: &Identifier) -> Result<&AccountState<()>, LedgerError> {
+        self.accounts.get_state(identifier).map_err(|e| e.into())
+    }
+
+    /// Get the declaration of a multisig account
+    pub fn get_declaration(&self, identifier: &Identifier) -> Result<&Declaration, LedgerError> {
+        self.declarations
+            .lookup(identifier)
+            .ok_or(LedgerError::DoesntExist)
+    }
+
     pub fn iter_accounts<'a>(&'a self) -> Iter<'a, Identifier, ()> {
         self.accounts.iter()
     }
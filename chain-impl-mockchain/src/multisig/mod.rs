@@ -17,8 +17,10 @@ mod test {
     use crate::accounting::account::SpendingCounter;
     use crate::transaction::{TransactionSignData, TransactionSignDataHash};
     use crate::{account, key};
+    use chain_core::mempack::{ReadBuf, Readable};
     use chain_crypto::{PublicKey, SecretKey};
-    use quickcheck::{Arbitrary, Gen};
+    use quickcheck::{Arbitrary, Gen, TestResult};
+    use quickcheck_macros::quickcheck;
     use rand_core::{CryptoRng, RngCore};
 
     fn make_keypair<R: RngCore + CryptoRng>(
@@ -134,6 +136,68 @@ mod test {
         }
     }
 
+    fn arbitrary_owners<G: Gen>(g: &mut G, depth: usize) -> Vec<DeclElement> {
+        let nb_owners = 2 + usize::arbitrary(g) % (index::LEVEL_MAXLIMIT - 1);
+        (0..nb_owners)
+            .map(|_| {
+                if depth < index::DEPTH_MAXLIMIT && u8::arbitrary(g) % 4 == 0 {
+                    let owners = arbitrary_owners(g, depth + 1);
+                    let threshold = 1 + u8::arbitrary(g) % owners.len() as u8;
+                    DeclElement::Sub(Declaration { threshold, owners })
+                } else {
+                    let mut b = [0u8; 32];
+                    for v in b.iter_mut() {
+                        *v = Arbitrary::arbitrary(g)
+                    }
+                    DeclElement::Owner(b.into())
+                }
+            })
+            .collect()
+    }
+
+    impl Arbitrary for Declaration {
+        fn arbitrary<G: Gen>(g: &mut G) -> Self {
+            let owners = arbitrary_owners(g, 1);
+            let threshold = 1 + u8::arbitrary(g) % owners.len() as u8;
+            Declaration { threshold, owners }
+        }
+    }
+
+    #[quickcheck]
+    fn declaration_serialization_bijection(declaration: Declaration) -> TestResult {
+        let bytes = declaration.serialize();
+        let mut buf = ReadBuf::from(bytes.as_ref());
+        let decoded = Declaration::read(&mut buf).unwrap();
+        TestResult::from_bool(buf.expect_end().is_ok() && decoded == declaration)
+    }
+
+    #[quickcheck]
+    fn arbitrary_declaration_is_valid(declaration: Declaration) -> bool {
+        declaration.is_valid().is_ok()
+    }
+
+    #[test]
+    fn declaration_with_nested_sub_is_invalid() {
+        let owner = |v: u8| DeclElement::Owner(key::Hash::hash_bytes(&[v]));
+        let inner = Declaration {
+            threshold: 1,
+            owners: vec![owner(1), owner(2)],
+        };
+        let middle = Declaration {
+            threshold: 1,
+            owners: vec![DeclElement::Sub(inner), owner(3)],
+        };
+        assert_eq!(middle.is_valid(), Ok(()));
+
+        let outer = Declaration {
+            threshold: 1,
+            owners: vec![DeclElement::Sub(middle), owner(4)],
+        };
+        assert_eq!(outer.is_valid(), Err(DeclarationError::SubNotImplemented));
+        let bytes = outer.serialize();
+        assert!(Declaration::read(&mut ReadBuf::from(bytes.as_ref())).is_err());
+    }
+
     impl Arbitrary for Identifier {
         fn arbitrary<G: Gen>(g: &mut G) -> Self {
             let mut b = [0u8; 32];
@@ -133,6 +133,14 @@ pub struct Parameters {
     /// Pool Capping
     /// This doesn't really make sense
     pub pool_participation_capping: Option<(NonZeroU32, NonZeroU32)>,
+    /// Pool saturation: the target number of stake pools in the system.
+    /// A pool controlling more than 1/target of the total stake only gets
+    /// the rewards it would have got with exactly 1/target of it.
+    pub pool_saturation_target: Option<NonZeroU32>,
+    /// Pool performance: the number of blocks expected in an epoch.
+    /// When set, the reward of a pool is based on its stake and reduced
+    /// by the ratio of blocks produced over blocks expected from its stake.
+    pub expected_epoch_blocks: Option<NonZeroU64>,
 }
 
 impl Parameters {
@@ -145,6 +153,8 @@ impl Parameters {
             epoch_start: 0,
             reward_drawing_limit_max: Limit::None,
             pool_participation_capping: None,
+            pool_saturation_target: None,
+            expected_epoch_blocks: None,
         }
     }
 }
@@ -216,6 +226,52 @@ pub fn rewards_contribution_calculation(
     }
 }
 
+/// Cap the reward of a pool according to its share of the total stake
+///
+/// A pool is saturated when it controls more than 1/target of the total
+/// stake, in which case its reward is scaled down proportionally to what
+/// a pool at exactly the saturation point would get.
+pub fn saturation_cap(
+    reward: Value,
+    pool_stake: Stake,
+    total_stake: Stake,
+    target: NonZeroU32,
+) -> Value {
+    let pool_stake = pool_stake.0 as u128;
+    let total_stake = total_stake.0 as u128;
+    let target = target.get() as u128;
+
+    if pool_stake * target <= total_stake {
+        return reward;
+    }
+    Value(((reward.0 as u128 * total_stake) / (pool_stake * target)) as u64)
+}
+
+/// Calculate the reward of a pool weighted by its performance
+///
+/// The pool is entitled to its share of stake of the total reward, reduced by
+/// the ratio of its produced blocks over the blocks expected from its stake.
+/// Producing more blocks than expected doesn't give more than the stake share.
+pub fn performance_weighted(
+    total_reward: Value,
+    pool_blocks: u32,
+    pool_stake: Stake,
+    total_stake: Stake,
+    expected_epoch_blocks: NonZeroU64,
+) -> Value {
+    if total_stake == Stake::zero() {
+        return Value::zero();
+    }
+    let total_reward = total_reward.0 as u128;
+
+    // with expected = expected_epoch_blocks * pool_stake / total_stake, the reward is
+    // total_reward * pool_stake / total_stake * min(produced / expected, 1), which
+    // simplifies to the minimum of the reward by blocks and the reward by stake.
+    let by_blocks = total_reward * pool_blocks as u128 / expected_epoch_blocks.get() as u128;
+    let by_stake = total_reward * pool_stake.0 as u128 / total_stake.0 as u128;
+    Value(std::cmp::min(by_blocks, by_stake) as u64)
+}
+
 /// Tax some value into the tax value and what is remaining
 pub fn tax_cut(v: Value, tax_type: &TaxType) -> Result<TaxDistribution, ValueError> {
     let mut left = v;
@@ -291,6 +347,62 @@ mod tests {
         }
     }
 
+    #[quickcheck]
+    fn saturation_cap_never_increases_reward(
+        reward: Value,
+        pool_stake: u64,
+        total_stake: u64,
+        target: u32,
+    ) -> TestResult {
+        let target = match NonZeroU32::new(target) {
+            None => return TestResult::discard(),
+            Some(target) => target,
+        };
+        if pool_stake == 0 || pool_stake > total_stake {
+            return TestResult::discard();
+        }
+        let capped = saturation_cap(reward, Stake(pool_stake), Stake(total_stake), target);
+        TestResult::from_bool(capped <= reward)
+    }
+
+    #[test]
+    fn saturation_cap_only_applies_above_saturation() {
+        let target = NonZeroU32::new(4).unwrap();
+        let total = Stake(1000);
+        assert_eq!(
+            saturation_cap(Value(100), Stake(250), total, target),
+            Value(100)
+        );
+        assert_eq!(
+            saturation_cap(Value(100), Stake(500), total, target),
+            Value(50)
+        );
+        assert_eq!(
+            saturation_cap(Value(100), Stake(1000), total, target),
+            Value(25)
+        );
+    }
+
+    #[test]
+    fn performance_weighted_reduces_underperforming_pools() {
+        let expected_epoch_blocks = NonZeroU64::new(10).unwrap();
+        let total = Stake(1000);
+        // expected to produce 5 blocks, only produced 2
+        assert_eq!(
+            performance_weighted(Value(100), 2, Stake(500), total, expected_epoch_blocks),
+            Value(20)
+        );
+        // producing more than expected is capped at the stake share
+        assert_eq!(
+            performance_weighted(Value(100), 8, Stake(500), total, expected_epoch_blocks),
+            Value(50)
+        );
+        assert_eq!(
+            performance_weighted(Value(100), 2, Stake(0), total, expected_epoch_blocks),
+            Value(0)
+        );
+    }
+
     #[test]
     fn ratio_cmp_works() {
         use std::cmp::Ordering;
@@ -339,6 +451,8 @@ mod tests {
             epoch_start: 0,
             reward_drawing_limit_max: Limit::None,
             pool_participation_capping: None,
+            pool_saturation_target: None,
+            expected_epoch_blocks: None,
         };
         let epoch = 1;
         let system_info = SystemInformation {
@@ -396,6 +510,8 @@ mod tests {
                 epoch_start: Arbitrary::arbitrary(g),
                 reward_drawing_limit_max: Limit::arbitrary(g),
                 pool_participation_capping: None,
+                pool_saturation_target: None,
+                expected_epoch_blocks: None,
             }
         }
     }
@@ -7,15 +7,16 @@ use crate::update;
 use crate::{
     chaineval::PraosNonce,
     chaintypes::ConsensusType,
-    config::{ConfigParam, RewardParams},
-    fee::LinearFee,
+    config::{ConfigParam, RewardParams, Tag},
+    fee::{LinearFee, PerCertificateFee, PerExtendedCertificateFee},
     key::BftLeaderId,
-    rewards,
+    leadership, rewards,
+    value::Value,
 };
 use std::convert::TryFrom;
 use std::error::Error;
 use std::fmt;
-use std::num::NonZeroU32;
+use std::num::{NonZeroU32, NonZeroU64};
 use std::sync::Arc;
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -25,6 +26,10 @@ pub struct Settings {
     pub slots_per_epoch: u32,
     pub slot_duration: u8,
     pub epoch_stability_depth: u32,
+    /// The share of the slots of every epoch reserved to the BFT leaders when
+    /// running Genesis Praos, to move gradually from BFT to Genesis Praos.
+    /// Zero leaves all the slots to the stake pools.
+    pub bft_slots_ratio: Milli,
     pub active_slots_coeff: ActiveSlotsCoeff,
     pub block_content_max_size: BlockContentSize,
     pub bft_leaders: Arc<Vec<BftLeaderId>>,
@@ -32,13 +37,34 @@ pub struct Settings {
     /// The number of epochs that a proposal remains valid. To be
     /// precise, if a proposal is made at date (epoch_p, slot), then
     /// it expires at the start of epoch 'epoch_p +
-    /// proposal_expiration + 1'. FIXME: make updateable.
+    /// proposal_expiration + 1'.
     pub proposal_expiration: u32,
     pub reward_params: Option<RewardParams>,
     pub treasury_params: Option<rewards::TaxType>,
     pub fees_goes_to: FeesGoesTo,
     pub rewards_limit: rewards::Limit,
     pub pool_participation_capping: Option<(NonZeroU32, NonZeroU32)>,
+    /// The number of epochs for which the rewards received by each account
+    /// and stake pool are kept in the ledger. Zero disables the history.
+    pub rewards_history_retention: u32,
+    /// The target number of stake pools; a pool's reward is capped to what
+    /// it would get with a 1/target share of the total stake.
+    pub pool_saturation_target: Option<NonZeroU32>,
+    /// Weight the pool rewards by the ratio of blocks produced over the
+    /// blocks expected from the pool's stake. Unearned rewards go to treasury.
+    pub pool_performance_rewards: bool,
+    /// The minimum share of the total stake that must take part in a stake
+    /// pools vote for it to be considered. Stake voting is disabled if unset.
+    pub stake_voting_quorum: Option<rewards::Ratio>,
+    /// The share of the voting stake above which a proposal is accepted by
+    /// the stake pools; a simple majority if unset.
+    pub stake_voting_threshold: Option<rewards::Ratio>,
+    /// The maximum size in bytes of the metadata attached to a transaction.
+    /// Zero disables the metadata transactions.
+    pub metadata_max_size: u32,
+    /// The value locked when registering a stake pool, refunded when the
+    /// pool retires.
+    pub pool_deposit: Value,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -114,6 +140,7 @@ impl Settings {
             slots_per_epoch: 1,
             slot_duration: 10,         // 10 sec
             epoch_stability_depth: 10, // num of block
+            bft_slots_ratio: Milli::ZERO,
             active_slots_coeff: ActiveSlotsCoeff::try_from(Milli::HALF).unwrap(),
             block_content_max_size: 102_400,
             bft_leaders: Arc::new(Vec::new()),
@@ -124,6 +151,13 @@ impl Settings {
             fees_goes_to: FeesGoesTo::Rewards,
             rewards_limit: rewards::Limit::None,
             pool_participation_capping: None,
+            rewards_history_retention: 0,
+            pool_saturation_target: None,
+            pool_performance_rewards: false,
+            stake_voting_quorum: None,
+            stake_voting_threshold: None,
+            metadata_max_size: 0,
+            pool_deposit: Value::zero(),
         }
     }
 
@@ -134,16 +168,17 @@ impl Settings {
     pub fn apply(&self, changes: &ConfigParams) -> Result<Self, update::Error> {
         let mut new_state = self.clone();
         let mut per_certificate_fees = None;
+        let mut per_extended_certificate_fees = None;
+        let mut per_metadata_byte = None;
+        let mut per_fragment_byte = None;
 
         for param in changes.iter() {
+            let tag = Tag::from(param);
+            if tag.is_immutable() {
+                return Err(update::Error::ReadOnlySetting(tag));
+            }
+
             match param {
-                ConfigParam::Block0Date(_)
-                | ConfigParam::Discrimination(_)
-                | ConfigParam::TreasuryAdd(_)
-                | ConfigParam::RewardPot(_)
-                | ConfigParam::KESUpdateSpeed(_) => {
-                    return Err(update::Error::ReadOnlySetting);
-                }
                 ConfigParam::ConsensusVersion(d) => {
                     new_state.consensus_version = *d;
                 }
@@ -156,6 +191,12 @@ impl Settings {
                 ConfigParam::EpochStabilityDepth(d) => {
                     new_state.epoch_stability_depth = *d;
                 }
+                ConfigParam::BftSlotsRatio(d) => {
+                    if *d > Milli::ONE {
+                        return Err(update::Error::BadBftSlotsRatio(*d));
+                    }
+                    new_state.bft_slots_ratio = *d;
+                }
                 ConfigParam::ConsensusGenesisPraosActiveSlotsCoeff(d) => {
                     new_state.active_slots_coeff = ActiveSlotsCoeff::try_from(*d)?;
                 }
@@ -179,7 +220,17 @@ impl Settings {
                     );
                 }
                 ConfigParam::LinearFee(d) => {
-                    new_state.linear_fees = Arc::new(*d);
+                    // the per certificate, metadata and fragment size fees are separate
+                    // parameters, keep them
+                    new_state.linear_fees = Arc::new(LinearFee {
+                        per_certificate_fees: new_state.linear_fees.per_certificate_fees,
+                        per_extended_certificate_fees: new_state
+                            .linear_fees
+                            .per_extended_certificate_fees,
+                        per_metadata_byte: new_state.linear_fees.per_metadata_byte,
+                        per_fragment_byte: new_state.linear_fees.per_fragment_byte,
+                        ..*d
+                    });
                 }
                 ConfigParam::ProposalExpiration(d) => {
                     new_state.proposal_expiration = *d;
@@ -207,18 +258,116 @@ impl Settings {
                 ConfigParam::PoolRewardParticipationCapping(r) => {
                     new_state.pool_participation_capping = Some(r.clone())
                 }
+                ConfigParam::RewardsHistoryRetention(d) => {
+                    new_state.rewards_history_retention = *d;
+                }
+                ConfigParam::PoolRewardSaturation(target) => {
+                    new_state.pool_saturation_target = *target;
+                }
+                ConfigParam::PoolRewardPerformance(enabled) => {
+                    new_state.pool_performance_rewards = *enabled;
+                }
+                ConfigParam::StakeVotingQuorum(ratio) => {
+                    new_state.stake_voting_quorum = Some(*ratio);
+                }
+                ConfigParam::StakeVotingThreshold(ratio) => {
+                    new_state.stake_voting_threshold = Some(*ratio);
+                }
+                ConfigParam::MetadataMaxSize(d) => {
+                    new_state.metadata_max_size = *d;
+                }
+                ConfigParam::PerMetadataByteFee(d) => {
+                    per_metadata_byte = Some(d);
+                }
+                ConfigParam::PerFragmentByteFee(d) => {
+                    per_fragment_byte = Some(d);
+                }
+                ConfigParam::PoolDeposit(d) => {
+                    new_state.pool_deposit = *d;
+                }
+                ConfigParam::PerExtendedCertificateFees(pcf) => {
+                    per_extended_certificate_fees = Some(pcf);
+                }
+                // the immutable parameters, rejected above
+                ConfigParam::Discrimination(_)
+                | ConfigParam::Block0Date(_)
+                | ConfigParam::KESUpdateSpeed(_)
+                | ConfigParam::TreasuryAdd(_)
+                | ConfigParam::RewardPot(_) => return Err(update::Error::ReadOnlySetting(tag)),
             }
         }
 
         if let Some(pcf) = per_certificate_fees {
-            Arc::get_mut(&mut new_state.linear_fees)
-                .unwrap()
-                .per_certificate_fees(*pcf);
+            Arc::make_mut(&mut new_state.linear_fees).per_certificate_fees(*pcf);
+        }
+        if let Some(pcf) = per_extended_certificate_fees {
+            Arc::make_mut(&mut new_state.linear_fees).per_extended_certificate_fees(*pcf);
+        }
+        if let Some(fee) = per_metadata_byte {
+            Arc::make_mut(&mut new_state.linear_fees).per_metadata_byte(*fee);
+        }
+        if let Some(fee) = per_fragment_byte {
+            Arc::make_mut(&mut new_state.linear_fees).per_fragment_byte(*fee);
         }
 
         Ok(new_state)
     }
 
+    /// Compute the parameters to apply on top of these settings to obtain
+    /// `new`: the BFT leaders changes and every other parameter of `new`
+    /// that differs from the current one.
+    ///
+    /// Optional parameters that are unset in `new` have no representation
+    /// and are not part of the result.
+    pub fn diff(&self, new: &Settings) -> ConfigParams {
+        let mut params = ConfigParams::new();
+
+        if new.bft_leaders.starts_with(&self.bft_leaders) {
+            for leader in new.bft_leaders[self.bft_leaders.len()..].iter() {
+                params.push(ConfigParam::AddBftLeader(leader.clone()));
+            }
+        } else {
+            // removing a leader removes all its occurrences, so the order of
+            // the leaders can only be kept by declaring them all again
+            let mut removed = Vec::new();
+            for leader in self.bft_leaders.iter() {
+                if !removed.contains(leader) {
+                    params.push(ConfigParam::RemoveBftLeader(leader.clone()));
+                    removed.push(leader.clone());
+                }
+            }
+            for leader in new.bft_leaders.iter() {
+                params.push(ConfigParam::AddBftLeader(leader.clone()));
+            }
+        }
+
+        let old_params = self.to_config_params();
+        for param in new.to_config_params().iter() {
+            match param {
+                ConfigParam::AddBftLeader(_) => (),
+                param if old_params.iter().any(|old| old == param) => (),
+                param => params.push(param.clone()),
+            }
+        }
+        // omitted from the parameters when set to default
+        if new.linear_fees.per_certificate_fees != self.linear_fees.per_certificate_fees
+            && new.linear_fees.per_certificate_fees == PerCertificateFee::default()
+        {
+            params.push(ConfigParam::PerCertificateFees(
+                new.linear_fees.per_certificate_fees,
+            ));
+        }
+        if new.linear_fees.per_extended_certificate_fees
+            != self.linear_fees.per_extended_certificate_fees
+            && new.linear_fees.per_extended_certificate_fees == PerExtendedCertificateFee::default()
+        {
+            params.push(ConfigParam::PerExtendedCertificateFees(
+                new.linear_fees.per_extended_certificate_fees,
+            ));
+        }
+        params
+    }
+
     pub fn to_config_params(&self) -> ConfigParams {
         let mut params = ConfigParams::new();
 
@@ -226,6 +375,7 @@ impl Settings {
         params.push(ConfigParam::SlotsPerEpoch(self.slots_per_epoch));
         params.push(ConfigParam::SlotDuration(self.slot_duration));
         params.push(ConfigParam::EpochStabilityDepth(self.epoch_stability_depth));
+        params.push(ConfigParam::BftSlotsRatio(self.bft_slots_ratio));
         params.push(ConfigParam::ConsensusGenesisPraosActiveSlotsCoeff(
             self.active_slots_coeff.into(),
         ));
@@ -237,6 +387,39 @@ impl Settings {
         }
         params.push(ConfigParam::LinearFee(*self.linear_fees));
         params.push(ConfigParam::ProposalExpiration(self.proposal_expiration));
+        params.push(ConfigParam::RewardsHistoryRetention(
+            self.rewards_history_retention,
+        ));
+        params.push(ConfigParam::PoolRewardPerformance(
+            self.pool_performance_rewards,
+        ));
+        params.push(ConfigParam::MetadataMaxSize(self.metadata_max_size));
+        params.push(ConfigParam::PerMetadataByteFee(
+            self.linear_fees.per_metadata_byte,
+        ));
+        params.push(ConfigParam::PerFragmentByteFee(
+            self.linear_fees.per_fragment_byte,
+        ));
+        params.push(ConfigParam::PoolDeposit(self.pool_deposit));
+        params.push(ConfigParam::FeesInTreasury(
+            self.fees_goes_to == FeesGoesTo::Treasury,
+        ));
+        params.push(match &self.rewards_limit {
+            rewards::Limit::None => ConfigParam::RewardLimitNone,
+            rewards::Limit::ByStakeAbsolute(ratio) => {
+                ConfigParam::RewardLimitByAbsoluteStake(*ratio)
+            }
+        });
+        if self.linear_fees.per_certificate_fees != PerCertificateFee::default() {
+            params.push(ConfigParam::PerCertificateFees(
+                self.linear_fees.per_certificate_fees,
+            ));
+        }
+        if self.linear_fees.per_extended_certificate_fees != PerExtendedCertificateFee::default() {
+            params.push(ConfigParam::PerExtendedCertificateFees(
+                self.linear_fees.per_extended_certificate_fees,
+            ));
+        }
 
         match &self.reward_params {
             Some(p) => params.push(ConfigParam::RewardParams(p.clone())),
@@ -246,15 +429,49 @@ impl Settings {
             Some(p) => params.push(ConfigParam::TreasuryParams(p.clone())),
             None => (),
         };
+        match self.pool_participation_capping {
+            Some(capping) => params.push(ConfigParam::PoolRewardParticipationCapping(capping)),
+            None => (),
+        };
+        params.push(ConfigParam::PoolRewardSaturation(
+            self.pool_saturation_target,
+        ));
+        match self.stake_voting_quorum {
+            Some(ratio) => params.push(ConfigParam::StakeVotingQuorum(ratio)),
+            None => (),
+        };
+        match self.stake_voting_threshold {
+            Some(ratio) => params.push(ConfigParam::StakeVotingThreshold(ratio)),
+            None => (),
+        };
 
         debug_assert_eq!(self, &Settings::new().apply(&params).unwrap());
 
         params
     }
 
+    /// The number of blocks expected from the stake pools in an epoch of
+    /// `slots_per_epoch` slots, if the pool rewards are weighted by performance.
+    /// The slots reserved to the BFT leaders are not counted.
+    pub fn expected_epoch_blocks(&self, slots_per_epoch: u32) -> Option<NonZeroU64> {
+        if self.pool_performance_rewards {
+            let active_slots_coeff: Milli = self.active_slots_coeff.into();
+            let bft_slots = if self.bft_leaders.is_empty() {
+                0
+            } else {
+                leadership::bft_slots_count(self.bft_slots_ratio, slots_per_epoch)
+            };
+            let pool_slots = slots_per_epoch - bft_slots;
+            NonZeroU64::new(pool_slots as u64 * active_slots_coeff.to_millis() / 1000)
+        } else {
+            None
+        }
+    }
+
     pub fn to_reward_params(&self) -> rewards::Parameters {
         let reward_drawing_limit_max = self.rewards_limit.clone();
         let pool_participation_capping = self.pool_participation_capping.clone();
+        let pool_saturation_target = self.pool_saturation_target;
 
         match self.reward_params {
             None => rewards::Parameters::zero(),
@@ -271,6 +488,8 @@ impl Settings {
                 epoch_rate,
                 reward_drawing_limit_max,
                 pool_participation_capping,
+                pool_saturation_target,
+                expected_epoch_blocks: None,
             },
             Some(RewardParams::Linear {
                 constant,
@@ -285,6 +504,8 @@ impl Settings {
                 epoch_rate,
                 reward_drawing_limit_max,
                 pool_participation_capping,
+                pool_saturation_target,
+                expected_epoch_blocks: None,
             },
         }
     }
@@ -293,7 +514,16 @@ impl Settings {
 #[cfg(test)]
 mod tests {
     use super::{FeesGoesTo, Settings};
-    use quickcheck::{Arbitrary, Gen};
+    use crate::config::{Block0Date, ConfigParam, Tag, IMMUTABLE_PARAMS};
+    use crate::fee::{LinearFee, PerCertificateFee, PerExtendedCertificateFee};
+    use crate::fragment::config::ConfigParams;
+    use crate::milli::Milli;
+    use crate::update;
+    use crate::value::Value;
+    use chain_addr::Discrimination;
+    use quickcheck::{Arbitrary, Gen, TestResult};
+    use quickcheck_macros::quickcheck;
+    use std::num::{NonZeroU32, NonZeroU64};
 
     impl Arbitrary for FeesGoesTo {
         fn arbitrary<G: Gen>(g: &mut G) -> Self {
@@ -310,4 +540,147 @@ mod tests {
             Settings::new()
         }
     }
+
+    fn apply_one(settings: &Settings, param: ConfigParam) -> Result<Settings, update::Error> {
+        let mut changes = ConfigParams::new();
+        changes.push(param);
+        settings.apply(&changes)
+    }
+
+    #[test]
+    fn immutable_params_are_rejected() {
+        let params = vec![
+            ConfigParam::Discrimination(Discrimination::Test),
+            ConfigParam::Block0Date(Block0Date(0)),
+            ConfigParam::KESUpdateSpeed(3600),
+            ConfigParam::TreasuryAdd(Value(100)),
+            ConfigParam::RewardPot(Value(100)),
+        ];
+        assert_eq!(params.len(), IMMUTABLE_PARAMS.len());
+
+        for param in params {
+            let tag = Tag::from(&param);
+            assert!(tag.is_immutable());
+            assert_eq!(
+                apply_one(&Settings::new(), param),
+                Err(update::Error::ReadOnlySetting(tag))
+            );
+        }
+    }
+
+    #[test]
+    fn bft_slots_ratio_above_one_is_rejected() {
+        let settings = apply_one(&Settings::new(), ConfigParam::BftSlotsRatio(Milli::ONE)).unwrap();
+        assert_eq!(settings.bft_slots_ratio, Milli::ONE);
+
+        let ratio = Milli::from_millis(1001);
+        assert_eq!(
+            apply_one(&settings, ConfigParam::BftSlotsRatio(ratio)),
+            Err(update::Error::BadBftSlotsRatio(ratio))
+        );
+    }
+
+    #[test]
+    fn pool_reward_saturation_can_be_cleared() {
+        let target = NonZeroU32::new(100);
+        let old = apply_one(&Settings::new(), ConfigParam::PoolRewardSaturation(target)).unwrap();
+        assert_eq!(old.pool_saturation_target, target);
+
+        let new = apply_one(&old, ConfigParam::PoolRewardSaturation(None)).unwrap();
+        assert_eq!(new.pool_saturation_target, None);
+        assert_eq!(old.apply(&old.diff(&new)), Ok(new));
+    }
+
+    #[test]
+    fn linear_fee_and_per_certificate_fees_are_updated_separately() {
+        let per_certificate_fees =
+            PerCertificateFee::new(NonZeroU64::new(10), NonZeroU64::new(2), None);
+        let settings = apply_one(
+            &Settings::new(),
+            ConfigParam::PerCertificateFees(per_certificate_fees),
+        )
+        .unwrap();
+        assert_eq!(
+            settings.linear_fees.per_certificate_fees,
+            per_certificate_fees
+        );
+
+        let settings =
+            apply_one(&settings, ConfigParam::LinearFee(LinearFee::new(1, 2, 3))).unwrap();
+        assert_eq!(settings.linear_fees.constant, 1);
+        assert_eq!(
+            settings.linear_fees.per_certificate_fees,
+            per_certificate_fees
+        );
+    }
+
+    #[test]
+    fn per_certificate_fees_and_per_extended_certificate_fees_are_updated_separately() {
+        let per_certificate_fees =
+            PerCertificateFee::new(NonZeroU64::new(10), NonZeroU64::new(2), None);
+        let mut per_extended_certificate_fees = PerExtendedCertificateFee::default();
+        per_extended_certificate_fees.pool_retirement(NonZeroU64::new(5));
+        let settings = apply_one(
+            &Settings::new(),
+            ConfigParam::PerCertificateFees(per_certificate_fees),
+        )
+        .unwrap();
+        let settings = apply_one(
+            &settings,
+            ConfigParam::PerExtendedCertificateFees(per_extended_certificate_fees),
+        )
+        .unwrap();
+        assert_eq!(
+            settings.linear_fees.per_certificate_fees,
+            per_certificate_fees
+        );
+        assert_eq!(
+            settings.linear_fees.per_extended_certificate_fees,
+            per_extended_certificate_fees
+        );
+    }
+
+    #[quickcheck]
+    fn mutable_params_are_kept_in_config_params(changes: ConfigParams) -> TestResult {
+        let mutable_changes: ConfigParams = {
+            let mut params = ConfigParams::new();
+            for param in changes.iter() {
+                if !Tag::from(param).is_immutable() {
+                    params.push(param.clone());
+                }
+            }
+            params
+        };
+        let settings = match Settings::new().apply(&mutable_changes) {
+            Ok(settings) => settings,
+            // invalid values (e.g. active slots coefficient)
+            Err(_) => return TestResult::discard(),
+        };
+        let params = settings.to_config_params();
+        TestResult::from_bool(Settings::new().apply(&params) == Ok(settings))
+    }
+
+    #[quickcheck]
+    fn diff_applied_to_old_settings_gives_new_settings(
+        initial: ConfigParams,
+        changes: ConfigParams,
+    ) -> TestResult {
+        let mutable = |params: ConfigParams| {
+            let mut mutable_params = ConfigParams::new();
+            for param in params.iter() {
+                if !Tag::from(param).is_immutable() {
+                    mutable_params.push(param.clone());
+                }
+            }
+            mutable_params
+        };
+        let (old, new) = match Settings::new().apply(&mutable(initial)).and_then(|old| {
+            let new = old.apply(&mutable(changes))?;
+            Ok((old, new))
+        }) {
+            Ok(settings) => settings,
+            Err(_) => return TestResult::discard(),
+        };
+        TestResult::from_bool(old.apply(&old.diff(&new)) == Ok(new))
+    }
 }
@@ -0,0 +1,592 @@
+use super::delegation::PoolsState;
+use super::distribution::{
+    get_distribution, PoolStakeDistribution, PoolStakeInformation, StakeAssignment,
+    StakeDistribution,
+};
+use super::stake::Stake;
+use crate::{
+    account, accounting::account::DelegationType, certificate::PoolId, transaction::Output, utxo,
+    value::Value,
+};
+use chain_addr::{Address, Kind};
+use imhamt::Hamt;
+use std::collections::hash_map::DefaultHasher;
+use thiserror::Error;
+
+/// The stake controlled by an account, and how it was assigned
+#[derive(Clone, PartialEq, Eq)]
+struct AccountStake {
+    delegation: DelegationType,
+    /// value held by the account itself
+    value: Stake,
+    /// values of the utxos of the group addresses using this account as stake
+    /// key, with their number of occurrences
+    utxos: Hamt<DefaultHasher, Stake, u64>,
+    /// sum of the assignments of the account value and of each utxo value, which
+    /// are split separately as `get_distribution` does
+    assignment: StakeAssignment,
+}
+
+impl AccountStake {
+    fn new() -> Self {
+        AccountStake {
+            delegation: DelegationType::NonDelegated,
+            value: Stake::zero(),
+            utxos: Hamt::new(),
+            assignment: StakeAssignment::new(&DelegationType::NonDelegated, Stake::zero(), |_| {
+                false
+            }),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+struct PoolStake {
+    total: Stake,
+    accounts: Hamt<DefaultHasher, account::Identifier, Stake>,
+}
+
+impl PoolStake {
+    fn new() -> Self {
+        PoolStake {
+            total: Stake::zero(),
+            accounts: Hamt::new(),
+        }
+    }
+
+    fn add(&self, id: &account::Identifier, stake: Stake) -> Self {
+        PoolStake {
+            total: self.total + stake,
+            accounts: self
+                .accounts
+                .insert_or_update_simple(id.clone(), stake, |s| Some(*s + stake)),
+        }
+    }
+
+    fn sub(&self, id: &account::Identifier, stake: Stake) -> Self {
+        PoolStake {
+            total: self.total.saturating_sub(stake),
+            accounts: self
+                .accounts
+                .replace_with(id, |s| s.saturating_sub(stake))
+                .unwrap_or_else(|_| self.accounts.clone()),
+        }
+    }
+
+    fn remove(&self, id: &account::Identifier, stake: Stake) -> Self {
+        PoolStake {
+            total: self.total.saturating_sub(stake),
+            accounts: self
+                .accounts
+                .remove(id)
+                .unwrap_or_else(|_| self.accounts.clone()),
+        }
+    }
+}
+
+/// Stake distribution maintained along the ledger changes.
+///
+/// Instead of scanning every account and every utxo as `get_distribution`
+/// does, the value of each account (and of the group addresses pointing to
+/// it) is assigned to the stake pools as it changes, so the distribution
+/// can be obtained at the cost of the number of delegators.
+#[derive(Clone, PartialEq, Eq)]
+pub struct StakeAggregates {
+    unassigned: Stake,
+    dangling: Stake,
+    accounts: Hamt<DefaultHasher, account::Identifier, AccountStake>,
+    /// stake of the registered pools
+    pools: Hamt<DefaultHasher, PoolId, PoolStake>,
+    /// accounts delegating to a pool, whether it is registered or not
+    delegators: Hamt<DefaultHasher, PoolId, Hamt<DefaultHasher, account::Identifier, ()>>,
+}
+
+/// The incrementally maintained stake distribution doesn't match the one
+/// computed from scratch
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("maintained stake distribution doesn't match the recomputed one")]
+pub struct StakeDistributionMismatch {
+    pub maintained: StakeDistribution,
+    pub recomputed: StakeDistribution,
+}
+
+fn delegated_pools(delegation: &DelegationType) -> Vec<&PoolId> {
+    match delegation {
+        DelegationType::NonDelegated => Vec::new(),
+        DelegationType::Full(pool_id) => vec![pool_id],
+        DelegationType::Ratio(dr) => dr.pools().iter().map(|(pool_id, _)| pool_id).collect(),
+    }
+}
+
+impl Default for StakeAggregates {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StakeAggregates {
+    pub fn new() -> Self {
+        StakeAggregates {
+            unassigned: Stake::zero(),
+            dangling: Stake::zero(),
+            accounts: Hamt::new(),
+            pools: Hamt::new(),
+            delegators: Hamt::new(),
+        }
+    }
+
+    /// Compute the aggregates from scratch, from the same sources as `get_distribution`
+    pub fn new_with(
+        accounts: &account::Ledger,
+        dstate: &PoolsState,
+        utxos: &utxo::Ledger<Address>,
+    ) -> Self {
+        let mut aggregates = dstate
+            .stake_pool_ids()
+            .fold(Self::new(), |aggregates, pool_id| {
+                aggregates.register_pool(&pool_id)
+            });
+        for (identifier, account_state) in accounts.iter() {
+            aggregates = aggregates.set_account(
+                identifier,
+                account_state.delegation(),
+                account_state.value(),
+            );
+        }
+        for output in utxos.values() {
+            aggregates = aggregates.add_utxo(output);
+        }
+        aggregates
+    }
+
+    /// Set the value and the delegation of an account
+    pub fn set_account(
+        &self,
+        id: &account::Identifier,
+        delegation: &DelegationType,
+        value: Value,
+    ) -> Self {
+        let mut new = self.clone();
+        let mut account = self.account_or_new(id);
+        if &account.delegation != delegation {
+            new.set_delegator(id, &account.delegation, delegation);
+            account.delegation = delegation.clone();
+        }
+        account.value = Stake::from_value(value);
+        new.reassign(id, account);
+        new
+    }
+
+    /// Account for a new utxo
+    pub fn add_utxo(&self, output: &Output<Address>) -> Self {
+        let value = Stake::from_value(output.value);
+        let mut new = self.clone();
+        match output.address.kind() {
+            Kind::Single(_) => new.unassigned += value,
+            Kind::Group(_, account_key) => {
+                let id = account_key.clone().into();
+                let mut account = new.account_or_new(&id);
+                account.utxos = account
+                    .utxos
+                    .insert_or_update_simple(value, 1, |n| Some(n + 1));
+                let assignment = new.assignment_of(&account.delegation, value);
+                new.add_assignment(&id, &assignment);
+                account.assignment.add(&assignment);
+                new.set_account_stake(&id, account);
+            }
+            // single or multisig account are not present in utxos
+            Kind::Account(_) | Kind::Multisig(_) => {}
+        }
+        new
+    }
+
+    /// Account for a spent utxo
+    pub fn remove_utxo(&self, output: &Output<Address>) -> Self {
+        let value = Stake::from_value(output.value);
+        let mut new = self.clone();
+        match output.address.kind() {
+            Kind::Single(_) => new.unassigned = new.unassigned.saturating_sub(value),
+            Kind::Group(_, account_key) => {
+                let id = account_key.clone().into();
+                let mut account = new.account_or_new(&id);
+                let utxos = account.utxos.update(&value, |n| {
+                    Ok::<_, ()>(if *n > 1 { Some(n - 1) } else { None })
+                });
+                if let Ok(utxos) = utxos {
+                    account.utxos = utxos;
+                    let assignment = new.assignment_of(&account.delegation, value);
+                    new.sub_assignment(&id, &assignment);
+                    account.assignment.remove(&assignment);
+                    new.set_account_stake(&id, account);
+                }
+            }
+            Kind::Account(_) | Kind::Multisig(_) => {}
+        }
+        new
+    }
+
+    /// Start assigning stake to a newly registered pool, moving the stake
+    /// of its delegators out of the dangling stake
+    pub fn register_pool(&self, pool_id: &PoolId) -> Self {
+        let mut new = self.clone();
+        match self.pools.insert(pool_id.clone(), PoolStake::new()) {
+            Ok(pools) => new.pools = pools,
+            Err(_) => return new,
+        }
+        new.reassign_delegators(pool_id);
+        new
+    }
+
+    /// Stop assigning stake to a retired pool, its delegators stake
+    /// becoming dangling
+    pub fn retire_pool(&self, pool_id: &PoolId) -> Self {
+        let mut new = self.clone();
+        match self.pools.remove(pool_id) {
+            Ok(pools) => new.pools = pools,
+            Err(_) => return new,
+        }
+        new.reassign_delegators(pool_id);
+        new
+    }
+
+    /// Get the stake distribution, with the registrations of the pools from `dstate`
+    pub fn distribution(&self, dstate: &PoolsState) -> StakeDistribution {
+        StakeDistribution {
+            unassigned: self.unassigned,
+            dangling: self.dangling,
+            to_pools: self
+                .pools
+                .iter()
+                .map(|(pool_id, pool)| {
+                    let information = PoolStakeInformation {
+                        registration: dstate
+                            .lookup(pool_id)
+                            .map(|pool_state| pool_state.registration.clone()),
+                        stake: PoolStakeDistribution {
+                            total: pool.total,
+                            accounts: pool
+                                .accounts
+                                .iter()
+                                .map(|(id, stake)| (id.clone(), *stake))
+                                .collect(),
+                        },
+                    };
+                    (pool_id.clone(), information)
+                })
+                .collect(),
+        }
+    }
+
+    /// Check the maintained distribution against a full recompute with `get_distribution`
+    pub fn check(
+        &self,
+        accounts: &account::Ledger,
+        dstate: &PoolsState,
+        utxos: &utxo::Ledger<Address>,
+    ) -> Result<(), StakeDistributionMismatch> {
+        let maintained = self.distribution(dstate);
+        let recomputed = get_distribution(accounts, dstate, utxos);
+        if maintained == recomputed {
+            Ok(())
+        } else {
+            Err(StakeDistributionMismatch {
+                maintained,
+                recomputed,
+            })
+        }
+    }
+
+    fn account_or_new(&self, id: &account::Identifier) -> AccountStake {
+        self.accounts
+            .lookup(id)
+            .cloned()
+            .unwrap_or_else(AccountStake::new)
+    }
+
+    fn set_account_stake(&mut self, id: &account::Identifier, account: AccountStake) {
+        self.accounts = self
+            .accounts
+            .insert_or_update_simple(id.clone(), account.clone(), |_| Some(account));
+    }
+
+    /// Assignment of a value with the given delegation to the registered pools
+    fn assignment_of(&self, delegation: &DelegationType, value: Stake) -> StakeAssignment {
+        let pools = &self.pools;
+        StakeAssignment::new(delegation, value, |pool_id| pools.contains_key(pool_id))
+    }
+
+    fn add_assignment(&mut self, id: &account::Identifier, assignment: &StakeAssignment) {
+        self.unassigned += assignment.unassigned;
+        self.dangling += assignment.dangling;
+        for (pool_id, stake) in assignment.pools.iter() {
+            if let Ok(pools) = self
+                .pools
+                .replace_with(pool_id, |pool| pool.add(id, *stake))
+            {
+                self.pools = pools;
+            }
+        }
+    }
+
+    fn sub_assignment(&mut self, id: &account::Identifier, assignment: &StakeAssignment) {
+        self.unassigned = self.unassigned.saturating_sub(assignment.unassigned);
+        self.dangling = self.dangling.saturating_sub(assignment.dangling);
+        for (pool_id, stake) in assignment.pools.iter() {
+            if let Ok(pools) = self
+                .pools
+                .replace_with(pool_id, |pool| pool.sub(id, *stake))
+            {
+                self.pools = pools;
+            }
+        }
+    }
+
+    /// Remove the previous assignment of the account stake, and assign it
+    /// again from its current values and delegation
+    fn reassign(&mut self, id: &account::Identifier, mut account: AccountStake) {
+        let previous = &account.assignment;
+        self.unassigned = self.unassigned.saturating_sub(previous.unassigned);
+        self.dangling = self.dangling.saturating_sub(previous.dangling);
+        for (pool_id, stake) in previous.pools.iter() {
+            if let Ok(pools) = self
+                .pools
+                .replace_with(pool_id, |pool| pool.remove(id, *stake))
+            {
+                self.pools = pools;
+            }
+        }
+
+        let mut assignment = self.assignment_of(&account.delegation, account.value);
+        for (value, count) in account.utxos.iter() {
+            let utxo_assignment = self.assignment_of(&account.delegation, *value);
+            for _ in 0..*count {
+                assignment.add(&utxo_assignment);
+            }
+        }
+        self.add_assignment(id, &assignment);
+        account.assignment = assignment;
+        self.set_account_stake(id, account);
+    }
+
+    fn reassign_delegators(&mut self, pool_id: &PoolId) {
+        let delegators: Vec<account::Identifier> = match self.delegators.lookup(pool_id) {
+            None => return,
+            Some(delegators) => delegators.iter().map(|(id, _)| id.clone()).collect(),
+        };
+        for id in delegators {
+            if let Some(account) = self.accounts.lookup(&id).cloned() {
+                self.reassign(&id, account);
+            }
+        }
+    }
+
+    fn set_delegator(
+        &mut self,
+        id: &account::Identifier,
+        previous: &DelegationType,
+        delegation: &DelegationType,
+    ) {
+        for pool_id in delegated_pools(previous) {
+            let updated = self.delegators.update(pool_id, |delegators| {
+                let delegators = delegators.remove(id).unwrap_or_else(|_| delegators.clone());
+                Ok::<_, ()>(if delegators.is_empty() {
+                    None
+                } else {
+                    Some(delegators)
+                })
+            });
+            if let Ok(updated) = updated {
+                self.delegators = updated;
+            }
+        }
+        for pool_id in delegated_pools(delegation) {
+            self.delegators = self.delegators.insert_or_update_simple(
+                pool_id.clone(),
+                std::iter::once((id.clone(), ())).collect(),
+                |delegators| {
+                    Some(
+                        delegators
+                            .insert(id.clone(), ())
+                            .unwrap_or_else(|_| delegators.clone()),
+                    )
+                },
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        account::DelegationRatio,
+        fragment::FragmentId,
+        testing::{
+            builders::StakePoolBuilder,
+            data::{AddressData, StakePool},
+            TestGen,
+        },
+    };
+    use chain_addr::Discrimination;
+
+    /// Apply the same changes to the aggregates and to the ledger structures
+    /// `get_distribution` is computed from
+    struct Fixture {
+        aggregates: StakeAggregates,
+        accounts: account::Ledger,
+        dstate: PoolsState,
+        utxos: utxo::Ledger<Address>,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            Fixture {
+                aggregates: StakeAggregates::new(),
+                accounts: account::Ledger::new(),
+                dstate: PoolsState::new(),
+                utxos: utxo::Ledger::new(),
+            }
+        }
+
+        fn add_account(&mut self, id: &account::Identifier, value: Value) {
+            self.accounts = self.accounts.add_account(id, value, ()).unwrap();
+            self.aggregates = self
+                .aggregates
+                .set_account(id, &DelegationType::NonDelegated, value);
+        }
+
+        fn delegate(&mut self, id: &account::Identifier, delegation: DelegationType) {
+            self.accounts = self.accounts.set_delegation(id, &delegation).unwrap();
+            let value = self.accounts.get_state(id).unwrap().value();
+            self.aggregates = self.aggregates.set_account(id, &delegation, value);
+        }
+
+        fn add_utxo(&mut self, fragment_id: FragmentId, output: Output<Address>) {
+            self.utxos = self
+                .utxos
+                .add(&fragment_id, &[(0, output.clone())])
+                .unwrap();
+            self.aggregates = self.aggregates.add_utxo(&output);
+        }
+
+        fn remove_utxo(&mut self, fragment_id: FragmentId) {
+            let (utxos, output) = self.utxos.remove(&fragment_id, 0).unwrap();
+            self.utxos = utxos;
+            self.aggregates = self.aggregates.remove_utxo(&output);
+        }
+
+        fn register(&mut self, pool: &StakePool) {
+            self.dstate = self.dstate.register_stake_pool(pool.info()).unwrap();
+            self.aggregates = self.aggregates.register_pool(&pool.id());
+        }
+
+        fn retire(&mut self, pool: &StakePool) {
+            self.dstate = self.dstate.deregister_stake_pool(&pool.id()).unwrap();
+            self.aggregates = self.aggregates.retire_pool(&pool.id());
+        }
+
+        fn assert_consistent(&self) {
+            self.aggregates
+                .check(&self.accounts, &self.dstate, &self.utxos)
+                .unwrap();
+            assert!(
+                StakeAggregates::new_with(&self.accounts, &self.dstate, &self.utxos)
+                    == self.aggregates
+            );
+        }
+    }
+
+    #[test]
+    pub fn aggregates_follow_utxos_and_delegation() {
+        let mut fixture = Fixture::new();
+        let pool = StakePoolBuilder::new().build();
+        let pool_id = pool.id();
+        fixture.register(&pool);
+
+        let account = AddressData::account(Discrimination::Test);
+        let group = AddressData::delegation_for(&account);
+        let single = AddressData::utxo(Discrimination::Test);
+        fixture.add_account(&account.to_id(), Value(100));
+        fixture.add_utxo(TestGen::hash(), group.make_output(&Value(20)));
+        let spent = TestGen::hash();
+        fixture.add_utxo(spent, single.make_output(&Value(7)));
+        fixture.assert_consistent();
+
+        fixture.delegate(&account.to_id(), DelegationType::Full(pool_id.clone()));
+        fixture.assert_consistent();
+        assert_eq!(
+            fixture
+                .aggregates
+                .distribution(&fixture.dstate)
+                .get_stake_for(&pool_id),
+            Some(Stake(120))
+        );
+
+        fixture.remove_utxo(spent);
+        fixture.assert_consistent();
+        assert_eq!(
+            fixture.aggregates.distribution(&fixture.dstate).unassigned,
+            Stake::zero()
+        );
+    }
+
+    #[test]
+    pub fn pool_registration_and_retirement_move_delegated_stake() {
+        let mut fixture = Fixture::new();
+        let first = StakePoolBuilder::new().build();
+        let second = StakePoolBuilder::new().build();
+
+        let account = AddressData::account(Discrimination::Test);
+        fixture.add_account(&account.to_id(), Value(101));
+        let ratio = DelegationRatio::new(4, vec![(first.id(), 1), (second.id(), 3)]).unwrap();
+        fixture.delegate(&account.to_id(), DelegationType::Ratio(ratio));
+        fixture.assert_consistent();
+        assert_eq!(
+            fixture.aggregates.distribution(&fixture.dstate).dangling,
+            Stake(101)
+        );
+
+        // the remaining of the split goes to the first registered pool
+        fixture.register(&second);
+        fixture.assert_consistent();
+        fixture.register(&first);
+        fixture.assert_consistent();
+
+        fixture.retire(&first);
+        fixture.assert_consistent();
+        let distribution = fixture.aggregates.distribution(&fixture.dstate);
+        assert_eq!(distribution.get_stake_for(&second.id()), Some(Stake(76)));
+        assert_eq!(distribution.dangling, Stake(25));
+    }
+
+    #[test]
+    pub fn account_value_and_utxos_are_split_separately() {
+        let mut fixture = Fixture::new();
+        let first = StakePoolBuilder::new().build();
+        let second = StakePoolBuilder::new().build();
+        fixture.register(&first);
+        fixture.register(&second);
+
+        let account = AddressData::account(Discrimination::Test);
+        let group = AddressData::delegation_for(&account);
+        fixture.add_account(&account.to_id(), Value(101));
+        let ratio = DelegationRatio::new(4, vec![(first.id(), 1), (second.id(), 3)]).unwrap();
+        fixture.delegate(&account.to_id(), DelegationType::Ratio(ratio));
+        let spent = TestGen::hash();
+        fixture.add_utxo(spent, group.make_output(&Value(3)));
+        fixture.add_utxo(TestGen::hash(), group.make_output(&Value(3)));
+        fixture.add_utxo(TestGen::hash(), group.make_output(&Value(2)));
+        fixture.assert_consistent();
+
+        // each remaining of the splits goes to the first pool, 109 would
+        // have been split in 28 and 81 at once
+        let distribution = fixture.aggregates.distribution(&fixture.dstate);
+        assert_eq!(distribution.get_stake_for(&first.id()), Some(Stake(34)));
+        assert_eq!(distribution.get_stake_for(&second.id()), Some(Stake(75)));
+
+        fixture.remove_utxo(spent);
+        fixture.assert_consistent();
+        fixture.retire(&first);
+        fixture.assert_consistent();
+    }
+}
@@ -1,7 +1,7 @@
 use crate::certificate::{PoolId, PoolRegistration, PoolRegistrationHash};
 use crate::date::Epoch;
 use crate::value::Value;
-use imhamt::Hamt;
+use imhamt::{Diff, Hamt};
 use std::collections::hash_map::DefaultHasher;
 use std::fmt::{self, Debug};
 use std::sync::Arc;
@@ -39,13 +39,20 @@ impl PoolLastRewards {
 pub struct PoolState {
     pub last_rewards: PoolLastRewards,
     pub registration: Arc<PoolRegistration>,
+    /// deposit locked at registration, refunded when the pool retires
+    pub deposit: Value,
 }
 
 impl PoolState {
     pub fn new(reg: PoolRegistration) -> Self {
+        Self::with_deposit(reg, Value::zero())
+    }
+
+    pub fn with_deposit(reg: PoolRegistration, deposit: Value) -> Self {
         PoolState {
             last_rewards: PoolLastRewards::default(),
             registration: Arc::new(reg),
+            deposit,
         }
     }
 
@@ -161,10 +168,18 @@ impl PoolsState {
     }
 
     pub fn register_stake_pool(&self, owner: PoolRegistration) -> Result<Self, PoolError> {
+        self.register_stake_pool_with_deposit(owner, Value::zero())
+    }
+
+    pub fn register_stake_pool_with_deposit(
+        &self,
+        owner: PoolRegistration,
+        deposit: Value,
+    ) -> Result<Self, PoolError> {
         let id = owner.to_id();
         let new_pools = self
             .stake_pools
-            .insert(id.clone(), PoolState::new(owner))
+            .insert(id.clone(), PoolState::with_deposit(owner, deposit))
             .map_err(|_| PoolError::AlreadyExists(id))?;
         Ok(PoolsState {
             stake_pools: new_pools,
@@ -179,6 +194,12 @@ impl PoolsState {
                 .map_err(|_| PoolError::NotFound(pool_id.clone()))?,
         })
     }
+
+    /// Compute the pools registered, retired and updated between this state
+    /// and a newer one.
+    pub fn diff<'a>(&'a self, new: &'a Self) -> Vec<Diff<'a, PoolId, PoolState>> {
+        self.stake_pools.diff(&new.stake_pools)
+    }
 }
 
 #[cfg(test)]
@@ -211,6 +232,7 @@ mod tests {
             PoolState {
                 last_rewards: PoolLastRewards::arbitrary(gen),
                 registration,
+                deposit: Value::arbitrary(gen),
             }
         }
     }
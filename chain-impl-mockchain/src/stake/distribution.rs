@@ -3,6 +3,7 @@ use crate::{
     account,
     accounting::account::DelegationType,
     certificate::{PoolId, PoolRegistration},
+    rewards::TaxType,
     utxo,
 };
 use chain_addr::{Address, Kind};
@@ -30,6 +31,25 @@ impl StakeDistribution {
     pub fn get_total_stake(&self) -> Stake {
         Stake::sum(self.to_pools.values().map(|psi| psi.stake.total))
     }
+
+    /// Replace the rewards tax of a registered pool in this distribution.
+    ///
+    /// This only affects this distribution, and is mostly useful to preview
+    /// the effect of a different tax on the rewards before registering it.
+    /// Returns false if the pool is not part of the distribution or not registered.
+    pub fn set_pool_tax(&mut self, pool_id: &PoolId, tax: TaxType) -> bool {
+        match self
+            .to_pools
+            .get_mut(pool_id)
+            .and_then(|psi| psi.registration.as_mut())
+        {
+            None => false,
+            Some(reg) => {
+                Arc::make_mut(reg).rewards = tax;
+                true
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -107,47 +127,103 @@ impl StakeDistribution {
     }
 }
 
-fn assign_account_value(
-    sd: &mut StakeDistribution,
-    account_identifier: &account::Identifier,
-    delegation_type: &DelegationType,
-    value: Stake,
-) {
-    match delegation_type {
-        DelegationType::NonDelegated => sd.unassigned += value,
-        DelegationType::Full(ref pool_id) => {
-            // if the pool exists, we add value to this pool distribution,
-            // otherwise it get added to the dangling sum
-            match sd.to_pools.get_mut(pool_id) {
-                None => sd.dangling += value,
-                Some(pool_info) => pool_info.add_value(&account_identifier, value),
+/// How the stake of an account is split between the stake pools, the
+/// unassigned and the dangling stake.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct StakeAssignment {
+    pub unassigned: Stake,
+    pub dangling: Stake,
+    pub pools: Vec<(PoolId, Stake)>,
+}
+
+impl StakeAssignment {
+    pub(crate) fn new<F>(delegation_type: &DelegationType, value: Stake, pool_exists: F) -> Self
+    where
+        F: Fn(&PoolId) -> bool,
+    {
+        let mut assignment = StakeAssignment {
+            unassigned: Stake::zero(),
+            dangling: Stake::zero(),
+            pools: Vec::new(),
+        };
+        match delegation_type {
+            DelegationType::NonDelegated => assignment.unassigned += value,
+            DelegationType::Full(ref pool_id) => {
+                // if the pool exists, we add value to this pool distribution,
+                // otherwise it get added to the dangling sum
+                if pool_exists(pool_id) {
+                    assignment.pools.push((pool_id.clone(), value))
+                } else {
+                    assignment.dangling += value
+                }
             }
-        }
-        DelegationType::Ratio(dr) => {
-            // is the ratio distribution is not correct, considered it unassigned, otherwise
-            // separate the total in as many parts as pools, and try to assign from the first to the last,
-            // the stake associated plus if there's any remaining from the division.
-            if dr.is_valid() {
-                let sin = value.split_in(dr.parts() as u32);
-                let mut r = sin.remaining;
-                for (pool_id, ratio) in dr.pools().iter() {
-                    let pool_value = sin.parts.scale(*ratio as u32);
-                    match sd.to_pools.get_mut(pool_id) {
-                        None => sd.dangling += pool_value,
-                        Some(pool_info) => {
-                            pool_info.add_value(&account_identifier, pool_value + r);
+            DelegationType::Ratio(dr) => {
+                // is the ratio distribution is not correct, considered it unassigned, otherwise
+                // separate the total in as many parts as pools, and try to assign from the first to the last,
+                // the stake associated plus if there's any remaining from the division.
+                if dr.is_valid() {
+                    let sin = value.split_in(dr.parts() as u32);
+                    let mut r = sin.remaining;
+                    for (pool_id, ratio) in dr.pools().iter() {
+                        let pool_value = sin.parts.scale(*ratio as u32);
+                        if pool_exists(pool_id) {
+                            assignment.pools.push((pool_id.clone(), pool_value + r));
                             r = Stake::zero();
+                        } else {
+                            assignment.dangling += pool_value
                         }
                     }
+                    // if r is not zero already, then we failed to assign it to anything, so just consider it as dangling
+                    if r > Stake::zero() {
+                        assignment.dangling += r
+                    }
+                } else {
+                    assignment.unassigned += value
                 }
-                // if r is not zero already, then we failed to assign it to anything, so just consider it as dangling
-                if r > Stake::zero() {
-                    sd.dangling += r
-                }
-            } else {
-                sd.unassigned += value
             }
         }
+        assignment
+    }
+
+    /// Add the assignment of another value of the same account
+    pub(crate) fn add(&mut self, other: &StakeAssignment) {
+        self.unassigned += other.unassigned;
+        self.dangling += other.dangling;
+        for (pool_id, stake) in other.pools.iter() {
+            match self.pools.iter_mut().find(|(id, _)| id == pool_id) {
+                Some((_, pool_stake)) => *pool_stake += *stake,
+                None => self.pools.push((pool_id.clone(), *stake)),
+            }
+        }
+    }
+
+    /// Remove the assignment of a value previously added with `add`
+    pub(crate) fn remove(&mut self, other: &StakeAssignment) {
+        self.unassigned = self.unassigned.saturating_sub(other.unassigned);
+        self.dangling = self.dangling.saturating_sub(other.dangling);
+        for (pool_id, stake) in other.pools.iter() {
+            if let Some((_, pool_stake)) = self.pools.iter_mut().find(|(id, _)| id == pool_id) {
+                *pool_stake = pool_stake.saturating_sub(*stake);
+            }
+        }
+    }
+}
+
+fn assign_account_value(
+    sd: &mut StakeDistribution,
+    account_identifier: &account::Identifier,
+    delegation_type: &DelegationType,
+    value: Stake,
+) {
+    let assignment = StakeAssignment::new(delegation_type, value, |pool_id| {
+        sd.to_pools.contains_key(pool_id)
+    });
+    sd.unassigned += assignment.unassigned;
+    sd.dangling += assignment.dangling;
+    for (pool_id, pool_value) in assignment.pools {
+        if let Some(pool_info) = sd.to_pools.get_mut(&pool_id) {
+            pool_info.add_value(account_identifier, pool_value)
+        }
     }
 }
 
@@ -1,7 +1,9 @@
+mod aggregates;
 mod delegation;
 mod distribution;
 mod stake;
 
+pub use aggregates::*;
 pub use delegation::*;
 pub use distribution::*;
 pub use stake::*;
@@ -44,6 +44,10 @@ impl Stake {
             remaining: Stake(self.0 % n),
         }
     }
+
+    pub fn saturating_sub(self, other: Self) -> Self {
+        Stake(self.0.saturating_sub(other.0))
+    }
 }
 
 impl From<Stake> for u64 {
@@ -4,6 +4,7 @@ use crate::{
     fee::LinearFee,
     fragment::config::ConfigParams,
     key::BftLeaderId,
+    rewards::{Ratio, TaxType},
     testing::builders::SignedProposalBuilder,
     testing::{arbitrary::utils as arbitrary_utils, builders::update_builder::ProposalBuilder},
     update::{SignedUpdateProposal, SignedUpdateVote, UpdateVote},
@@ -69,6 +70,9 @@ impl Arbitrary for UpdateProposalData {
             ConfigParam::BlockContentMaxSize(u32::arbitrary(gen)),
             ConfigParam::LinearFee(LinearFee::arbitrary(gen)),
             ConfigParam::ProposalExpiration(u32::arbitrary(gen)),
+            ConfigParam::TreasuryParams(TaxType::arbitrary(gen)),
+            ConfigParam::FeesInTreasury(bool::arbitrary(gen)),
+            ConfigParam::RewardLimitByAbsoluteStake(Ratio::arbitrary(gen)),
         ];
 
         let update_proposal = ProposalBuilder::new()
@@ -1,11 +1,14 @@
 use crate::{
+    certificate::{PoolId, PoolOwnersSigned},
     config::ConfigParam,
-    key::BftLeaderId,
+    key::{BftLeaderId, EitherEd25519SecretKey},
+    transaction::{SingleAccountBindingSignature, TransactionBindingAuthData},
     update::{
-        SignedUpdateProposal, SignedUpdateVote, UpdateProposal, UpdateProposalId,
-        UpdateProposalWithProposer, UpdateVote,
+        PoolUpdateVote, SignedPoolUpdateVote, SignedUpdateProposal, SignedUpdateVote,
+        UpdateProposal, UpdateProposalId, UpdateProposalWithProposer, UpdateVote,
     },
 };
+use chain_core::property::Serialize;
 
 pub fn build_proposal(
     proposer_id: BftLeaderId,
@@ -37,3 +40,32 @@ pub fn build_vote(proposal_id: UpdateProposalId, leader_id: BftLeaderId) -> Sign
     };
     SignedUpdateVote { vote: update_vote }
 }
+
+pub fn build_pool_vote(
+    proposal_id: UpdateProposalId,
+    pool_id: PoolId,
+    approve: bool,
+    owners_keys: &[EitherEd25519SecretKey],
+) -> SignedPoolUpdateVote {
+    let vote = PoolUpdateVote {
+        proposal_id,
+        pool_id,
+        approve,
+    };
+
+    //sign vote by the pool owners
+    let data = vote.serialize_as_vec().unwrap();
+    let auth_data = TransactionBindingAuthData(&data);
+    let signatures = owners_keys
+        .iter()
+        .enumerate()
+        .map(|(i, key)| {
+            let sig = SingleAccountBindingSignature::new(&auth_data, |d| key.sign_slice(&d.0));
+            (i as u8, sig)
+        })
+        .collect();
+    SignedPoolUpdateVote {
+        vote,
+        signature: PoolOwnersSigned { signatures },
+    }
+}
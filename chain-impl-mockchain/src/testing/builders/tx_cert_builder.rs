@@ -16,20 +16,40 @@ use crate::{
 pub struct TestTxCertBuilder {
     block0_hash: HeaderId,
     fee: LinearFee,
+    pool_deposit: Value,
 }
 
 impl TestTxCertBuilder {
     pub fn new(block0_hash: HeaderId, fee: LinearFee) -> Self {
-        Self { block0_hash, fee }
+        Self {
+            block0_hash,
+            fee,
+            pool_deposit: Value::zero(),
+        }
+    }
+
+    /// Pay the given deposit, in addition to the fee, when registering a pool
+    pub fn with_pool_deposit(mut self, pool_deposit: Value) -> Self {
+        self.pool_deposit = pool_deposit;
+        self
     }
 
     fn block0_hash(&self) -> &HeaderId {
         &self.block0_hash
     }
 
-    fn fee(&self, certificate: &Certificate) -> Value {
+    fn fee(&self, certificate: &Certificate, fragment_size: usize) -> Value {
         let payload: CertificatePayload = certificate.into();
-        self.fee.calculate(Some(payload.as_slice()), 1, 0)
+        self.fee
+            .calculate(Some(payload.as_slice()), 1, 0, 0, fragment_size)
+    }
+
+    fn input_value(&self, certificate: &Certificate, fragment_size: usize) -> Value {
+        let fee = self.fee(certificate, fragment_size);
+        match certificate {
+            Certificate::PoolRegistration(_) => (fee + self.pool_deposit).unwrap(),
+            _ => fee,
+        }
     }
 
     fn set_initial_ios<P: Payload>(
@@ -37,9 +57,10 @@ impl TestTxCertBuilder {
         builder: TxBuilderState<SetIOs<P>>,
         funder: &Wallet,
         cert: &Certificate,
+        fragment_size: usize,
     ) -> TxBuilderState<SetAuthData<P>> {
         //utxo not supported yet
-        let input = funder.make_input_with_value(&self.fee(cert));
+        let input = funder.make_input_with_value(&self.input_value(cert, fragment_size));
         let builder = builder.set_ios(&[input], &[]);
         let witness = make_witness(
             self.block0_hash(),
@@ -54,10 +75,32 @@ impl TestTxCertBuilder {
         cert: &Certificate,
         keys: Vec<EitherEd25519SecretKey>,
         funder: &Wallet,
+    ) -> Fragment {
+        // the size of the fragment does not depend on the value of its input,
+        // so a first pass gives the size to charge
+        let fragment_size = self
+            .fragment_of_size(cert, &keys, funder, 0)
+            .to_raw()
+            .as_ref()
+            .len();
+        self.fragment_of_size(cert, &keys, funder, fragment_size)
+    }
+
+    fn fragment_of_size(
+        &self,
+        cert: &Certificate,
+        keys: &[EitherEd25519SecretKey],
+        funder: &Wallet,
+        fragment_size: usize,
     ) -> Fragment {
         match cert {
             Certificate::StakeDelegation(s) => {
-                let builder = self.set_initial_ios(TxBuilder::new().set_payload(s), &funder, cert);
+                let builder = self.set_initial_ios(
+                    TxBuilder::new().set_payload(s),
+                    &funder,
+                    cert,
+                    fragment_size,
+                );
                 let signature =
                     AccountBindingSignature::new_single(&builder.get_auth_data(), |d| {
                         keys[0].sign_slice(&d.0)
@@ -66,28 +109,58 @@ impl TestTxCertBuilder {
                 Fragment::StakeDelegation(tx)
             }
             Certificate::PoolRegistration(s) => {
-                let builder = self.set_initial_ios(TxBuilder::new().set_payload(s), &funder, cert);
-                let signature = pool_owner_sign(&keys, &builder);
+                let builder = self.set_initial_ios(
+                    TxBuilder::new().set_payload(s),
+                    &funder,
+                    cert,
+                    fragment_size,
+                );
+                let signature = pool_owner_sign(keys, &builder);
                 let tx = builder.set_payload_auth(&signature);
                 Fragment::PoolRegistration(tx)
             }
             Certificate::PoolRetirement(s) => {
-                let builder = self.set_initial_ios(TxBuilder::new().set_payload(s), &funder, cert);
-                let signature = pool_owner_sign(&keys, &builder);
+                let builder = self.set_initial_ios(
+                    TxBuilder::new().set_payload(s),
+                    &funder,
+                    cert,
+                    fragment_size,
+                );
+                let signature = pool_owner_sign(keys, &builder);
                 let tx = builder.set_payload_auth(&signature);
                 Fragment::PoolRetirement(tx)
             }
             Certificate::PoolUpdate(s) => {
-                let builder = self.set_initial_ios(TxBuilder::new().set_payload(s), &funder, cert);
-                let signature = pool_owner_sign(&keys, &builder);
+                let builder = self.set_initial_ios(
+                    TxBuilder::new().set_payload(s),
+                    &funder,
+                    cert,
+                    fragment_size,
+                );
+                let signature = pool_owner_sign(keys, &builder);
                 let tx = builder.set_payload_auth(&signature);
                 Fragment::PoolUpdate(tx)
             }
             Certificate::OwnerStakeDelegation(s) => {
-                let builder = self.set_initial_ios(TxBuilder::new().set_payload(s), &funder, cert);
+                let builder = self.set_initial_ios(
+                    TxBuilder::new().set_payload(s),
+                    &funder,
+                    cert,
+                    fragment_size,
+                );
                 let tx = builder.set_payload_auth(&());
                 Fragment::OwnerStakeDelegation(tx)
             }
+            Certificate::MultisigDeclaration(s) => {
+                let builder = self.set_initial_ios(
+                    TxBuilder::new().set_payload(s),
+                    &funder,
+                    cert,
+                    fragment_size,
+                );
+                let tx = builder.set_payload_auth(&());
+                Fragment::MultisigDeclaration(tx)
+            }
         }
     }
 
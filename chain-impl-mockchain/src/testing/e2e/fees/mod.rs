@@ -1,5 +1,5 @@
 use crate::{
-    fee::{LinearFee, PerCertificateFee},
+    fee::{LinearFee, PerCertificateFee, PerExtendedCertificateFee},
     testing::{
         builders::StakePoolBuilder,
         ledger::ConfigBuilder,
@@ -182,3 +182,41 @@ pub fn owner_delegates_fee() {
 
     ledger_verifier.total_value_is(&Value(expected_total_funds_after));
 }
+
+#[test]
+pub fn pool_retirement_fee() {
+    let default_certificate_fee = 10;
+    let certificate_pool_retirement_fee = 50;
+    let alice_funds = 1_000;
+
+    let mut per_extended_certificate_fee = PerExtendedCertificateFee::default();
+    per_extended_certificate_fee.pool_retirement(NonZeroU64::new(certificate_pool_retirement_fee));
+
+    let (mut ledger, controller) = prepare_scenario()
+        .with_config(
+            ConfigBuilder::new(0)
+                .with_discrimination(Discrimination::Test)
+                .with_fee(LinearFee::new(1, 1, default_certificate_fee))
+                .with_per_extended_certificate_fee(per_extended_certificate_fee),
+        )
+        .with_initials(vec![wallet("Alice").with(alice_funds).owns("stake_pool")])
+        .build()
+        .unwrap();
+
+    let mut alice = controller.wallet("Alice").unwrap();
+    let stake_pool = controller.stake_pool("stake_pool").unwrap();
+
+    controller
+        .retire(&[&alice], &stake_pool, &mut ledger)
+        .unwrap();
+    alice.confirm_transaction();
+
+    let expected_fee = 1 + 1 + certificate_pool_retirement_fee;
+    let ledger_verifier = LedgerStateVerifier::new(ledger.clone().into());
+    ledger_verifier
+        .pots()
+        .has_fee_equals_to(&Value(expected_fee));
+    ledger_verifier
+        .account(alice.as_account_data())
+        .has_value(&Value(alice_funds - expected_fee));
+}
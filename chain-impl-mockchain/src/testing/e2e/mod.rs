@@ -1,5 +1,8 @@
 pub mod fees;
 pub mod management_threshold;
+pub mod multisig;
 pub mod owner_delegation;
+pub mod pool_deposit;
 pub mod rewards;
 pub mod stake_distribution;
+pub mod stake_snapshots;
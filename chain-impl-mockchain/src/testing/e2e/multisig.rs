@@ -0,0 +1,106 @@
+use crate::{
+    fee::{LinearFee, PerExtendedCertificateFee},
+    ledger::Error as LedgerError,
+    multisig::{DeclElement, Declaration, DeclarationError, LedgerError as MultisigLedgerError},
+    testing::{
+        data::Wallet,
+        ledger::ConfigBuilder,
+        scenario::{prepare_scenario, wallet},
+        verifiers::LedgerStateVerifier,
+    },
+    value::Value,
+};
+use chain_addr::Discrimination;
+
+use std::num::NonZeroU64;
+
+fn declaration_for(threshold: u8, owners: &[&Wallet]) -> Declaration {
+    Declaration {
+        threshold,
+        owners: owners
+            .iter()
+            .map(|owner| DeclElement::from_publickey(&owner.public_key()))
+            .collect(),
+    }
+}
+
+#[test]
+pub fn multisig_declaration_is_charged_and_registered() {
+    let certificate_fee = 10;
+    let certificate_multisig_declaration_fee = 50;
+    let alice_funds = 1_000;
+
+    let mut per_extended_certificate_fee = PerExtendedCertificateFee::default();
+    per_extended_certificate_fee
+        .multisig_declaration(NonZeroU64::new(certificate_multisig_declaration_fee));
+
+    let (mut ledger, controller) = prepare_scenario()
+        .with_config(
+            ConfigBuilder::new(0)
+                .with_discrimination(Discrimination::Test)
+                .with_fee(LinearFee::new(1, 1, certificate_fee))
+                .with_per_extended_certificate_fee(per_extended_certificate_fee),
+        )
+        .with_initials(vec![
+            wallet("Alice").with(alice_funds),
+            wallet("Bob").with(1_000),
+            wallet("Clarice").with(1_000),
+        ])
+        .build()
+        .unwrap();
+
+    let mut alice = controller.wallet("Alice").unwrap();
+    let bob = controller.wallet("Bob").unwrap();
+    let clarice = controller.wallet("Clarice").unwrap();
+    let declaration = declaration_for(2, &[&alice, &bob, &clarice]);
+
+    let fragment = controller
+        .fragment_factory()
+        .multisig_declaration(&alice, declaration.clone());
+    ledger.apply_fragment(&fragment, ledger.date()).unwrap();
+    alice.confirm_transaction();
+
+    assert!(ledger.ledger.multisig.exists(&declaration.to_identifier()));
+
+    let expected_fee = 1 + 1 + certificate_multisig_declaration_fee;
+    let ledger_verifier = LedgerStateVerifier::new(ledger.clone().into());
+    ledger_verifier
+        .pots()
+        .has_fee_equals_to(&Value(expected_fee));
+    ledger_verifier
+        .account(alice.as_account_data())
+        .has_value(&Value(alice_funds - expected_fee));
+
+    // the same declaration cannot be registered twice
+    let fragment = controller
+        .fragment_factory()
+        .multisig_declaration(&alice, declaration);
+    assert_eq!(
+        ledger.apply_fragment(&fragment, ledger.date()),
+        Err(LedgerError::Multisig(MultisigLedgerError::AlreadyExist))
+    );
+}
+
+#[test]
+pub fn multisig_declaration_with_invalid_threshold_is_rejected() {
+    let (mut ledger, controller) = prepare_scenario()
+        .with_config(ConfigBuilder::new(0).with_discrimination(Discrimination::Test))
+        .with_initials(vec![wallet("Alice").with(1_000), wallet("Bob").with(1_000)])
+        .build()
+        .unwrap();
+
+    let alice = controller.wallet("Alice").unwrap();
+    let bob = controller.wallet("Bob").unwrap();
+    let declaration = declaration_for(3, &[&alice, &bob]);
+
+    let fragment = controller
+        .fragment_factory()
+        .multisig_declaration(&alice, declaration.clone());
+    assert_eq!(
+        ledger.apply_fragment(&fragment, ledger.date()),
+        Err(LedgerError::Multisig(
+            MultisigLedgerError::DeclarationError(DeclarationError::ThresholdInvalid)
+        ))
+    );
+    assert!(!ledger.ledger.multisig.exists(&declaration.to_identifier()));
+}
@@ -0,0 +1,100 @@
+use crate::{
+    fee::LinearFee,
+    testing::{
+        builders::StakePoolBuilder,
+        ledger::ConfigBuilder,
+        scenario::{prepare_scenario, wallet},
+        verifiers::LedgerStateVerifier,
+    },
+    value::Value,
+};
+use chain_addr::Discrimination;
+
+#[test]
+pub fn pool_deposit_is_refunded_on_retirement() {
+    let pool_deposit = 100;
+    let certificate_fee = 10;
+    let alice_funds = 1_000;
+    // constant (1) + coefficient (1) * inputs (1) + certificate_fee (10) = 12;
+    let expected_fee = 1 + 1 + certificate_fee;
+
+    let (mut ledger, controller) = prepare_scenario()
+        .with_config(
+            ConfigBuilder::new(0)
+                .with_discrimination(Discrimination::Test)
+                .with_fee(LinearFee::new(1, 1, certificate_fee))
+                .with_pool_deposit(Value(pool_deposit)),
+        )
+        .with_initials(vec![wallet("Alice").with(alice_funds)])
+        .build()
+        .unwrap();
+
+    let mut alice = controller.wallet("Alice").unwrap();
+    let stake_pool = StakePoolBuilder::new()
+        .with_owners(vec![alice.public_key()])
+        .build();
+    let total_value = ledger.ledger.get_total_value().unwrap();
+
+    controller
+        .register(&alice, &stake_pool, &mut ledger)
+        .unwrap();
+    alice.confirm_transaction();
+
+    let mut ledger_verifier = LedgerStateVerifier::new(ledger.clone().into());
+    ledger_verifier
+        .info("after register")
+        .pots()
+        .has_fee_equals_to(&Value(expected_fee))
+        .has_deposits_equals_to(&Value(pool_deposit));
+    ledger_verifier
+        .info("after register")
+        .account(alice.as_account_data())
+        .has_value(&Value(alice_funds - expected_fee - pool_deposit));
+    ledger_verifier.total_value_is(&total_value);
+
+    controller
+        .retire(&[&alice], &stake_pool, &mut ledger)
+        .unwrap();
+    alice.confirm_transaction();
+
+    let mut ledger_verifier = LedgerStateVerifier::new(ledger.clone().into());
+    ledger_verifier
+        .info("after retire")
+        .pots()
+        .has_fee_equals_to(&Value(expected_fee * 2))
+        .has_deposits_equals_to(&Value::zero());
+    ledger_verifier
+        .info("after retire")
+        .account(alice.as_account_data())
+        .has_value(&Value(alice_funds - expected_fee * 2));
+    ledger_verifier.total_value_is(&total_value);
+}
+
+#[test]
+pub fn pool_registration_without_deposit_fails() {
+    let (mut ledger, controller) = prepare_scenario()
+        .with_config(
+            ConfigBuilder::new(0)
+                .with_discrimination(Discrimination::Test)
+                .with_fee(LinearFee::new(1, 1, 1))
+                .with_pool_deposit(Value(100)),
+        )
+        .with_initials(vec![wallet("Alice").with(1_000)])
+        .build()
+        .unwrap();
+
+    let alice = controller.wallet("Alice").unwrap();
+    let stake_pool = StakePoolBuilder::new()
+        .with_owners(vec![alice.public_key()])
+        .build();
+
+    let fragment = controller
+        .fragment_factory()
+        .with_pool_deposit(Value::zero())
+        .stake_pool_registration(&alice, &stake_pool);
+    assert!(ledger.apply_fragment(&fragment, ledger.date()).is_err());
+
+    LedgerStateVerifier::new(ledger.into())
+        .pots()
+        .has_deposits_equals_to(&Value::zero());
+}
@@ -2,17 +2,23 @@ use crate::{
     certificate::PoolId,
     config::RewardParams,
     fee::LinearFee,
-    rewards::Ratio,
+    key::Hash,
+    milli::Milli,
+    multisig::{DeclElement, Declaration},
+    rewards::{Ratio, TaxType},
     testing::{
         builders::StakePoolBuilder,
+        data::StakePool,
         ledger::{ConfigBuilder, TestLedger},
         scenario::{prepare_scenario, stake_pool, wallet},
         verifiers::LedgerStateVerifier,
     },
+    transaction::AccountIdentifier,
     value::Value,
 };
 
 use std::num::{NonZeroU32, NonZeroU64};
+use std::sync::Arc;
 
 pub mod tax;
 
@@ -280,6 +286,440 @@ pub fn rewards_reward_account() {
         .has_last_reward(&Value(9));
 }
 
+fn multisig_declaration() -> Declaration {
+    Declaration {
+        threshold: 1,
+        owners: vec![
+            DeclElement::Owner(Hash::hash_bytes(&[1])),
+            DeclElement::Owner(Hash::hash_bytes(&[2])),
+        ],
+    }
+}
+
+// there is no fragment to register a pool with a multisig reward account yet,
+// so the registration is patched directly in the ledger
+fn set_multisig_reward_account(
+    ledger: &mut TestLedger,
+    stake_pool: &StakePool,
+    declaration: &Declaration,
+) {
+    let mut state = ledger
+        .ledger
+        .delegation
+        .stake_pool_get_state(&stake_pool.id())
+        .unwrap()
+        .clone();
+    Arc::make_mut(&mut state.registration).reward_account =
+        Some(AccountIdentifier::Multi(declaration.to_identifier()));
+    ledger
+        .ledger
+        .delegation
+        .stake_pool_set_state(&stake_pool.id(), state)
+        .unwrap();
+}
+
+#[test]
+pub fn rewards_multisig_reward_account() {
+    let (mut ledger, controller) = prepare_scenario()
+        .with_config(
+            ConfigBuilder::new(0)
+                .with_rewards(Value(1000))
+                .with_treasury(Value(0))
+                .with_rewards_params(RewardParams::Linear {
+                    constant: 100,
+                    ratio: Ratio {
+                        numerator: 1,
+                        denominator: NonZeroU64::new(1).unwrap(),
+                    },
+                    epoch_start: 0,
+                    epoch_rate: NonZeroU32::new(1).unwrap(),
+                }),
+        )
+        .with_initials(vec![wallet("Alice").with(1_000).owns("stake_pool")])
+        .with_stake_pools(vec![stake_pool("stake_pool").tax_ratio(1, 10)])
+        .build()
+        .unwrap();
+    let stake_pool = controller.stake_pool("stake_pool").unwrap();
+
+    let declaration = multisig_declaration();
+    let identifier = declaration.to_identifier();
+    ledger.ledger.multisig = ledger.ledger.multisig.add_account(&declaration).unwrap();
+    set_multisig_reward_account(&mut ledger, &stake_pool, &declaration);
+
+    assert!(ledger.produce_empty_block(&stake_pool).is_ok());
+    ledger.distribute_rewards().unwrap();
+
+    LedgerStateVerifier::new(ledger.clone().into())
+        .info("after rewards distribution to multisig reward account")
+        .pots()
+        .has_fee_equals_to(&Value::zero())
+        .and()
+        .has_treasury_equals_to(&Value(90))
+        .and()
+        .has_remaining_rewards_equals_to(&Value(901));
+
+    let state = ledger.ledger.multisig.get_state(&identifier).unwrap();
+    assert_eq!(state.get_value(), Value(9));
+    assert_eq!(state.last_rewards.reward, Value(9));
+}
+
+#[test]
+pub fn rewards_goes_to_treasury_if_multisig_reward_account_is_not_declared() {
+    let (mut ledger, controller) = prepare_scenario()
+        .with_config(
+            ConfigBuilder::new(0)
+                .with_rewards(Value(1000))
+                .with_treasury(Value(0))
+                .with_rewards_params(RewardParams::Linear {
+                    constant: 100,
+                    ratio: Ratio {
+                        numerator: 1,
+                        denominator: NonZeroU64::new(1).unwrap(),
+                    },
+                    epoch_start: 0,
+                    epoch_rate: NonZeroU32::new(1).unwrap(),
+                }),
+        )
+        .with_initials(vec![wallet("Alice").with(1_000).owns("stake_pool")])
+        .with_stake_pools(vec![stake_pool("stake_pool").tax_ratio(1, 10)])
+        .build()
+        .unwrap();
+    let stake_pool = controller.stake_pool("stake_pool").unwrap();
+
+    set_multisig_reward_account(&mut ledger, &stake_pool, &multisig_declaration());
+
+    assert!(ledger.produce_empty_block(&stake_pool).is_ok());
+    ledger.distribute_rewards().unwrap();
+
+    LedgerStateVerifier::new(ledger.clone().into())
+        .info("after rewards distribution to undeclared multisig reward account")
+        .multisigs_count_is_zero()
+        .pots()
+        .has_fee_equals_to(&Value::zero())
+        .and()
+        .has_treasury_equals_to(&Value(99))
+        .and()
+        .has_remaining_rewards_equals_to(&Value(901));
+}
+
+#[test]
+pub fn rewards_are_capped_for_saturated_pools() {
+    let (mut ledger, controller) = prepare_scenario()
+        .with_config(
+            ConfigBuilder::new(0)
+                .with_rewards(Value(1000))
+                .with_treasury(Value(0))
+                .with_pool_saturation_target(NonZeroU32::new(2).unwrap())
+                .with_rewards_params(RewardParams::Linear {
+                    constant: 100,
+                    ratio: Ratio::zero(),
+                    epoch_start: 0,
+                    epoch_rate: NonZeroU32::new(1).unwrap(),
+                }),
+        )
+        .with_initials(vec![
+            wallet("Alice")
+                .with(3_000)
+                .owns_and_delegates_to("alice_stake_pool"),
+            wallet("Bob")
+                .with(1_000)
+                .owns_and_delegates_to("bob_stake_pool"),
+        ])
+        .with_stake_pools(vec![
+            stake_pool("alice_stake_pool").tax_ratio(1, 1),
+            stake_pool("bob_stake_pool").tax_ratio(1, 1),
+        ])
+        .build()
+        .unwrap();
+
+    let alice = controller.wallet("Alice").unwrap();
+    let bob = controller.wallet("Bob").unwrap();
+    let alice_stake_pool = controller.stake_pool("alice_stake_pool").unwrap();
+    let bob_stake_pool = controller.stake_pool("bob_stake_pool").unwrap();
+
+    assert!(ledger.produce_empty_block(&alice_stake_pool).is_ok());
+    assert!(ledger.produce_empty_block(&bob_stake_pool).is_ok());
+    ledger.distribute_rewards().unwrap();
+
+    // alice's pool controls 3/4 of the stake, above the 1/2 saturation point,
+    // so it only gets 2/3 of its 50 reward, the rest going to treasury
+    let mut ledger_verifier = LedgerStateVerifier::new(ledger.clone().into());
+    ledger_verifier
+        .info("after rewards distribution with saturation")
+        .pots()
+        .has_treasury_equals_to(&Value(17))
+        .and()
+        .has_remaining_rewards_equals_to(&Value(900));
+    ledger_verifier
+        .account(alice.as_account_data())
+        .has_value(&Value(3_033));
+    ledger_verifier
+        .account(bob.as_account_data())
+        .has_value(&Value(1_050));
+}
+
+#[test]
+pub fn rewards_are_weighted_by_pool_performance() {
+    let (mut ledger, controller) = prepare_scenario()
+        .with_config(
+            ConfigBuilder::new(0)
+                .with_rewards(Value(1000))
+                .with_treasury(Value(0))
+                .with_slots_per_epoch(10)
+                .with_active_slots_coeff(Milli::from_millis(500))
+                .with_pool_performance_rewards(true)
+                .with_rewards_params(RewardParams::Linear {
+                    constant: 100,
+                    ratio: Ratio::zero(),
+                    epoch_start: 0,
+                    epoch_rate: NonZeroU32::new(1).unwrap(),
+                }),
+        )
+        .with_initials(vec![
+            wallet("Alice")
+                .with(3_000)
+                .owns_and_delegates_to("alice_stake_pool"),
+            wallet("Bob")
+                .with(1_000)
+                .owns_and_delegates_to("bob_stake_pool"),
+        ])
+        .with_stake_pools(vec![
+            stake_pool("alice_stake_pool").tax_ratio(1, 1),
+            stake_pool("bob_stake_pool").tax_ratio(1, 1),
+        ])
+        .build()
+        .unwrap();
+
+    let alice = controller.wallet("Alice").unwrap();
+    let bob = controller.wallet("Bob").unwrap();
+    let alice_stake_pool = controller.stake_pool("alice_stake_pool").unwrap();
+    let bob_stake_pool = controller.stake_pool("bob_stake_pool").unwrap();
+
+    assert!(ledger.produce_empty_block(&alice_stake_pool).is_ok());
+    assert!(ledger.produce_empty_block(&alice_stake_pool).is_ok());
+    assert!(ledger.produce_empty_block(&bob_stake_pool).is_ok());
+    ledger.distribute_rewards().unwrap();
+
+    // 5 blocks expected in the epoch: alice's pool was expected to produce
+    // 3.75 blocks and bob's pool 1.25, both are underperforming.
+    let mut ledger_verifier = LedgerStateVerifier::new(ledger.clone().into());
+    ledger_verifier
+        .info("after performance weighted rewards distribution")
+        .pots()
+        .has_treasury_equals_to(&Value(40))
+        .and()
+        .has_remaining_rewards_equals_to(&Value(900));
+    ledger_verifier
+        .account(alice.as_account_data())
+        .has_value(&Value(3_040));
+    ledger_verifier
+        .account(bob.as_account_data())
+        .has_value(&Value(1_020));
+}
+
+#[test]
+pub fn rewards_history_is_recorded() {
+    let (mut ledger, controller) = prepare_scenario()
+        .with_config(
+            ConfigBuilder::new(0)
+                .with_rewards(Value(1000))
+                .with_treasury(Value(0))
+                .with_rewards_history_retention(2)
+                .with_rewards_params(RewardParams::Linear {
+                    constant: 100,
+                    ratio: Ratio {
+                        numerator: 1,
+                        denominator: NonZeroU64::new(1).unwrap(),
+                    },
+                    epoch_start: 0,
+                    epoch_rate: NonZeroU32::new(1).unwrap(),
+                }),
+        )
+        .with_initials(vec![wallet("Alice").with(1_000).owns("stake_pool")])
+        .with_stake_pools(vec![stake_pool("stake_pool")
+            .with_reward_account(true)
+            .tax_ratio(1, 10)])
+        .build()
+        .unwrap();
+    let stake_pool = controller.stake_pool("stake_pool").unwrap();
+    let reward_account = stake_pool.reward_account().unwrap().to_id();
+
+    for _ in 0..3 {
+        assert!(ledger.produce_empty_block(&stake_pool).is_ok());
+        ledger.distribute_rewards().unwrap();
+        ledger.ledger.date.epoch += 1;
+    }
+
+    let rewards_history = ledger.ledger.rewards_history();
+    assert_eq!(
+        rewards_history.account(&reward_account),
+        vec![(2, Value(9)), (3, Value(9))]
+    );
+    assert_eq!(
+        rewards_history.stake_pool(&stake_pool.id()),
+        vec![(2, (Value(9), Value(89))), (3, (Value(9), Value(88)))]
+    );
+    assert!(rewards_history.epoch(1).is_none());
+}
+
+#[test]
+pub fn rewards_preview_matches_distribution() {
+    let (mut ledger, controller) = prepare_scenario()
+        .with_config(
+            ConfigBuilder::new(0)
+                .with_rewards(Value(1000))
+                .with_treasury(Value(0))
+                .with_rewards_params(RewardParams::Linear {
+                    constant: 100,
+                    ratio: Ratio {
+                        numerator: 1,
+                        denominator: NonZeroU64::new(1).unwrap(),
+                    },
+                    epoch_start: 0,
+                    epoch_rate: NonZeroU32::new(1).unwrap(),
+                }),
+        )
+        .with_initials(vec![wallet("Alice").with(1_000).owns("stake_pool")])
+        .with_stake_pools(vec![stake_pool("stake_pool")
+            .with_reward_account(true)
+            .tax_ratio(1, 10)])
+        .build()
+        .unwrap();
+    let stake_pool = controller.stake_pool("stake_pool").unwrap();
+    let reward_account = stake_pool.reward_account().unwrap().to_id();
+
+    assert!(ledger.produce_empty_block(&stake_pool).is_ok());
+    let before = ledger.ledger.clone();
+    let distribution = before.get_stake_distribution();
+    let ledger_params = before.get_ledger_parameters();
+
+    let preview = before
+        .preview_rewards(&distribution, &ledger_params)
+        .unwrap();
+    assert_eq!(before, ledger.ledger);
+
+    ledger.distribute_rewards().unwrap();
+
+    assert_eq!(preview.drawn, Value(99));
+    assert_eq!(
+        preview.stake_pools.get(&stake_pool.id()),
+        Some(&(Value(9), Value(90)))
+    );
+    assert_eq!(preview.accounts.get(&reward_account), Some(&Value(9)));
+    LedgerStateVerifier::new(ledger.clone().into())
+        .info("after rewards distribution")
+        .pots()
+        .has_remaining_rewards_equals_to(&Value(901));
+}
+
+#[test]
+pub fn rewards_preview_with_different_pool_tax() {
+    let (mut ledger, controller) = prepare_scenario()
+        .with_config(
+            ConfigBuilder::new(0)
+                .with_rewards(Value(1000))
+                .with_treasury(Value(0))
+                .with_rewards_params(RewardParams::Linear {
+                    constant: 100,
+                    ratio: Ratio::zero(),
+                    epoch_start: 0,
+                    epoch_rate: NonZeroU32::new(1).unwrap(),
+                }),
+        )
+        .with_initials(vec![wallet("Alice").with(1_000).owns("stake_pool")])
+        .with_stake_pools(vec![stake_pool("stake_pool")
+            .with_reward_account(true)
+            .tax_ratio(1, 10)])
+        .build()
+        .unwrap();
+    let stake_pool = controller.stake_pool("stake_pool").unwrap();
+
+    assert!(ledger.produce_empty_block(&stake_pool).is_ok());
+    let mut distribution = ledger.ledger.get_stake_distribution();
+    assert!(distribution.set_pool_tax(
+        &stake_pool.id(),
+        TaxType {
+            fixed: Value(10),
+            ratio: Ratio::zero(),
+            max_limit: None,
+        }
+    ));
+
+    let preview = ledger
+        .ledger
+        .preview_rewards(&distribution, &ledger.ledger.get_ledger_parameters())
+        .unwrap();
+    assert_eq!(
+        preview.stake_pools.get(&stake_pool.id()),
+        Some(&(Value(10), Value(90)))
+    );
+}
+
+#[test]
+pub fn rewards_projection_follows_compounding() {
+    let (mut ledger, controller) = prepare_scenario()
+        .with_config(
+            ConfigBuilder::new(0)
+                .with_rewards(Value(250))
+                .with_treasury(Value(0))
+                .with_rewards_params(RewardParams::Linear {
+                    constant: 100,
+                    ratio: Ratio {
+                        numerator: 10,
+                        denominator: NonZeroU64::new(1).unwrap(),
+                    },
+                    epoch_start: 0,
+                    epoch_rate: NonZeroU32::new(1).unwrap(),
+                }),
+        )
+        .with_initials(vec![wallet("Alice").with(1_000).owns("stake_pool")])
+        .with_stake_pools(vec![stake_pool("stake_pool").with_reward_account(true)])
+        .build()
+        .unwrap();
+    let stake_pool = controller.stake_pool("stake_pool").unwrap();
+
+    assert!(ledger.produce_empty_block(&stake_pool).is_ok());
+    let projection = ledger
+        .ledger
+        .project_rewards(
+            &ledger.ledger.get_stake_distribution(),
+            &ledger.ledger.get_ledger_parameters(),
+            4,
+        )
+        .unwrap();
+
+    // the last epoch is bounded by what remains in the rewards pot
+    let drawn: Vec<_> = projection
+        .iter()
+        .map(|(epoch, info)| (*epoch, info.drawn))
+        .collect();
+    assert_eq!(
+        drawn,
+        vec![
+            (1, Value(90)),
+            (2, Value(80)),
+            (3, Value(70)),
+            (4, Value(10))
+        ]
+    );
+}
+
+#[test]
+pub fn rewards_history_is_disabled_by_default() {
+    let (mut ledger, controller) = prepare_scenario()
+        .with_config(ConfigBuilder::new(0).with_rewards(Value(1000)))
+        .with_initials(vec![wallet("Alice").with(1_000).owns("stake_pool")])
+        .with_stake_pools(vec![stake_pool("stake_pool").with_reward_account(true)])
+        .build()
+        .unwrap();
+    let stake_pool = controller.stake_pool("stake_pool").unwrap();
+
+    assert!(ledger.produce_empty_block(&stake_pool).is_ok());
+    ledger.distribute_rewards().unwrap();
+
+    assert_eq!(ledger.ledger.rewards_history().iter().count(), 0);
+}
+
 #[test]
 pub fn rewards_goes_to_treasury_if_stake_pool_is_retired() {
     let (mut ledger, controller) = prepare_scenario()
@@ -54,6 +54,8 @@ pub fn stake_distribution_to_many_stake_pools() {
 
     LedgerStateVerifier::new(ledger.clone().into())
         .info("after delegation to many stake pools")
+        .stake_distribution_is_consistent()
+        .and()
         .distribution()
         .pools_distribution_is(expected_distribution);
 }
@@ -93,6 +95,8 @@ pub fn stake_distribution_changes_after_rewards_are_collected() {
 
     LedgerStateVerifier::new(ledger.clone().into())
         .info("before rewards collection")
+        .stake_distribution_is_consistent()
+        .and()
         .distribution()
         .unassigned_is(Stake::from_value(Value(2000)))
         .pools_distribution_is(vec![(alice_stake_pool.id(), Value(1000))]);
@@ -102,6 +106,8 @@ pub fn stake_distribution_changes_after_rewards_are_collected() {
 
     LedgerStateVerifier::new(ledger.clone().into())
         .info("after rewards collection")
+        .stake_distribution_is_consistent()
+        .and()
         .distribution()
         .unassigned_is(Stake::from_value(Value(2000)))
         .pools_distribution_is(vec![(alice_stake_pool.id(), Value(1009))]);
@@ -0,0 +1,98 @@
+use crate::{
+    config::RewardParams,
+    date::BlockDate,
+    leadership::genesis::LeadershipData,
+    ledger::RewardsInfoParameters,
+    rewards::Ratio,
+    testing::{
+        builders::GenesisPraosBlockBuilder,
+        ledger::ConfigBuilder,
+        scenario::{prepare_scenario, stake_pool, wallet},
+    },
+    value::Value,
+};
+use std::num::{NonZeroU32, NonZeroU64};
+
+#[test]
+pub fn leader_election_and_rewards_use_stake_of_two_epochs_back() {
+    let (mut ledger, controller) = prepare_scenario()
+        .with_config(
+            ConfigBuilder::new(0)
+                .with_rewards(Value(100))
+                .with_rewards_params(RewardParams::Linear {
+                    constant: 10,
+                    ratio: Ratio {
+                        numerator: 1,
+                        denominator: NonZeroU64::new(1).unwrap(),
+                    },
+                    epoch_start: 0,
+                    epoch_rate: NonZeroU32::new(1).unwrap(),
+                }),
+        )
+        .with_initials(vec![
+            wallet("Alice")
+                .with(1_000)
+                .owns_and_delegates_to("stake_pool"),
+            wallet("Bob").with(1_000),
+        ])
+        .with_stake_pools(vec![stake_pool("stake_pool").no_tax()])
+        .build()
+        .unwrap();
+
+    let stake_pool = controller.stake_pool("stake_pool").unwrap();
+    let alice = controller.wallet("Alice").unwrap();
+    let bob = controller.wallet("Bob").unwrap();
+
+    let block0_distribution = ledger.ledger.get_stake_distribution();
+    controller
+        .delegates(&bob, &stake_pool, &mut ledger)
+        .unwrap();
+    let epoch0_distribution = ledger.ledger.get_stake_distribution();
+    assert_ne!(block0_distribution, epoch0_distribution);
+
+    // stake moved during epoch 0 is only used from epoch 2
+    for epoch in 0..2 {
+        assert_eq!(
+            ledger.ledger.epoch_stake_distribution(epoch),
+            block0_distribution
+        );
+    }
+
+    let block = GenesisPraosBlockBuilder::new()
+        .with_date(BlockDate {
+            epoch: 1,
+            slot_id: 0,
+        })
+        .with_chain_length(ledger.chain_length())
+        .with_parent_id(ledger.block0_hash)
+        .build(&stake_pool, ledger.era());
+    ledger.apply_block(block).unwrap();
+
+    assert_eq!(
+        ledger.ledger.epoch_stake_distribution(1),
+        block0_distribution
+    );
+    assert_eq!(
+        ledger.ledger.epoch_stake_distribution(2),
+        epoch0_distribution
+    );
+    assert_eq!(
+        LeadershipData::new(2, &ledger.ledger).distribution(),
+        &epoch0_distribution
+    );
+
+    // bob delegated after the stake of epoch 1 was captured
+    let (_, rewards_info) = ledger
+        .ledger
+        .distribute_epoch_rewards(
+            &ledger.ledger.get_ledger_parameters(),
+            RewardsInfoParameters::report_all(),
+        )
+        .unwrap();
+    assert!(rewards_info
+        .accounts
+        .contains_key(&alice.as_account_data().to_id()));
+    assert!(!rewards_info
+        .accounts
+        .contains_key(&bob.as_account_data().to_id()));
+}
@@ -7,7 +7,7 @@ use crate::{
     chaintypes::{ChainLength, ConsensusType, ConsensusVersion, HeaderId},
     config::{Block0Date, ConfigParam, RewardParams},
     date::BlockDate,
-    fee::{LinearFee, PerCertificateFee},
+    fee::{LinearFee, PerCertificateFee, PerExtendedCertificateFee},
     fragment::{config::ConfigParams, Fragment, FragmentId},
     key::BftLeaderId,
     leadership::genesis::LeadershipData,
@@ -41,6 +41,7 @@ pub struct ConfigBuilder {
     discrimination: Discrimination,
     linear_fee: Option<LinearFee>,
     per_certificate_fee: Option<PerCertificateFee>,
+    per_extended_certificate_fee: Option<PerExtendedCertificateFee>,
     leaders: Vec<BftLeaderId>,
     seed: u64,
     rewards: Value,
@@ -51,6 +52,16 @@ pub struct ConfigBuilder {
     kes_update_speed: u32,
     block0_date: Block0Date,
     consensus_version: ConsensusVersion,
+    rewards_history_retention: Option<u32>,
+    pool_saturation_target: Option<NonZeroU32>,
+    pool_performance_rewards: bool,
+    stake_voting_quorum: Option<Ratio>,
+    stake_voting_threshold: Option<Ratio>,
+    metadata_max_size: Option<u32>,
+    per_metadata_byte_fee: Option<u64>,
+    per_fragment_byte_fee: Option<u64>,
+    pool_deposit: Option<Value>,
+    bft_slots_ratio: Option<Milli>,
 }
 
 impl ConfigBuilder {
@@ -63,6 +74,7 @@ impl ConfigBuilder {
             leaders: Vec::new(),
             linear_fee: None,
             per_certificate_fee: None,
+            per_extended_certificate_fee: None,
             seed,
             rewards: Value(1_000_000),
             reward_params: RewardParams::Linear {
@@ -80,6 +92,16 @@ impl ConfigBuilder {
             kes_update_speed: 3600 * 12,
             block0_date: Block0Date(0),
             consensus_version: ConsensusVersion::Bft,
+            rewards_history_retention: None,
+            pool_saturation_target: None,
+            pool_performance_rewards: false,
+            stake_voting_quorum: None,
+            stake_voting_threshold: None,
+            metadata_max_size: None,
+            per_metadata_byte_fee: None,
+            per_fragment_byte_fee: None,
+            pool_deposit: None,
+            bft_slots_ratio: None,
         }
     }
 
@@ -128,6 +150,14 @@ impl ConfigBuilder {
         self
     }
 
+    pub fn with_per_extended_certificate_fee(
+        mut self,
+        per_extended_certificate_fee: PerExtendedCertificateFee,
+    ) -> Self {
+        self.per_extended_certificate_fee = Some(per_extended_certificate_fee);
+        self
+    }
+
     pub fn with_slots_per_epoch(mut self, slots_per_epoch: u32) -> Self {
         self.slots_per_epoch = slots_per_epoch;
         self
@@ -158,6 +188,56 @@ impl ConfigBuilder {
         self
     }
 
+    pub fn with_rewards_history_retention(mut self, rewards_history_retention: u32) -> Self {
+        self.rewards_history_retention = Some(rewards_history_retention);
+        self
+    }
+
+    pub fn with_pool_saturation_target(mut self, pool_saturation_target: NonZeroU32) -> Self {
+        self.pool_saturation_target = Some(pool_saturation_target);
+        self
+    }
+
+    pub fn with_pool_performance_rewards(mut self, pool_performance_rewards: bool) -> Self {
+        self.pool_performance_rewards = pool_performance_rewards;
+        self
+    }
+
+    pub fn with_stake_voting_quorum(mut self, stake_voting_quorum: Ratio) -> Self {
+        self.stake_voting_quorum = Some(stake_voting_quorum);
+        self
+    }
+
+    pub fn with_stake_voting_threshold(mut self, stake_voting_threshold: Ratio) -> Self {
+        self.stake_voting_threshold = Some(stake_voting_threshold);
+        self
+    }
+
+    pub fn with_metadata_max_size(mut self, metadata_max_size: u32) -> Self {
+        self.metadata_max_size = Some(metadata_max_size);
+        self
+    }
+
+    pub fn with_per_metadata_byte_fee(mut self, per_metadata_byte_fee: u64) -> Self {
+        self.per_metadata_byte_fee = Some(per_metadata_byte_fee);
+        self
+    }
+
+    pub fn with_per_fragment_byte_fee(mut self, per_fragment_byte_fee: u64) -> Self {
+        self.per_fragment_byte_fee = Some(per_fragment_byte_fee);
+        self
+    }
+
+    pub fn with_pool_deposit(mut self, pool_deposit: Value) -> Self {
+        self.pool_deposit = Some(pool_deposit);
+        self
+    }
+
+    pub fn with_bft_slots_ratio(mut self, bft_slots_ratio: Milli) -> Self {
+        self.bft_slots_ratio = Some(bft_slots_ratio);
+        self
+    }
+
     fn create_single_bft_leader() -> BftLeaderId {
         let leader_prv_key: SecretKey<Ed25519Extended> = SecretKey::generate(rand_core::OsRng);
         let leader_pub_key = leader_prv_key.to_public();
@@ -199,6 +279,56 @@ impl ConfigBuilder {
             ));
         }
 
+        if let Some(per_extended_certificate_fee) = self.per_extended_certificate_fee {
+            ie.push(ConfigParam::PerExtendedCertificateFees(
+                per_extended_certificate_fee,
+            ));
+        }
+
+        if let Some(rewards_history_retention) = self.rewards_history_retention {
+            ie.push(ConfigParam::RewardsHistoryRetention(
+                rewards_history_retention,
+            ));
+        }
+
+        if let Some(pool_saturation_target) = self.pool_saturation_target {
+            ie.push(ConfigParam::PoolRewardSaturation(Some(
+                pool_saturation_target,
+            )));
+        }
+
+        if self.pool_performance_rewards {
+            ie.push(ConfigParam::PoolRewardPerformance(true));
+        }
+
+        if let Some(stake_voting_quorum) = self.stake_voting_quorum {
+            ie.push(ConfigParam::StakeVotingQuorum(stake_voting_quorum));
+        }
+
+        if let Some(stake_voting_threshold) = self.stake_voting_threshold {
+            ie.push(ConfigParam::StakeVotingThreshold(stake_voting_threshold));
+        }
+
+        if let Some(metadata_max_size) = self.metadata_max_size {
+            ie.push(ConfigParam::MetadataMaxSize(metadata_max_size));
+        }
+
+        if let Some(per_metadata_byte_fee) = self.per_metadata_byte_fee {
+            ie.push(ConfigParam::PerMetadataByteFee(per_metadata_byte_fee));
+        }
+
+        if let Some(per_fragment_byte_fee) = self.per_fragment_byte_fee {
+            ie.push(ConfigParam::PerFragmentByteFee(per_fragment_byte_fee));
+        }
+
+        if let Some(pool_deposit) = self.pool_deposit {
+            ie.push(ConfigParam::PoolDeposit(pool_deposit));
+        }
+
+        if let Some(bft_slots_ratio) = self.bft_slots_ratio {
+            ie.push(ConfigParam::BftSlotsRatio(bft_slots_ratio));
+        }
+
         ie.push(ConfigParam::Block0Date(self.block0_date));
         ie.push(ConfigParam::SlotDuration(self.slot_duration));
         ie.push(ConfigParam::ConsensusGenesisPraosActiveSlotsCoeff(
@@ -415,6 +545,7 @@ impl TestLedger {
                     Ok((ledger, _)) => {
                         // TODO more bookkeeping for accounts and utxos
                         self.ledger = ledger;
+                        self.assert_stake_distribution_consistent();
                         Ok(())
                     }
                 }
@@ -428,6 +559,7 @@ impl TestLedger {
             .ledger
             .clone()
             .apply_fragment(&self.parameters, fragment, date)?;
+        self.assert_stake_distribution_consistent();
         Ok(())
     }
 
@@ -438,9 +570,21 @@ impl TestLedger {
             &block.contents,
             &header_meta,
         )?;
+        self.assert_stake_distribution_consistent();
         Ok(())
     }
 
+    /// the ledger maintains the stake distribution along the changes, make
+    /// sure it never drifts from a full recompute
+    fn assert_stake_distribution_consistent(&self) {
+        if let Err(mismatch) = self.ledger.check_stake_distribution() {
+            panic!(
+                "maintained stake distribution {:?} differs from recomputed {:?}",
+                mismatch.maintained, mismatch.recomputed
+            );
+        }
+    }
+
     pub fn total_funds(&self) -> Value {
         self.ledger
             .get_total_value()
@@ -525,6 +669,7 @@ impl TestLedger {
             Err(err) => Err(err),
             Ok((ledger, _)) => {
                 self.ledger = ledger;
+                self.assert_stake_distribution_consistent();
                 Ok(())
             }
         }
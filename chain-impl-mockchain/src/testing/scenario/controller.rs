@@ -6,6 +6,7 @@ use crate::{
         data::{StakePool, Wallet},
         ledger::TestLedger,
     },
+    value::Value,
 };
 
 use super::{
@@ -80,6 +81,12 @@ impl Controller {
             })
     }
 
+    /// Pay the given deposit when registering stake pools
+    pub fn with_pool_deposit(mut self, pool_deposit: Value) -> Self {
+        self.fragment_factory = self.fragment_factory.with_pool_deposit(pool_deposit);
+        self
+    }
+
     pub fn fragment_factory(&self) -> FragmentFactory {
         self.fragment_factory.clone()
     }
@@ -1,10 +1,11 @@
 use crate::{
     accounting::account::{DelegationRatio, DelegationType},
-    certificate::Certificate,
     certificate::PoolId,
+    certificate::{Certificate, MultisigDeclaration},
     fee::LinearFee,
     fragment::Fragment,
     key::Hash,
+    multisig::Declaration,
     testing::{
         builders::{
             build_no_stake_delegation, build_owner_stake_delegation,
@@ -22,15 +23,26 @@ use crate::{
 pub struct FragmentFactory {
     pub block0_hash: Hash,
     pub fee: LinearFee,
+    pub pool_deposit: Value,
 }
 
 impl FragmentFactory {
     pub fn from_ledger(test_ledger: &TestLedger) -> Self {
         Self::new(test_ledger.block0_hash.clone(), test_ledger.fee().clone())
+            .with_pool_deposit(test_ledger.ledger.settings.pool_deposit)
     }
 
     pub fn new(block0_hash: Hash, fee: LinearFee) -> Self {
-        Self { block0_hash, fee }
+        Self {
+            block0_hash,
+            fee,
+            pool_deposit: Value::zero(),
+        }
+    }
+
+    pub fn with_pool_deposit(mut self, pool_deposit: Value) -> Self {
+        self.pool_deposit = pool_deposit;
+        self
     }
 
     pub fn transaction(
@@ -98,8 +110,14 @@ impl FragmentFactory {
         self.transaction_with_cert(&owners, certificate)
     }
 
+    pub fn multisig_declaration(&self, funder: &Wallet, declaration: Declaration) -> Fragment {
+        let certificate = Certificate::MultisigDeclaration(MultisigDeclaration { declaration });
+        self.transaction_with_cert(&[funder], certificate)
+    }
+
     fn transaction_with_cert(&self, wallets: &[&Wallet], certificate: Certificate) -> Fragment {
         TestTxCertBuilder::new(self.block0_hash.clone(), self.fee.clone())
+            .with_pool_deposit(self.pool_deposit)
             .make_transaction(wallets, &certificate)
     }
 }
@@ -102,10 +102,11 @@ impl ScenarioBuilder {
             .expect("cannot build test ledger");
         let block0_hash = test_ledger.block0_hash.clone();
         let fee = test_ledger.fee().clone();
+        let pool_deposit = test_ledger.ledger.settings.pool_deposit;
 
         Ok((
             test_ledger,
-            Controller::new(block0_hash, fee, wallets, stake_pools),
+            Controller::new(block0_hash, fee, wallets, stake_pools).with_pool_deposit(pool_deposit),
         ))
     }
 
@@ -118,6 +118,16 @@ impl LedgerStateVerifier {
         DistributionVerifier::new(self.ledger.get_stake_distribution(), self.info.clone())
     }
 
+    pub fn stake_distribution_is_consistent(&self) -> &Self {
+        if let Err(mismatch) = self.ledger.check_stake_distribution() {
+            panic!(
+                "Maintained stake distribution {:?} differs from recomputed {:?} {}",
+                mismatch.maintained, mismatch.recomputed, self.info
+            );
+        }
+        self
+    }
+
     pub fn stake_pools(&self) -> StakePoolsVerifier {
         StakePoolsVerifier::new(self.ledger.delegation.clone(), self.info.clone())
     }
@@ -275,6 +285,15 @@ impl PotsVerifier {
         );
         self
     }
+
+    pub fn has_deposits_equals_to(&self, value: &Value) -> &Self {
+        assert_eq!(
+            self.pots.deposits, *value,
+            "incorrect deposits value {}",
+            self.info
+        );
+        self
+    }
 }
 
 pub struct StakePoolsVerifier {
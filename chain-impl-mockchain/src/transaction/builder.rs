@@ -4,6 +4,7 @@ use super::transaction::{
     Transaction, TransactionAuthData, TransactionBindingAuthData, TransactionStruct,
 };
 use super::transfer::Output;
+use super::validity::ValidityInterval;
 use super::witness::Witness;
 use chain_addr::Address;
 use std::marker::PhantomData;
@@ -53,6 +54,7 @@ impl TxBuilder {
                 outputs: 0,
                 witnesses: 0,
                 payload_auth: 0,
+                validity: ValidityInterval::unbounded(),
             },
             phantom: PhantomData,
         }
@@ -85,6 +87,15 @@ impl TxBuilderState<SetPayload> {
 }
 
 impl<P> TxBuilderState<SetIOs<P>> {
+    /// Set the interval of block dates in which this transaction is valid.
+    ///
+    /// Without it, the transaction never expires and keeps the original
+    /// encoding, otherwise its fragment tag is flagged (see `Fragment::to_raw`).
+    pub fn set_validity(mut self, validity: ValidityInterval) -> Self {
+        self.tstruct.validity = validity;
+        self
+    }
+
     /// Set the inputs and outputs of this transaction
     ///
     /// This cannot accept more than 255 inputs, 255 outputs, since
@@ -105,6 +116,8 @@ impl<P> TxBuilderState<SetIOs<P>> {
 
         self.data.push(nb_inputs);
         self.data.push(nb_outputs);
+        self.data
+            .extend_from_slice(&self.tstruct.validity.to_bytes());
 
         self.tstruct.nb_inputs = nb_inputs;
         self.tstruct.nb_outputs = nb_outputs;
@@ -1,14 +1,30 @@
-use super::{Balance, Input, Output, Payload, PayloadSlice};
+use super::input::INPUT_SIZE;
+use super::{Balance, Input, Output, Payload, PayloadSlice, ValidityInterval};
+use crate::certificate::CertificateSlice;
 use crate::fee::FeeAlgorithm;
+use crate::fragment::FRAGMENT_HEADER_SIZE;
 use crate::value::{Value, ValueError};
-use chain_addr::Address;
+use chain_addr::{Address, ADDR_SIZE_GROUP};
 use std::error;
 use std::fmt;
 
+/// Size of the number of inputs and outputs
+const IOS_HEADER_SIZE: usize = 2;
+/// Size of an utxo or account witness
+const WITNESS_SIZE: usize = 1 + 64;
+/// Size of the signature of a stake delegation by its account
+const ACCOUNT_BINDING_SIGNATURE_SIZE: usize = 1 + 64;
+/// Size of the number of signatures of the pool owners, and of each indexed signature
+const POOL_OWNERS_SIGNATURES_HEADER_SIZE: usize = 1;
+const POOL_OWNER_SIGNATURE_SIZE: usize = 1 + 64;
+/// Size of an output to a group address, the largest of the common kinds
+const OUTPUT_PLACEHOLDER_SIZE: usize = ADDR_SIZE_GROUP + 8;
+
 /// Inputs & Outputs for a transaction being built
 pub struct InputOutputBuilder {
     inputs: Vec<Input>,
     outputs: Vec<Output<Address>>,
+    validity: ValidityInterval,
 }
 
 /// Inputs & Outputs for a built transaction
@@ -49,6 +65,33 @@ impl error::Error for Error {
     }
 }
 
+/// Estimate the size of the authentication of a payload, which is only known
+/// once the transaction is built.
+///
+/// The pool certificates are assumed to be signed by the pool owners: as many
+/// as the management threshold of the registration carried by the certificate,
+/// and a single one for a retirement, which does not carry it.
+fn estimate_payload_auth_size<'a, P: Payload>(payload: &PayloadSlice<'a, P>) -> usize {
+    if !P::HAS_AUTH {
+        return 0;
+    }
+    let pool_owners_signatures_size =
+        |n: u8| POOL_OWNERS_SIGNATURES_HEADER_SIZE + n as usize * POOL_OWNER_SIGNATURE_SIZE;
+    match payload.clone().to_certificate_slice() {
+        Some(CertificateSlice::StakeDelegation(_)) => ACCOUNT_BINDING_SIGNATURE_SIZE,
+        Some(CertificateSlice::PoolRegistration(c)) => {
+            pool_owners_signatures_size(c.into_payload().management_threshold())
+        }
+        Some(CertificateSlice::PoolUpdate(c)) => {
+            pool_owners_signatures_size(c.into_payload().new_pool_reg.management_threshold())
+        }
+        Some(CertificateSlice::PoolRetirement(_)) => pool_owners_signatures_size(1),
+        Some(CertificateSlice::OwnerStakeDelegation(_))
+        | Some(CertificateSlice::MultisigDeclaration(_))
+        | None => 0,
+    }
+}
+
 /// Output policy to be used in transaction. This policy is used then
 /// there is positive balance on in the OUTPUT+FEE-INPUT. Policy
 /// explains how to use that balance. Rember that policy application
@@ -67,6 +110,7 @@ impl InputOutputBuilder {
         InputOutputBuilder {
             inputs: Vec::new(),
             outputs: Vec::new(),
+            validity: ValidityInterval::unbounded(),
         }
     }
 
@@ -84,7 +128,11 @@ impl InputOutputBuilder {
         if outputs.len() > 255 {
             return Err(Error::TxTooManyOutputs);
         }
-        Ok(InputOutputBuilder { inputs, outputs })
+        Ok(InputOutputBuilder {
+            inputs,
+            outputs,
+            validity: ValidityInterval::unbounded(),
+        })
     }
 
     /// Build the InputOutput from the Builder
@@ -95,6 +143,12 @@ impl InputOutputBuilder {
         }
     }
 
+    /// Set the validity interval the transaction will be built with, its size
+    /// is part of the fragment size the fee may depend on. Unbounded by default.
+    pub fn set_validity(&mut self, validity: ValidityInterval) {
+        self.validity = validity;
+    }
+
     /// Add additional input.
     ///
     /// Each input may extend the size of the required fee.
@@ -144,6 +198,34 @@ impl InputOutputBuilder {
         }
     }
 
+    /// Estimate the size of the fragment of the transaction, with the given number of
+    /// inputs and outputs to be added.
+    ///
+    /// Every input is assumed to be witnessed by an utxo or account witness, and
+    /// the outputs to be added to go to group addresses. See `estimate_payload_auth_size`
+    /// for the payload authentication.
+    pub fn estimate_fragment_size<'a, P: Payload>(
+        &self,
+        payload: &PayloadSlice<'a, P>,
+        inputs_placeholders: u8,
+        outputs_placeholders: u8,
+    ) -> usize {
+        let nb_inputs = self.inputs.len() + inputs_placeholders as usize;
+        let outputs_size: usize = self
+            .outputs
+            .iter()
+            .map(|output| output.address.to_bytes().len() + 8)
+            .sum();
+        FRAGMENT_HEADER_SIZE
+            + payload.as_bytes().len()
+            + IOS_HEADER_SIZE
+            + self.validity.to_bytes().len()
+            + nb_inputs * (INPUT_SIZE + WITNESS_SIZE)
+            + outputs_size
+            + outputs_placeholders as usize * OUTPUT_PLACEHOLDER_SIZE
+            + estimate_payload_auth_size(payload)
+    }
+
     /// Calculate the fees on a given fee algorithm for the current transaction
     pub fn estimate_fee<'a, P: Payload, F: FeeAlgorithm>(
         &self,
@@ -151,9 +233,11 @@ impl InputOutputBuilder {
         fee_algorithm: &F,
     ) -> Value {
         fee_algorithm.calculate(
-            payload.to_certificate_slice(),
+            payload.clone().to_certificate_slice(),
             self.inputs.len() as u8,
             self.outputs.len() as u8,
+            payload.clone().to_metadata_slice().map_or(0, |m| m.len()),
+            self.estimate_fragment_size(&payload, 0, 0),
         )
     }
 
@@ -185,7 +269,13 @@ impl InputOutputBuilder {
         let nb_inputs = self.inputs.len() as u8 + inputs_placeholders;
         let nb_outputs = self.outputs.len() as u8 + outputs_placeholders;
 
-        let fee = fee_algorithm.calculate(payload.to_certificate_slice(), nb_inputs, nb_outputs);
+        let fee = fee_algorithm.calculate(
+            payload.clone().to_certificate_slice(),
+            nb_inputs,
+            nb_outputs,
+            payload.clone().to_metadata_slice().map_or(0, |m| m.len()),
+            self.estimate_fragment_size(&payload, inputs_placeholders, outputs_placeholders),
+        );
         self.balance(fee).map_err(Error::MathErr)
     }
 
@@ -0,0 +1,133 @@
+use super::payload::{Payload, PayloadAuthData, PayloadData, PayloadSlice};
+use super::transaction::TransactionSlice;
+use crate::certificate::CertificateSlice;
+use chain_core::{
+    mempack::{ReadBuf, ReadError, Readable},
+    property,
+};
+use std::marker::PhantomData;
+
+/// Maximum size of a metadata blob allowed by the encoding. The ledger
+/// further restricts this with the `MetadataMaxSize` setting.
+pub const METADATA_MAXIMUM_SIZE: usize = u16::MAX as usize;
+
+/// Opaque metadata attached to a value transfer, such as an invoice id
+/// or the hash of a document.
+///
+/// The metadata is part of the transaction's signed data, and its size is
+/// charged by the fee algorithm.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metadata(Box<[u8]>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataTooBig(pub usize);
+
+impl Metadata {
+    pub fn new(bytes: Vec<u8>) -> Result<Self, MetadataTooBig> {
+        if bytes.len() > METADATA_MAXIMUM_SIZE {
+            return Err(MetadataTooBig(bytes.len()));
+        }
+        Ok(Metadata(bytes.into()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    // Format is:
+    // SIZE (u16) METADATA (SIZE bytes)
+    fn serialize(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(2 + self.0.len());
+        out.extend_from_slice(&(self.0.len() as u16).to_be_bytes());
+        out.extend_from_slice(&self.0);
+        out
+    }
+}
+
+impl AsRef<[u8]> for Metadata {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl property::Serialize for Metadata {
+    type Error = std::io::Error;
+    fn serialize<W: std::io::Write>(&self, mut writer: W) -> Result<(), Self::Error> {
+        writer.write_all(&Metadata::serialize(self))
+    }
+}
+
+impl Readable for Metadata {
+    fn read<'a>(buf: &mut ReadBuf<'a>) -> Result<Self, ReadError> {
+        let size = buf.get_u16()? as usize;
+        let bytes = buf.get_slice(size)?;
+        Ok(Metadata(bytes.into()))
+    }
+}
+
+impl Payload for Metadata {
+    const HAS_DATA: bool = true;
+    const HAS_AUTH: bool = false;
+    type Auth = ();
+
+    fn payload_data(&self) -> PayloadData<Self> {
+        PayloadData(self.serialize().into(), PhantomData)
+    }
+
+    fn payload_auth_data(_: &Self::Auth) -> PayloadAuthData<Self> {
+        PayloadAuthData(Vec::with_capacity(0).into(), PhantomData)
+    }
+
+    fn to_certificate_slice<'a>(_: PayloadSlice<'a, Self>) -> Option<CertificateSlice<'a>> {
+        None
+    }
+
+    fn to_metadata_slice<'a>(p: PayloadSlice<'a, Self>) -> Option<&'a [u8]> {
+        Some(p.metadata())
+    }
+}
+
+impl<'a> PayloadSlice<'a, Metadata> {
+    /// The metadata bytes, borrowed from the serialized payload
+    pub fn metadata(&self) -> &'a [u8] {
+        let size = u16::from_be_bytes([self.0[0], self.0[1]]) as usize;
+        &self.0[2..2 + size]
+    }
+}
+
+impl<'a> TransactionSlice<'a, Metadata> {
+    /// The metadata bytes, borrowed from the serialized transaction
+    pub fn metadata(&self) -> &'a [u8] {
+        self.payload().metadata()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::TxBuilder;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn metadata_too_big() {
+        assert_eq!(
+            Metadata::new(vec![0; METADATA_MAXIMUM_SIZE + 1]),
+            Err(MetadataTooBig(METADATA_MAXIMUM_SIZE + 1))
+        );
+    }
+
+    #[quickcheck]
+    fn metadata_is_readable_from_transaction_slice(bytes: Vec<u8>) -> bool {
+        let metadata = Metadata::new(bytes.clone()).unwrap();
+        let tx = TxBuilder::new()
+            .set_payload(&metadata)
+            .set_ios(&[], &[])
+            .set_witnesses(&[])
+            .set_payload_auth(&());
+        tx.as_slice().metadata() == &bytes[..] && tx.as_slice().payload().into_payload() == metadata
+    }
+}
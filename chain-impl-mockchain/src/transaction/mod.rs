@@ -2,10 +2,12 @@ mod builder;
 mod element;
 mod input;
 mod io;
+mod metadata;
 mod payload;
 mod transaction;
 mod transfer;
 mod utxo;
+mod validity;
 mod witness;
 
 #[cfg(any(test, feature = "property-test-api"))]
@@ -19,10 +21,12 @@ pub use builder::{SetAuthData, SetIOs, SetPayload, SetWitnesses, TxBuilder, TxBu
 pub use element::*;
 pub use input::*;
 pub use io::{Error, InputOutput, InputOutputBuilder, OutputPolicy};
+pub use metadata::{Metadata, MetadataTooBig, METADATA_MAXIMUM_SIZE};
 pub use payload::{NoExtra, Payload, PayloadAuthData, PayloadAuthSlice, PayloadData, PayloadSlice};
 pub use transaction::*;
 pub use transfer::*;
 pub use utxo::*;
+pub use validity::ValidityInterval;
 pub use witness::*;
 
 impl<Extra: Payload> property::Serialize for Transaction<Extra> {
@@ -42,5 +46,17 @@ impl<Extra: Payload> Readable for Transaction<Extra> {
     }
 }
 
+impl<Extra: Payload> Transaction<Extra> {
+    /// Read a transaction with a validity interval, which `Readable` does not
+    /// expect as it only reads the original encoding
+    pub fn read_with_validity<'a>(buf: &mut ReadBuf<'a>) -> Result<Self, ReadError> {
+        let utx = UnverifiedTransactionSlice::from(buf.get_slice_end());
+        match utx.check_with_validity() {
+            Ok(tx) => Ok(tx.into_owned()),
+            Err(_) => Err(ReadError::StructureInvalid("transaction".to_string())),
+        }
+    }
+}
+
 // TEMPORARY
 pub type AuthenticatedTransaction<P> = Transaction<P>;
@@ -16,6 +16,11 @@ pub trait Payload: Readable {
     fn payload_auth_data(auth: &Self::Auth) -> PayloadAuthData<Self>;
 
     fn to_certificate_slice<'a>(p: PayloadSlice<'a, Self>) -> Option<CertificateSlice<'a>>;
+
+    /// The metadata bytes carried by the payload, if any
+    fn to_metadata_slice<'a>(_: PayloadSlice<'a, Self>) -> Option<&'a [u8]> {
+        None
+    }
 }
 
 /// Owned binary representation of a payload
@@ -94,6 +99,10 @@ impl<'a, P: Payload> PayloadSlice<'a, P> {
     pub fn to_certificate_slice(self) -> Option<CertificateSlice<'a>> {
         <P as Payload>::to_certificate_slice(self)
     }
+
+    pub fn to_metadata_slice(self) -> Option<&'a [u8]> {
+        <P as Payload>::to_metadata_slice(self)
+    }
 }
 
 impl<P: ?Sized> AsRef<[u8]> for PayloadData<P> {
@@ -1,9 +1,13 @@
 use super::{
     element::SingleAccountBindingSignature, AccountBindingSignature, AccountIdentifier, Input,
-    NoExtra, Payload, Transaction, TxBuilder, UnspecifiedAccountIdentifier, UtxoPointer, Witness,
+    Metadata, NoExtra, Output, Payload, Transaction, TxBuilder, UnspecifiedAccountIdentifier,
+    UtxoPointer, ValidityInterval, Witness,
 };
 use crate::certificate::OwnerStakeDelegation;
+use crate::date::BlockDate;
 use crate::key::{EitherEd25519SecretKey, SpendingSignature};
+use chain_addr::Address;
+use chain_core::mempack::read_from_raw;
 use chain_crypto::{testing::arbitrary_secret_key, Ed25519, SecretKey, Signature};
 use quickcheck::{Arbitrary, Gen, TestResult};
 use quickcheck_macros::quickcheck;
@@ -12,6 +16,9 @@ quickcheck! {
     fn transaction_encode_decode(transaction: Transaction<NoExtra>) -> TestResult {
         chain_core::property::testing::serialization_bijection_r(transaction)
     }
+    fn metadata_tx_encode_decode(transaction: Transaction<Metadata>) -> TestResult {
+        chain_core::property::testing::serialization_bijection_r(transaction)
+    }
     fn stake_owner_delegation_tx_encode_decode(transaction: Transaction<OwnerStakeDelegation>) -> TestResult {
         chain_core::property::testing::serialization_bijection_r(transaction)
     }
@@ -25,6 +32,34 @@ quickcheck! {
     }
 }
 
+#[quickcheck]
+pub fn transaction_in_original_encoding_is_decoded(
+    input: Input,
+    output: Output<Address>,
+    witness: Witness,
+) -> TestResult {
+    // payload-less transaction as encoded before validity intervals:
+    // NB_INPUTS NB_OUTPUTS INPUTS OUTPUTS WITNESSES
+    let mut bytes = vec![1, 1];
+    bytes.extend_from_slice(&input.bytes());
+    bytes.extend_from_slice(&output.address.to_bytes());
+    bytes.extend_from_slice(&output.value.bytes());
+    bytes.extend_from_slice(&witness.to_bytes());
+
+    let tx: Transaction<NoExtra> = match read_from_raw(&bytes) {
+        Ok(tx) => tx,
+        Err(e) => return TestResult::error(e.to_string()),
+    };
+    let rebuilt = TxBuilder::new()
+        .set_nopayload()
+        .set_ios(&[input], &[output])
+        .set_witnesses(&[witness])
+        .set_payload_auth(&());
+    TestResult::from_bool(
+        tx.validity().is_unbounded() && tx.as_ref() == &bytes[..] && rebuilt == tx,
+    )
+}
+
 use std::fmt::Display;
 
 fn check_eq<X: Eq + Display>(s1: &str, x1: X, s2: &str, x2: X, s: &str) -> Result<(), String> {
@@ -130,6 +165,29 @@ impl Arbitrary for NoExtra {
     }
 }
 
+fn arbitrary_block_date<G: Gen>(g: &mut G) -> Option<BlockDate> {
+    if Arbitrary::arbitrary(g) {
+        Some(BlockDate {
+            epoch: Arbitrary::arbitrary(g),
+            slot_id: Arbitrary::arbitrary(g),
+        })
+    } else {
+        None
+    }
+}
+
+impl Arbitrary for ValidityInterval {
+    fn arbitrary<G: Gen>(g: &mut G) -> Self {
+        ValidityInterval::new(arbitrary_block_date(g), arbitrary_block_date(g))
+    }
+}
+
+impl Arbitrary for Metadata {
+    fn arbitrary<G: Gen>(g: &mut G) -> Self {
+        Metadata::new(Arbitrary::arbitrary(g)).unwrap()
+    }
+}
+
 impl<Extra: Arbitrary + Payload> Arbitrary for Transaction<Extra>
 where
     Extra::Auth: Arbitrary,
@@ -3,6 +3,7 @@ use super::element::{Balance, BalanceError, TransactionSignDataHash};
 use super::input::{Input, INPUT_SIZE};
 use super::payload::{Payload, PayloadAuthSlice, PayloadSlice};
 use super::transfer::Output;
+use super::validity::ValidityInterval;
 use super::witness::Witness;
 use crate::value::{Value, ValueError};
 use chain_addr::Address;
@@ -35,6 +36,7 @@ impl<P> Debug for Transaction<P> {
             .field("nb_inputs", &tx.nb_inputs())
             .field("nb_outputs", &tx.nb_outputs())
             .field("nb_witnesses", &tx.nb_witnesses())
+            .field("validity", &tx.validity())
             .field("total_input_value", &self.total_input())
             .field("total_output_value", &self.total_output())
             .finish()
@@ -224,6 +226,7 @@ impl<'a> InputsWitnessesSlice<'a> {
 pub enum TransactionStructError {
     CannotReadNbInputs,
     CannotReadNbOutputs,
+    ValidityInvalid,
     PayloadInvalid,
     InputsInvalid,
     OutputsInvalid,
@@ -242,10 +245,14 @@ pub(super) struct TransactionStruct {
     pub(super) outputs: usize,
     pub(super) witnesses: usize,
     pub(super) payload_auth: usize,
+    pub(super) validity: ValidityInterval,
 }
 
 /// Verify the structure of the transaction and return all the offsets
-fn get_spine<'a, P: Payload>(slice: &'a [u8]) -> Result<TransactionStruct, TransactionStructError> {
+fn get_spine<'a, P: Payload>(
+    slice: &'a [u8],
+    with_validity: bool,
+) -> Result<TransactionStruct, TransactionStructError> {
     let sz = slice.len();
     let mut rb = ReadBuf::from(slice);
 
@@ -261,6 +268,11 @@ fn get_spine<'a, P: Payload>(slice: &'a [u8]) -> Result<TransactionStruct, Trans
     let nb_outputs = rb
         .get_u8()
         .map_err(|_| TransactionStructError::CannotReadNbOutputs)?;
+    let validity = if with_validity {
+        ValidityInterval::read(&mut rb).map_err(|_| TransactionStructError::ValidityInvalid)?
+    } else {
+        ValidityInterval::unbounded()
+    };
 
     let inputs_pos = rb.position();
     rb.skip_bytes(nb_inputs as usize * INPUT_SIZE)
@@ -297,6 +309,7 @@ fn get_spine<'a, P: Payload>(slice: &'a [u8]) -> Result<TransactionStruct, Trans
         outputs: outputs_pos,
         witnesses: witnesses_pos,
         payload_auth: payload_auth_pos,
+        validity,
     })
 }
 
@@ -311,7 +324,20 @@ impl<'a, P: Payload> From<&'a [u8]> for UnverifiedTransactionSlice<'a, P> {
 
 impl<'a, P: Payload> UnverifiedTransactionSlice<'a, P> {
     pub fn check(self) -> Result<TransactionSlice<'a, P>, TransactionStructError> {
-        let tstruct = get_spine::<P>(&self.data)?;
+        self.check_spine(false)
+    }
+
+    /// Check a transaction with a validity interval, which is encoded after
+    /// its number of outputs and is not part of the original encoding
+    pub fn check_with_validity(self) -> Result<TransactionSlice<'a, P>, TransactionStructError> {
+        self.check_spine(true)
+    }
+
+    fn check_spine(
+        self,
+        with_validity: bool,
+    ) -> Result<TransactionSlice<'a, P>, TransactionStructError> {
+        let tstruct = get_spine::<P>(&self.data, with_validity)?;
         Ok(TransactionSlice {
             data: self.data,
             tstruct: tstruct,
@@ -368,6 +394,10 @@ impl<P> Transaction<P> {
         self.tstruct.nb_outputs
     }
 
+    pub fn validity(&self) -> ValidityInterval {
+        self.tstruct.validity
+    }
+
     pub fn total_input(&self) -> Result<Value, ValueError> {
         Value::sum(self.as_slice().inputs().iter().map(|input| input.value()))
     }
@@ -458,6 +488,15 @@ impl<'a, P> TransactionSlice<'a, P> {
         self.tstruct.nb_inputs
     }
 
+    pub fn validity(&self) -> ValidityInterval {
+        self.tstruct.validity
+    }
+
+    /// Size in bytes of the serialized transaction
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
     pub fn inputs(&self) -> InputsSlice<'a> {
         InputsSlice(
             self.tstruct.nb_inputs,
@@ -0,0 +1,136 @@
+use crate::date::BlockDate;
+use chain_core::mempack::{ReadBuf, ReadError};
+
+const VALID_FROM_FLAG: u8 = 0b01;
+const VALID_UNTIL_FLAG: u8 = 0b10;
+
+/// Interval of block dates in which a transaction can be applied to the ledger.
+///
+/// Both bounds are inclusive and optional, an unbounded interval means the
+/// transaction never expires (which is the default).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ValidityInterval {
+    pub valid_from: Option<BlockDate>,
+    pub valid_until: Option<BlockDate>,
+}
+
+impl ValidityInterval {
+    pub fn unbounded() -> Self {
+        ValidityInterval {
+            valid_from: None,
+            valid_until: None,
+        }
+    }
+
+    pub fn new(valid_from: Option<BlockDate>, valid_until: Option<BlockDate>) -> Self {
+        ValidityInterval {
+            valid_from,
+            valid_until,
+        }
+    }
+
+    pub fn until(valid_until: BlockDate) -> Self {
+        Self::new(None, Some(valid_until))
+    }
+
+    pub fn is_unbounded(&self) -> bool {
+        self.valid_from.is_none() && self.valid_until.is_none()
+    }
+
+    /// Check if the given date is in the interval
+    pub fn contains(&self, date: &BlockDate) -> bool {
+        !matches!(self.valid_from, Some(from) if *date < from)
+            && !matches!(self.valid_until, Some(until) if until < *date)
+    }
+
+    // Format is empty for an unbounded interval, otherwise:
+    // FLAGS (byte) [ VALID_FROM (epoch u32, slot u32) ] [ VALID_UNTIL (epoch u32, slot u32) ]
+    pub(super) fn to_bytes(self) -> Vec<u8> {
+        if self.is_unbounded() {
+            return Vec::new();
+        }
+        let mut flags = 0u8;
+        let mut out = vec![flags];
+        for (flag, date) in [
+            (VALID_FROM_FLAG, self.valid_from),
+            (VALID_UNTIL_FLAG, self.valid_until),
+        ]
+        .iter()
+        {
+            if let Some(date) = date {
+                flags |= flag;
+                out.extend_from_slice(&date.epoch.to_be_bytes());
+                out.extend_from_slice(&date.slot_id.to_be_bytes());
+            }
+        }
+        out[0] = flags;
+        out
+    }
+
+    pub(super) fn read(buf: &mut ReadBuf) -> Result<Self, ReadError> {
+        let flags = buf.get_u8()?;
+        if flags == 0 || flags & !(VALID_FROM_FLAG | VALID_UNTIL_FLAG) != 0 {
+            return Err(ReadError::UnknownTag(flags as u32));
+        }
+        let mut read_date = |flag: u8| -> Result<Option<BlockDate>, ReadError> {
+            if flags & flag == 0 {
+                return Ok(None);
+            }
+            let epoch = buf.get_u32()?;
+            let slot_id = buf.get_u32()?;
+            Ok(Some(BlockDate { epoch, slot_id }))
+        };
+        let valid_from = read_date(VALID_FROM_FLAG)?;
+        let valid_until = read_date(VALID_UNTIL_FLAG)?;
+        Ok(ValidityInterval {
+            valid_from,
+            valid_until,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+
+    #[quickcheck]
+    fn validity_interval_encode_decode(validity: ValidityInterval) -> bool {
+        let bytes = validity.to_bytes();
+        if validity.is_unbounded() {
+            return bytes.is_empty();
+        }
+        let mut buf = ReadBuf::from(&bytes);
+        ValidityInterval::read(&mut buf).unwrap() == validity && buf.is_end()
+    }
+
+    #[test]
+    fn interval_without_bounds_is_rejected() {
+        assert!(ValidityInterval::read(&mut ReadBuf::from(&[0])).is_err());
+    }
+
+    #[quickcheck]
+    fn unbounded_contains_any_date(date: BlockDate) -> bool {
+        ValidityInterval::unbounded().contains(&date)
+    }
+
+    #[test]
+    fn bounds_are_inclusive() {
+        let from = BlockDate {
+            epoch: 1,
+            slot_id: 10,
+        };
+        let until = BlockDate {
+            epoch: 2,
+            slot_id: 0,
+        };
+        let validity = ValidityInterval::new(Some(from), Some(until));
+        assert!(validity.contains(&from));
+        assert!(validity.contains(&until));
+        assert!(!validity.contains(&BlockDate {
+            epoch: 1,
+            slot_id: 9
+        }));
+        assert!(!validity.contains(&until.next_epoch()));
+    }
+}
@@ -3,9 +3,8 @@ use crate::value::Value;
 
 /// An amount of value owned by the treasury.
 ///
-/// Right now, it doesn't have any mechanism to
-/// withdraw money from, so it serves just to
-/// record a monotically increasing special account.
+/// Value can only be withdrawn from it through treasury
+/// transfer proposals accepted by the BFT leaders.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Treasury(Value);
 
@@ -21,6 +20,12 @@ impl Treasury {
         Ok(())
     }
 
+    /// Withdraw some value from the treasury
+    pub fn withdraw(&mut self, v: Value) -> Result<(), Error> {
+        self.0 = (self.0 - v).map_err(|error| Error::PotValueInvalid { error })?;
+        Ok(())
+    }
+
     /// Get value in the treasury
     pub fn value(&self) -> Value {
         self.0
@@ -233,6 +233,9 @@ impl TransactionFinalizer {
                 Certificate::OwnerStakeDelegation(c) => {
                     self.tx.clone().replace_extra(c.clone()).hash()
                 }
+                Certificate::MultisigDeclaration(c) => {
+                    self.tx.clone().replace_extra(c.clone()).hash()
+                }
             },
         }
     }
@@ -310,6 +313,13 @@ impl TransactionFinalizer {
                     };
                     Ok(Fragment::OwnerStakeDelegation(atx))
                 }
+                Certificate::MultisigDeclaration(c) => {
+                    let atx = tx::AuthenticatedTransaction {
+                        transaction: tx.transaction.clone().replace_extra(c.clone()),
+                        witnesses: tx.witnesses,
+                    };
+                    Ok(Fragment::MultisigDeclaration(atx))
+                }
             },
         }
     }
@@ -1,10 +1,17 @@
 //use crate::certificate::{verify_certificate, HasPublicKeys, SignatureRaw};
+use crate::certificate::{PoolId, PoolOwnersSigned, PoolRegistration};
+use crate::config::Tag;
 use crate::date::BlockDate;
 use crate::fragment::config::ConfigParams;
 use crate::key::BftLeaderId;
+use crate::rewards::Ratio;
 use crate::setting::{ActiveSlotsCoeffError, Settings};
+use crate::stake::{Stake, StakeDistribution};
+use crate::transaction::{Output, TransactionBindingAuthData};
+use chain_addr::Address;
 use chain_core::mempack::{ReadBuf, ReadError, Readable};
 use chain_core::property;
+use chain_core::property::Serialize as _;
 use chain_crypto::Verification;
 use std::collections::{BTreeMap, HashSet};
 
@@ -13,15 +20,22 @@ pub struct UpdateState {
     // Note: we use a BTreeMap to ensure that proposals are processed
     // in a well-defined (sorted) order.
     pub proposals: BTreeMap<UpdateProposalId, UpdateProposalState>,
+    pub treasury_transfers: BTreeMap<UpdateProposalId, TreasuryTransferProposalState>,
 }
 
 impl UpdateState {
     pub fn new() -> Self {
         UpdateState {
             proposals: BTreeMap::new(),
+            treasury_transfers: BTreeMap::new(),
         }
     }
 
+    fn contains_proposal(&self, proposal_id: &UpdateProposalId) -> bool {
+        self.proposals.contains_key(proposal_id)
+            || self.treasury_transfers.contains_key(proposal_id)
+    }
+
     pub fn apply_proposal(
         mut self,
         proposal_id: UpdateProposalId,
@@ -44,7 +58,7 @@ impl UpdateState {
 
         let proposal = &proposal.proposal.proposal;
 
-        if let Some(_) = self.proposals.get_mut(&proposal_id) {
+        if self.contains_proposal(&proposal_id) {
             Err(Error::DuplicateProposal(proposal_id))
         } else {
             self.proposals.insert(
@@ -53,6 +67,43 @@ impl UpdateState {
                     proposal: proposal.clone(),
                     proposal_date: cur_date,
                     votes: HashSet::new(),
+                    pool_votes: BTreeMap::new(),
+                    tally: StakeVoteTally::zero(),
+                },
+            );
+            Ok(self)
+        }
+    }
+
+    pub fn apply_treasury_transfer_proposal(
+        mut self,
+        proposal_id: UpdateProposalId,
+        proposal: &SignedTreasuryTransferProposal,
+        settings: &Settings,
+        cur_date: BlockDate,
+    ) -> Result<Self, Error> {
+        let proposer_id = &proposal.proposal.proposer_id;
+
+        if proposal.verify() == Verification::Failed {
+            return Err(Error::BadProposalSignature(
+                proposal_id,
+                proposer_id.clone(),
+            ));
+        }
+
+        if !settings.bft_leaders.contains(proposer_id) {
+            return Err(Error::BadProposer(proposal_id, proposer_id.clone()));
+        }
+
+        if self.contains_proposal(&proposal_id) {
+            Err(Error::DuplicateProposal(proposal_id))
+        } else {
+            self.treasury_transfers.insert(
+                proposal_id,
+                TreasuryTransferProposalState {
+                    proposal: proposal.proposal.proposal.clone(),
+                    proposal_date: cur_date,
+                    votes: HashSet::new(),
                 },
             );
             Ok(self)
@@ -80,8 +131,16 @@ impl UpdateState {
             ));
         }
 
-        if let Some(proposal) = self.proposals.get_mut(&vote.proposal_id) {
-            if !proposal.votes.insert(vote.voter_id.clone()) {
+        let votes = match self.proposals.get_mut(&vote.proposal_id) {
+            Some(proposal) => Some(&mut proposal.votes),
+            None => self
+                .treasury_transfers
+                .get_mut(&vote.proposal_id)
+                .map(|proposal| &mut proposal.votes),
+        };
+
+        if let Some(votes) = votes {
+            if !votes.insert(vote.voter_id.clone()) {
                 return Err(Error::DuplicateVote(
                     vote.proposal_id.clone(),
                     vote.voter_id.clone(),
@@ -94,13 +153,80 @@ impl UpdateState {
         }
     }
 
+    /// Record the vote of a stake pool on an update proposal. The vote
+    /// must be signed by the pool owners, following the pool management
+    /// threshold, and is only accepted if the stake voting is enabled.
+    pub fn apply_pool_vote(
+        mut self,
+        vote: &SignedPoolUpdateVote,
+        pool_info: &PoolRegistration,
+        settings: &Settings,
+    ) -> Result<Self, Error> {
+        let proposal_id = &vote.vote.proposal_id;
+        let pool_id = &vote.vote.pool_id;
+
+        if settings.stake_voting_quorum.is_none() {
+            return Err(Error::StakeVotingDisabled(proposal_id.clone()));
+        }
+
+        if vote.verify(pool_info) == Verification::Failed {
+            return Err(Error::BadPoolVoteSignature(
+                proposal_id.clone(),
+                pool_id.clone(),
+            ));
+        }
+
+        match self.proposals.get_mut(proposal_id) {
+            Some(proposal) => {
+                if proposal.pool_votes.contains_key(pool_id) {
+                    return Err(Error::DuplicatePoolVote(
+                        proposal_id.clone(),
+                        pool_id.clone(),
+                    ));
+                }
+                proposal
+                    .pool_votes
+                    .insert(pool_id.clone(), vote.vote.approve);
+                Ok(self)
+            }
+            None => Err(Error::VoteForMissingProposal(proposal_id.clone())),
+        }
+    }
+
+    /// Check if any of the update proposals received a vote from a stake pool
+    pub fn has_pool_votes(&self) -> bool {
+        self.proposals
+            .values()
+            .any(|proposal| !proposal.pool_votes.is_empty())
+    }
+
+    /// Tally the stake pools votes of every update proposal, weighting
+    /// each vote by the stake delegated to the pool in `distribution`.
+    pub fn tally_pool_votes(mut self, distribution: &StakeDistribution) -> Self {
+        for proposal in self.proposals.values_mut() {
+            proposal.tally = StakeVoteTally::new(&proposal.pool_votes, distribution);
+        }
+        self
+    }
+
+    /// Process the proposals when entering a new epoch: the accepted update
+    /// proposals are applied to the settings, and the accepted treasury transfers
+    /// are returned (in order of proposal ID) for the ledger to pay them.
     pub fn process_proposals(
         mut self,
         mut settings: Settings,
         prev_date: BlockDate,
         new_date: BlockDate,
-    ) -> Result<(Self, Settings), Error> {
+    ) -> Result<
+        (
+            Self,
+            Settings,
+            Vec<(UpdateProposalId, TreasuryTransferProposal)>,
+        ),
+        Error,
+    > {
         let mut expired_ids = vec![];
+        let mut accepted_transfers = vec![];
 
         assert!(prev_date < new_date);
 
@@ -109,12 +235,15 @@ impl UpdateState {
         if prev_date.epoch < new_date.epoch {
             for (proposal_id, proposal_state) in &self.proposals {
                 // If a majority of BFT leaders voted for the
-                // proposal, then apply it. FIXME: multiple proposals
+                // proposal, or if the stake pools accepted it, then
+                // apply it. FIXME: multiple proposals
                 // might become accepted at the same time, in which
                 // case they're currently applied in order of proposal
                 // ID. FIXME: delay the effectuation of the proposal
                 // for some number of epochs.
-                if proposal_state.votes.len() > settings.bft_leaders.len() / 2 {
+                if proposal_state.votes.len() > settings.bft_leaders.len() / 2
+                    || proposal_state.tally.accepted(&settings)
+                {
                     settings = settings.apply(&proposal_state.proposal.changes)?;
                     expired_ids.push(proposal_id.clone());
                 } else if proposal_state.proposal_date.epoch + settings.proposal_expiration
@@ -127,9 +256,27 @@ impl UpdateState {
             for proposal_id in expired_ids {
                 self.proposals.remove(&proposal_id);
             }
+
+            // Treasury transfers are accepted with the same majority, and expire
+            // after the same period, as the update proposals.
+            let mut expired_ids = vec![];
+            for (proposal_id, proposal_state) in &self.treasury_transfers {
+                if proposal_state.votes.len() > settings.bft_leaders.len() / 2 {
+                    accepted_transfers.push((proposal_id.clone(), proposal_state.proposal.clone()));
+                    expired_ids.push(proposal_id.clone());
+                } else if proposal_state.proposal_date.epoch + settings.proposal_expiration
+                    < new_date.epoch
+                {
+                    expired_ids.push(proposal_id.clone());
+                }
+            }
+
+            for proposal_id in expired_ids {
+                self.treasury_transfers.remove(&proposal_id);
+            }
         }
 
-        Ok((self, settings))
+        Ok((self, settings, accepted_transfers))
     }
 }
 
@@ -138,6 +285,95 @@ pub struct UpdateProposalState {
     pub proposal: UpdateProposal,
     pub proposal_date: BlockDate,
     pub votes: HashSet<UpdateVoterId>,
+    /// The stake pools votes, for (true) or against (false) the proposal
+    pub pool_votes: BTreeMap<PoolId, bool>,
+    /// The stake pools votes weighted by stake, as of the last tally
+    pub tally: StakeVoteTally,
+}
+
+/// The result of the stake pools votes on a proposal, each vote
+/// being weighted by the stake delegated to the pool.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StakeVoteTally {
+    /// The stake of the pools which voted for the proposal
+    pub approve: Stake,
+    /// The stake of the pools which voted against the proposal
+    pub reject: Stake,
+    /// The total stake of the distribution used for the tally
+    pub total: Stake,
+}
+
+impl StakeVoteTally {
+    pub fn zero() -> Self {
+        StakeVoteTally {
+            approve: Stake::zero(),
+            reject: Stake::zero(),
+            total: Stake::zero(),
+        }
+    }
+
+    pub fn new(pool_votes: &BTreeMap<PoolId, bool>, distribution: &StakeDistribution) -> Self {
+        let mut tally = StakeVoteTally {
+            total: distribution.get_total_stake(),
+            ..StakeVoteTally::zero()
+        };
+        for (pool_id, approve) in pool_votes {
+            let stake = distribution
+                .get_stake_for(pool_id)
+                .unwrap_or_else(Stake::zero);
+            if *approve {
+                tally.approve += stake;
+            } else {
+                tally.reject += stake;
+            }
+        }
+        tally
+    }
+
+    /// The stake of the pools which took part in the vote
+    pub fn participation(&self) -> Stake {
+        self.approve + self.reject
+    }
+
+    /// Check that the participating stake is at least `quorum` of the total stake
+    pub fn quorum_reached(&self, quorum: &Ratio) -> bool {
+        let total: u64 = self.total.into();
+        let participation: u64 = self.participation().into();
+        total > 0
+            && participation as u128 * quorum.denominator.get() as u128
+                >= total as u128 * quorum.numerator as u128
+    }
+
+    /// Check that the approving stake is more than `threshold` of the participating
+    /// stake, or more than half of it if there is no threshold
+    pub fn approved(&self, threshold: Option<&Ratio>) -> bool {
+        let (numerator, denominator) = match threshold {
+            Some(ratio) => (ratio.numerator, ratio.denominator.get()),
+            None => (1, 2),
+        };
+        let approve: u64 = self.approve.into();
+        let participation: u64 = self.participation().into();
+        approve as u128 * denominator as u128 > participation as u128 * numerator as u128
+    }
+
+    /// Check if the proposal is accepted by the stake pools, following the
+    /// stake voting quorum and threshold of `settings`
+    pub fn accepted(&self, settings: &Settings) -> bool {
+        match &settings.stake_voting_quorum {
+            None => false,
+            Some(quorum) => {
+                self.quorum_reached(quorum)
+                    && self.approved(settings.stake_voting_threshold.as_ref())
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TreasuryTransferProposalState {
+    pub proposal: TreasuryTransferProposal,
+    pub proposal_date: BlockDate,
+    pub votes: HashSet<UpdateVoterId>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -153,7 +389,10 @@ pub enum Error {
     BadVoteSignature(UpdateProposalId, UpdateVoterId),
     BadVoter(UpdateProposalId, UpdateVoterId),
     DuplicateVote(UpdateProposalId, UpdateVoterId),
-    ReadOnlySetting,
+    StakeVotingDisabled(UpdateProposalId),
+    BadPoolVoteSignature(UpdateProposalId, PoolId),
+    DuplicatePoolVote(UpdateProposalId, PoolId),
+    ReadOnlySetting(Tag),
     BadBftSlotsRatio(crate::milli::Milli),
     BadConsensusGenesisPraosActiveSlotsCoeff(ActiveSlotsCoeffError),
 }
@@ -202,9 +441,25 @@ impl std::fmt::Display for Error {
                 "Received a duplicate vote from {:?} for proposal {}",
                 voter_id, proposal_id
             ),
-            Error::ReadOnlySetting => write!(
+            Error::StakeVotingDisabled(proposal_id) => write!(
                 f,
-                "Received a proposal to modify a chain parameter that can only be set in block 0"
+                "Received a stake pool vote for proposal {} but stake voting is disabled",
+                proposal_id
+            ),
+            Error::BadPoolVoteSignature(proposal_id, pool_id) => write!(
+                f,
+                "Vote from stake pool {} for proposal {} has an incorrect signature",
+                pool_id, proposal_id
+            ),
+            Error::DuplicatePoolVote(proposal_id, pool_id) => write!(
+                f,
+                "Received a duplicate vote from stake pool {} for proposal {}",
+                pool_id, proposal_id
+            ),
+            Error::ReadOnlySetting(tag) => write!(
+                f,
+                "Received a proposal to modify the chain parameter '{}' that can only be set in block 0",
+                tag.as_ref()
             ),
             Error::BadBftSlotsRatio(m) => {
                 write!(f, "Cannot set BFT slots ratio to invalid value {}", m)
@@ -322,6 +577,88 @@ impl Readable for SignedUpdateProposal {
     }
 }
 
+/// A proposal to transfer some value from the treasury to an account
+/// or an address. It is paid when the proposal is accepted, provided
+/// the treasury holds enough value at that time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TreasuryTransferProposal {
+    pub transfer: Output<Address>,
+}
+
+impl property::Serialize for TreasuryTransferProposal {
+    type Error = std::io::Error;
+    fn serialize<W: std::io::Write>(&self, writer: W) -> Result<(), Self::Error> {
+        use chain_core::packer::*;
+        let mut codec = Codec::new(writer);
+        codec.put_bytes(&self.transfer.address.to_bytes())?;
+        codec.put_u64(self.transfer.value.0)?;
+        Ok(())
+    }
+}
+
+impl Readable for TreasuryTransferProposal {
+    fn read<'a>(buf: &mut ReadBuf<'a>) -> Result<Self, ReadError> {
+        Ok(Self {
+            transfer: Readable::read(buf)?,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TreasuryTransferProposalWithProposer {
+    pub proposal: TreasuryTransferProposal,
+    pub proposer_id: UpdateVoterId,
+}
+
+impl property::Serialize for TreasuryTransferProposalWithProposer {
+    type Error = std::io::Error;
+    fn serialize<W: std::io::Write>(&self, writer: W) -> Result<(), Self::Error> {
+        use chain_core::packer::*;
+        let mut codec = Codec::new(writer);
+        self.proposal.serialize(&mut codec)?;
+        self.proposer_id.serialize(&mut codec)?;
+        Ok(())
+    }
+}
+
+impl Readable for TreasuryTransferProposalWithProposer {
+    fn read<'a>(buf: &mut ReadBuf<'a>) -> Result<Self, ReadError> {
+        Ok(Self {
+            proposal: Readable::read(buf)?,
+            proposer_id: Readable::read(buf)?,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SignedTreasuryTransferProposal {
+    pub proposal: TreasuryTransferProposalWithProposer,
+}
+
+impl SignedTreasuryTransferProposal {
+    pub fn verify(&self) -> Verification {
+        Verification::Success
+    }
+}
+
+impl property::Serialize for SignedTreasuryTransferProposal {
+    type Error = std::io::Error;
+    fn serialize<W: std::io::Write>(&self, writer: W) -> Result<(), Self::Error> {
+        use chain_core::packer::*;
+        let mut codec = Codec::new(writer);
+        self.proposal.serialize(&mut codec)?;
+        Ok(())
+    }
+}
+
+impl Readable for SignedTreasuryTransferProposal {
+    fn read<'a>(buf: &mut ReadBuf<'a>) -> Result<Self, ReadError> {
+        Ok(Self {
+            proposal: Readable::read(buf)?,
+        })
+    }
+}
+
 // A positive vote for a proposal.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct UpdateVote {
@@ -380,6 +717,89 @@ impl Readable for SignedUpdateVote {
     }
 }
 
+/// A vote of a stake pool for or against an update proposal.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PoolUpdateVote {
+    pub proposal_id: UpdateProposalId,
+    pub pool_id: PoolId,
+    pub approve: bool,
+}
+
+impl property::Serialize for PoolUpdateVote {
+    type Error = std::io::Error;
+    fn serialize<W: std::io::Write>(&self, writer: W) -> Result<(), Self::Error> {
+        use chain_core::packer::*;
+        let mut codec = Codec::new(writer);
+        self.proposal_id.serialize(&mut codec)?;
+        codec.put_bytes(self.pool_id.as_ref())?;
+        codec.put_u8(if self.approve { 1 } else { 0 })?;
+        Ok(())
+    }
+}
+
+impl Readable for PoolUpdateVote {
+    fn read<'a>(buf: &mut ReadBuf<'a>) -> Result<Self, ReadError> {
+        let proposal_id = Readable::read(buf)?;
+        let pool_id = <[u8; 32]>::read(buf)?.into();
+        let approve = match buf.get_u8()? {
+            0 => false,
+            1 => true,
+            n => {
+                return Err(ReadError::StructureInvalid(format!(
+                    "invalid pool vote choice {}",
+                    n
+                )))
+            }
+        };
+        Ok(PoolUpdateVote {
+            proposal_id,
+            pool_id,
+            approve,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SignedPoolUpdateVote {
+    pub vote: PoolUpdateVote,
+    /// The pool owners signatures of the serialized vote
+    pub signature: PoolOwnersSigned,
+}
+
+impl SignedPoolUpdateVote {
+    pub fn verify(&self, pool_info: &PoolRegistration) -> Verification {
+        let data = self.vote.serialize_as_vec().unwrap();
+        self.signature
+            .verify(pool_info, &TransactionBindingAuthData(&data))
+    }
+}
+
+impl property::Serialize for SignedPoolUpdateVote {
+    type Error = std::io::Error;
+    fn serialize<W: std::io::Write>(&self, writer: W) -> Result<(), Self::Error> {
+        use chain_core::packer::*;
+        use typed_bytes::ByteBuilder;
+        let mut codec = Codec::new(writer);
+        self.vote.serialize(&mut codec)?;
+        codec.put_bytes(
+            &self
+                .signature
+                .serialize_in(ByteBuilder::new())
+                .finalize_as_vec(),
+        )?;
+        Ok(())
+    }
+}
+
+impl Readable for SignedPoolUpdateVote {
+    fn read<'a>(buf: &mut ReadBuf<'a>) -> Result<Self, ReadError> {
+        Ok(SignedPoolUpdateVote {
+            vote: Readable::read(buf)?,
+            signature: Readable::read(buf)?,
+        })
+    }
+}
+
 #[cfg(any(test, feature = "property-test-api"))]
 mod tests {
     use super::*;
@@ -387,20 +807,27 @@ mod tests {
         config::ConfigParam,
         fragment::config::ConfigParams,
         testing::{
-            builders::update_builder::{ProposalBuilder, SignedProposalBuilder, UpdateVoteBuilder},
-            data::LeaderPair,
+            builders::{
+                build_pool_vote,
+                update_builder::{ProposalBuilder, SignedProposalBuilder, UpdateVoteBuilder},
+                StakePoolBuilder,
+            },
+            data::{AddressData, LeaderPair},
             TestGen,
         },
         update::{
-            SignedUpdateProposal, SignedUpdateVote, UpdateProposal, UpdateProposalWithProposer,
-            UpdateVote,
+            SignedTreasuryTransferProposal, SignedUpdateProposal, SignedUpdateVote,
+            TreasuryTransferProposal, TreasuryTransferProposalWithProposer, UpdateProposal,
+            UpdateProposalWithProposer, UpdateVote,
         },
+        value::Value,
     };
     use chain_addr::Discrimination;
-    use chain_core::property::testing::serialization_bijection;
+    use chain_core::property::testing::{serialization_bijection, serialization_bijection_r};
     use quickcheck::{Arbitrary, Gen, TestResult};
     use quickcheck_macros::quickcheck;
     use std::iter;
+    use std::num::NonZeroU64;
 
     impl Arbitrary for UpdateProposal {
         fn arbitrary<G: Gen>(g: &mut G) -> Self {
@@ -429,6 +856,44 @@ mod tests {
         }
     }
 
+    impl Arbitrary for TreasuryTransferProposal {
+        fn arbitrary<G: Gen>(g: &mut G) -> Self {
+            Self {
+                transfer: Arbitrary::arbitrary(g),
+            }
+        }
+    }
+
+    impl Arbitrary for TreasuryTransferProposalWithProposer {
+        fn arbitrary<G: Gen>(g: &mut G) -> Self {
+            Self {
+                proposal: Arbitrary::arbitrary(g),
+                proposer_id: Arbitrary::arbitrary(g),
+            }
+        }
+    }
+
+    impl Arbitrary for SignedTreasuryTransferProposal {
+        fn arbitrary<G: Gen>(g: &mut G) -> Self {
+            Self {
+                proposal: Arbitrary::arbitrary(g),
+            }
+        }
+    }
+
+    impl Arbitrary for TreasuryTransferProposalState {
+        fn arbitrary<G: Gen>(g: &mut G) -> Self {
+            let size = usize::arbitrary(g);
+            Self {
+                proposal: TreasuryTransferProposal::arbitrary(g),
+                proposal_date: BlockDate::arbitrary(g),
+                votes: iter::from_fn(|| Some(UpdateVoterId::arbitrary(g)))
+                    .take(size)
+                    .collect(),
+            }
+        }
+    }
+
     impl Arbitrary for UpdateVote {
         fn arbitrary<G: Gen>(g: &mut G) -> Self {
             Self {
@@ -449,12 +914,46 @@ mod tests {
     impl Arbitrary for UpdateProposalState {
         fn arbitrary<G: Gen>(g: &mut G) -> Self {
             let size = usize::arbitrary(g);
+            let pool_votes_size = usize::arbitrary(g) % 10;
             Self {
                 proposal: UpdateProposal::arbitrary(g),
                 proposal_date: BlockDate::arbitrary(g),
                 votes: iter::from_fn(|| Some(UpdateVoterId::arbitrary(g)))
                     .take(size)
                     .collect(),
+                pool_votes: iter::from_fn(|| Some((PoolId::arbitrary(g), bool::arbitrary(g))))
+                    .take(pool_votes_size)
+                    .collect(),
+                tally: StakeVoteTally::arbitrary(g),
+            }
+        }
+    }
+
+    impl Arbitrary for StakeVoteTally {
+        fn arbitrary<G: Gen>(g: &mut G) -> Self {
+            Self {
+                approve: Arbitrary::arbitrary(g),
+                reject: Arbitrary::arbitrary(g),
+                total: Arbitrary::arbitrary(g),
+            }
+        }
+    }
+
+    impl Arbitrary for PoolUpdateVote {
+        fn arbitrary<G: Gen>(g: &mut G) -> Self {
+            Self {
+                proposal_id: Arbitrary::arbitrary(g),
+                pool_id: Arbitrary::arbitrary(g),
+                approve: Arbitrary::arbitrary(g),
+            }
+        }
+    }
+
+    impl Arbitrary for SignedPoolUpdateVote {
+        fn arbitrary<G: Gen>(g: &mut G) -> Self {
+            Self {
+                vote: Arbitrary::arbitrary(g),
+                signature: Arbitrary::arbitrary(g),
             }
         }
     }
@@ -493,10 +992,182 @@ mod tests {
         update_state.apply_vote(&signed_update_vote, &settings)
     }
 
+    fn apply_treasury_transfer_proposal(
+        update_state: UpdateState,
+        proposal_id: UpdateProposalId,
+        proposer: &LeaderPair,
+        settings: &Settings,
+        block_date: BlockDate,
+    ) -> Result<UpdateState, Error> {
+        let proposal = SignedTreasuryTransferProposal {
+            proposal: TreasuryTransferProposalWithProposer {
+                proposal: TreasuryTransferProposal {
+                    transfer: Output::from_address(
+                        AddressData::account(Discrimination::Test).address,
+                        Value(100),
+                    ),
+                },
+                proposer_id: proposer.id(),
+            },
+        };
+
+        update_state.apply_treasury_transfer_proposal(proposal_id, &proposal, &settings, block_date)
+    }
+
     quickcheck! {
         fn update_proposal_serialize_deserialize_bijection(update_proposal: UpdateProposal) -> TestResult {
             serialization_bijection(update_proposal)
         }
+
+        fn treasury_transfer_proposal_serialize_readable_bijection(proposal: TreasuryTransferProposal) -> TestResult {
+            serialization_bijection_r(proposal)
+        }
+    }
+
+    #[test]
+    pub fn accepted_treasury_transfer_is_returned_when_processing_proposals() {
+        let mut update_state = UpdateState::new();
+        let proposal_id = TestGen::hash();
+        let block_date = BlockDate::first();
+        let leaders = TestGen::leaders_pairs()
+            .take(3)
+            .collect::<Vec<LeaderPair>>();
+        let settings = TestGen::settings(leaders.clone());
+
+        update_state = apply_treasury_transfer_proposal(
+            update_state,
+            proposal_id,
+            &leaders[0],
+            &settings,
+            block_date,
+        )
+        .expect("failed while applying treasury transfer proposal");
+
+        // a single vote out of 3 leaders is not enough
+        update_state = apply_update_vote(update_state, proposal_id, &leaders[0], &settings)
+            .expect("failed while applying vote");
+        let (not_accepted, _, transfers) = update_state
+            .clone()
+            .process_proposals(settings.clone(), block_date, block_date.next_epoch())
+            .expect("error while processing proposal");
+        assert!(transfers.is_empty());
+        assert_eq!(not_accepted.treasury_transfers.len(), 1);
+
+        update_state = apply_update_vote(update_state, proposal_id, &leaders[1], &settings)
+            .expect("failed while applying vote");
+        let (update_state, _, transfers) = update_state
+            .process_proposals(settings, block_date, block_date.next_epoch())
+            .expect("error while processing proposal");
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].0, proposal_id);
+        assert_eq!(transfers[0].1.transfer.value, Value(100));
+        assert_eq!(update_state.treasury_transfers.len(), 0);
+    }
+
+    fn ratio(numerator: u64, denominator: u64) -> Ratio {
+        Ratio {
+            numerator,
+            denominator: NonZeroU64::new(denominator).unwrap(),
+        }
+    }
+
+    fn tally(approve: u64, reject: u64, total: u64) -> StakeVoteTally {
+        StakeVoteTally {
+            approve: Stake(approve),
+            reject: Stake(reject),
+            total: Stake(total),
+        }
+    }
+
+    #[test]
+    pub fn stake_vote_tally_checks_quorum_and_threshold() {
+        assert!(tally(30, 20, 100).quorum_reached(&ratio(1, 2)));
+        assert!(!tally(30, 19, 100).quorum_reached(&ratio(1, 2)));
+        assert!(!tally(0, 0, 0).quorum_reached(&ratio(0, 1)));
+
+        assert!(tally(51, 49, 100).approved(None));
+        assert!(!tally(50, 50, 100).approved(None));
+        assert!(tally(67, 33, 100).approved(Some(&ratio(2, 3))));
+        assert!(!tally(66, 34, 100).approved(Some(&ratio(2, 3))));
+
+        let mut settings = Settings::new();
+        assert!(!tally(100, 0, 100).accepted(&settings));
+        settings.stake_voting_quorum = Some(ratio(1, 2));
+        assert!(tally(40, 20, 100).accepted(&settings));
+        assert!(!tally(40, 0, 100).accepted(&settings));
+        settings.stake_voting_threshold = Some(ratio(3, 4));
+        assert!(!tally(40, 20, 100).accepted(&settings));
+    }
+
+    #[test]
+    pub fn apply_pool_vote_is_checked() {
+        let owner = AddressData::account(Discrimination::Test);
+        let stake_pool = StakePoolBuilder::new()
+            .with_owners(vec![owner.public_key()])
+            .build();
+        let leaders = TestGen::leaders_pairs()
+            .take(1)
+            .collect::<Vec<LeaderPair>>();
+        let mut settings = TestGen::settings(leaders.clone());
+        let proposal_id = TestGen::hash();
+        let update_state = apply_update_proposal(
+            UpdateState::new(),
+            proposal_id,
+            &ConfigParam::SlotsPerEpoch(100),
+            &leaders[0],
+            &settings,
+            BlockDate::first(),
+        )
+        .expect("failed while applying proposal");
+        let vote = build_pool_vote(proposal_id, stake_pool.id(), true, &[owner.private_key()]);
+
+        // stake voting is disabled
+        assert_eq!(
+            update_state
+                .clone()
+                .apply_pool_vote(&vote, &stake_pool.info(), &settings),
+            Err(Error::StakeVotingDisabled(proposal_id))
+        );
+
+        settings.stake_voting_quorum = Some(ratio(1, 2));
+
+        // vote not signed by the pool owners
+        let bad_vote = build_pool_vote(
+            proposal_id,
+            stake_pool.id(),
+            true,
+            &[AddressData::account(Discrimination::Test).private_key()],
+        );
+        assert_eq!(
+            update_state
+                .clone()
+                .apply_pool_vote(&bad_vote, &stake_pool.info(), &settings),
+            Err(Error::BadPoolVoteSignature(proposal_id, stake_pool.id()))
+        );
+
+        let update_state = update_state
+            .apply_pool_vote(&vote, &stake_pool.info(), &settings)
+            .expect("failed while applying pool vote");
+        assert!(update_state.has_pool_votes());
+        assert_eq!(
+            update_state.apply_pool_vote(&vote, &stake_pool.info(), &settings),
+            Err(Error::DuplicatePoolVote(proposal_id, stake_pool.id()))
+        );
+    }
+
+    #[test]
+    pub fn apply_treasury_transfer_proposal_with_unknown_proposer_should_return_error() {
+        let unknown_leader = TestGen::leader_pair();
+        let settings = TestGen::settings(TestGen::leaders_pairs().take(3).collect());
+
+        assert!(apply_treasury_transfer_proposal(
+            UpdateState::new(),
+            TestGen::hash(),
+            &unknown_leader,
+            &settings,
+            BlockDate::first(),
+        )
+        .is_err());
     }
 
     #[test]
@@ -752,7 +1423,7 @@ mod tests {
 
         let last_proposal_id = update_state.proposals.keys().cloned().last().unwrap();
 
-        let (update_state, settings) = update_state
+        let (update_state, settings, _) = update_state
             .process_proposals(settings, block_date, block_date.next_epoch())
             .expect("error while processing proposal");
 
@@ -832,7 +1503,7 @@ mod tests {
         // if proposal expiration period is not exceeded after that
         // proposal should be removed from proposal collection
         for _i in 0..expiry_block_data.get_last_epoch() {
-            let (update_state, _settings) = update_state
+            let (update_state, _settings, _) = update_state
                 .clone()
                 .process_proposals(
                     settings.clone(),
@@ -3,6 +3,11 @@
 //! The UTXO works similarly to cash where the demoninations are of arbitrary values,
 //! and each demonination get permanantly consumed by the system once spent.
 //!
+//! The ledger can optionally maintain an index from the output addresses to
+//! the unspent outputs, for wallets and explorers to find the UTXOs of an
+//! address without scanning the whole ledger. The index is disabled by
+//! default, so nodes that don't need it don't pay for it.
+//!
 
 use crate::fragment::FragmentId;
 use crate::transaction::{Output, TransactionIndex};
@@ -10,9 +15,10 @@ use chain_addr::Address;
 use sparse_array::{FastSparseArray, FastSparseArrayBuilder, FastSparseArrayIter};
 use std::collections::hash_map::DefaultHasher;
 use std::fmt;
+use std::hash::Hash;
 use thiserror::Error;
 
-use imhamt::{Hamt, HamtIter, InsertError, RemoveError, ReplaceError, UpdateError};
+use imhamt::{Diff, Hamt, HamtIter, InsertError, RemoveError, ReplaceError, UpdateError};
 
 #[derive(Debug, Error, Clone, PartialEq, Eq)]
 pub enum Error {
@@ -75,9 +81,24 @@ impl<OutAddress: Clone> TransactionUnspents<OutAddress> {
     }
 }
 
+type AddressUnspents = Hamt<DefaultHasher, (FragmentId, TransactionIndex), ()>;
+
 /// Ledger of UTXO
-#[derive(Clone, PartialEq, Eq)]
-pub struct Ledger<OutAddress>(Hamt<DefaultHasher, FragmentId, TransactionUnspents<OutAddress>>);
+#[derive(Clone)]
+pub struct Ledger<OutAddress: Hash + Eq> {
+    unspents: Hamt<DefaultHasher, FragmentId, TransactionUnspents<OutAddress>>,
+    address_index: Option<Hamt<DefaultHasher, OutAddress, AddressUnspents>>,
+}
+
+/// The address index is a cache of the unspents, two ledgers are equal if
+/// they have the same unspents whether they are indexed or not.
+impl<OutAddress: Hash + Eq> PartialEq for Ledger<OutAddress> {
+    fn eq(&self, other: &Self) -> bool {
+        self.unspents == other.unspents
+    }
+}
+
+impl<OutAddress: Hash + Eq> Eq for Ledger<OutAddress> {}
 
 pub struct Iter<'a, V> {
     hamt_iter: HamtIter<'a, FragmentId, TransactionUnspents<V>>,
@@ -113,17 +134,17 @@ pub struct EntryOwned<OutputAddress> {
     pub output: Output<OutputAddress>,
 }
 
-impl<OutAddress> Ledger<OutAddress> {
+impl<OutAddress: Hash + Eq> Ledger<OutAddress> {
     pub fn iter<'a>(&'a self) -> Iter<'a, OutAddress> {
         Iter {
-            hamt_iter: self.0.iter(),
+            hamt_iter: self.unspents.iter(),
             unspents_iter: None,
         }
     }
 
     pub fn values<'a>(&'a self) -> Values<'a, OutAddress> {
         Values {
-            hamt_iter: self.0.iter(),
+            hamt_iter: self.unspents.iter(),
             unspents_iter: None,
         }
     }
@@ -133,7 +154,7 @@ impl<OutAddress> Ledger<OutAddress> {
         tid: &FragmentId,
         index: &TransactionIndex,
     ) -> Option<Entry<'a, OutAddress>> {
-        self.0
+        self.unspents
             .lookup(tid)
             .and_then(|unspent| unspent.0.get(*index))
             .map(|output| Entry {
@@ -144,7 +165,12 @@ impl<OutAddress> Ledger<OutAddress> {
     }
 
     pub fn is_empty(&self) -> bool {
-        self.0.is_empty()
+        self.unspents.is_empty()
+    }
+
+    /// Check if the ledger maintains the index of the unspents by address
+    pub fn has_address_index(&self) -> bool {
+        self.address_index.is_some()
     }
 }
 
@@ -192,10 +218,102 @@ impl<'a, V> Iterator for Iter<'a, V> {
     }
 }
 
-impl<OutAddress: Clone> Ledger<OutAddress> {
+impl<OutAddress: Hash + Eq> Ledger<OutAddress> {
+    /// Compute the outputs added and removed between this ledger and a newer
+    /// one, returned as `(added, removed)`.
+    ///
+    /// Only the transactions that differ between both ledgers are visited.
+    pub fn diff<'a>(
+        &'a self,
+        new: &'a Self,
+    ) -> (Vec<Entry<'a, OutAddress>>, Vec<Entry<'a, OutAddress>>) {
+        let mut added = Vec::new();
+        let mut removed = Vec::new();
+        let entry = |id: &FragmentId, (index, output): (u8, &'a Output<OutAddress>)| Entry {
+            fragment_id: id.clone(),
+            output_index: index,
+            output,
+        };
+        for diff in self.unspents.diff(&new.unspents) {
+            match diff {
+                Diff::Added(id, unspents) => {
+                    added.extend(unspents.0.iter().map(|o| entry(id, o)));
+                }
+                Diff::Removed(id, unspents) => {
+                    removed.extend(unspents.0.iter().map(|o| entry(id, o)));
+                }
+                Diff::Changed(id, old, new) => {
+                    added.extend(
+                        new.0
+                            .iter()
+                            .filter(|(i, o)| old.0.get(*i) != Some(*o))
+                            .map(|o| entry(id, o)),
+                    );
+                    removed.extend(
+                        old.0
+                            .iter()
+                            .filter(|(i, o)| new.0.get(*i) != Some(*o))
+                            .map(|o| entry(id, o)),
+                    );
+                }
+            }
+        }
+        (added, removed)
+    }
+}
+
+impl<OutAddress: Clone + Hash + Eq> Ledger<OutAddress> {
     /// Create a new empty UTXO Ledger
     pub fn new() -> Self {
-        Ledger(Hamt::new())
+        Ledger {
+            unspents: Hamt::new(),
+            address_index: None,
+        }
+    }
+
+    /// Maintain an index of the unspents by address, built from the current
+    /// unspents if the ledger is not indexed yet
+    pub fn with_address_index(&self) -> Self {
+        if self.has_address_index() {
+            return self.clone();
+        }
+        let mut address_index = Hamt::new();
+        for entry in self.iter() {
+            address_index = index_add(
+                &address_index,
+                &entry.output.address,
+                (entry.fragment_id, entry.output_index),
+            );
+        }
+        Ledger {
+            unspents: self.unspents.clone(),
+            address_index: Some(address_index),
+        }
+    }
+
+    /// Stop maintaining the index of the unspents by address
+    pub fn without_address_index(&self) -> Self {
+        Ledger {
+            unspents: self.unspents.clone(),
+            address_index: None,
+        }
+    }
+
+    /// Get all the unspents of an address, from the index.
+    ///
+    /// Returns `None` if the ledger is not indexed, see `with_address_index`.
+    pub fn get_by_address<'a>(
+        &'a self,
+        address: &OutAddress,
+    ) -> Option<Vec<Entry<'a, OutAddress>>> {
+        let address_index = self.address_index.as_ref()?;
+        Some(match address_index.lookup(address) {
+            None => Vec::new(),
+            Some(unspents) => unspents
+                .iter()
+                .filter_map(|((tid, index), ())| self.get(tid, index))
+                .collect(),
+        })
     }
 
     /// Add new outputs associated with a specific transaction
@@ -209,8 +327,41 @@ impl<OutAddress: Clone> Ledger<OutAddress> {
         assert!(outs.len() > 0);
         assert!(outs.len() < 255);
         let b = TransactionUnspents::from_outputs(outs);
-        let next = self.0.insert(tid.clone(), b)?;
-        Ok(Ledger(next))
+        let unspents = self.unspents.insert(tid.clone(), b)?;
+        let address_index = self.address_index.as_ref().map(|address_index| {
+            outs.iter()
+                .fold(address_index.clone(), |address_index, (index, output)| {
+                    index_add(&address_index, &output.address, (*tid, *index))
+                })
+        });
+        Ok(Ledger {
+            unspents,
+            address_index,
+        })
+    }
+
+    fn remove_unspents(
+        &self,
+        tid: &FragmentId,
+        treemap: TransactionUnspents<OutAddress>,
+        removed: &[(TransactionIndex, &Output<OutAddress>)],
+    ) -> Result<Self, Error> {
+        let unspents = if treemap.0.is_empty() {
+            self.unspents.remove(tid)?
+        } else {
+            self.unspents.replace(tid, treemap)?.0
+        };
+        let address_index = self.address_index.as_ref().map(|address_index| {
+            removed
+                .iter()
+                .fold(address_index.clone(), |address_index, (index, output)| {
+                    index_remove(&address_index, &output.address, &(*tid, *index))
+                })
+        });
+        Ok(Ledger {
+            unspents,
+            address_index,
+        })
     }
 
     /// Spend a specific index from the transaction
@@ -220,16 +371,13 @@ impl<OutAddress: Clone> Ledger<OutAddress> {
         tid: &FragmentId,
         index: TransactionIndex,
     ) -> Result<(Self, Output<OutAddress>), Error> {
-        let (treemap, output) = match self.0.lookup(tid) {
+        let (treemap, output) = match self.unspents.lookup(tid) {
             None => Err(Error::TransactionNotFound),
             Some(out) => out.remove_input(index),
         }?;
 
-        if treemap.0.is_empty() {
-            Ok((Ledger(self.0.remove(tid)?), output))
-        } else {
-            Ok((Ledger(self.0.replace(tid, treemap)?.0), output))
-        }
+        let ledger = self.remove_unspents(tid, treemap, &[(index, &output)])?;
+        Ok((ledger, output))
     }
 
     pub fn remove_multiple(
@@ -237,7 +385,7 @@ impl<OutAddress: Clone> Ledger<OutAddress> {
         tid: &FragmentId,
         indices: &[TransactionIndex],
     ) -> Result<(Self, Vec<Output<OutAddress>>), Error> {
-        let (treemap, outputs) = match self.0.lookup(tid) {
+        let (treemap, outputs) = match self.unspents.lookup(tid) {
             None => Err(Error::TransactionNotFound),
             Some(out) => {
                 let mut treemap = out.clone();
@@ -251,15 +399,47 @@ impl<OutAddress: Clone> Ledger<OutAddress> {
             }
         }?;
 
-        if treemap.0.is_empty() {
-            Ok((Ledger(self.0.remove(tid)?), outputs))
-        } else {
-            Ok((Ledger(self.0.replace(tid, treemap)?.0), outputs))
-        }
+        let removed: Vec<_> = indices.iter().copied().zip(outputs.iter()).collect();
+        let ledger = self.remove_unspents(tid, treemap, &removed)?;
+        Ok((ledger, outputs))
     }
 }
 
-impl<OutAddress: Clone>
+fn index_add<OutAddress: Clone + Hash + Eq>(
+    address_index: &Hamt<DefaultHasher, OutAddress, AddressUnspents>,
+    address: &OutAddress,
+    pointer: (FragmentId, TransactionIndex),
+) -> Hamt<DefaultHasher, OutAddress, AddressUnspents> {
+    let unspents = Hamt::new().insert(pointer, ()).unwrap();
+    address_index.insert_or_update_simple(address.clone(), unspents, |unspents| {
+        Some(
+            unspents
+                .insert(pointer, ())
+                .unwrap_or_else(|_| unspents.clone()),
+        )
+    })
+}
+
+fn index_remove<OutAddress: Clone + Hash + Eq>(
+    address_index: &Hamt<DefaultHasher, OutAddress, AddressUnspents>,
+    address: &OutAddress,
+    pointer: &(FragmentId, TransactionIndex),
+) -> Hamt<DefaultHasher, OutAddress, AddressUnspents> {
+    address_index
+        .update(address, |unspents| {
+            let unspents = unspents
+                .remove(pointer)
+                .unwrap_or_else(|_| unspents.clone());
+            Ok::<_, ()>(if unspents.is_empty() {
+                None
+            } else {
+                Some(unspents)
+            })
+        })
+        .unwrap_or_else(|_| address_index.clone())
+}
+
+impl<OutAddress: Clone + Hash + Eq>
     std::iter::FromIterator<(FragmentId, Vec<(TransactionIndex, Output<OutAddress>)>)>
     for Ledger<OutAddress>
 {
@@ -419,6 +599,61 @@ mod tests {
         TestResult::passed()
     }
 
+    fn address_index_matches_unspents(ledger: &Ledger<Address>) -> bool {
+        ledger.iter().all(|entry| {
+            let mut indexed = ledger.get_by_address(&entry.output.address).unwrap();
+            let mut scanned: Vec<_> = ledger
+                .iter()
+                .filter(|other| other.output.address == entry.output.address)
+                .collect();
+            indexed.sort_by_key(|e| (e.fragment_id, e.output_index));
+            scanned.sort_by_key(|e| (e.fragment_id, e.output_index));
+            indexed == scanned
+        })
+    }
+
+    #[quickcheck]
+    pub fn address_index_follows_unspents(initial_utxos: ArbitraryUtxos) -> TestResult {
+        let mut ledger = Ledger::new().with_address_index();
+        ledger = initial_utxos.fill(ledger);
+        if !address_index_matches_unspents(&ledger) {
+            return TestResult::error("index differs after adding outputs");
+        }
+        if !address_index_matches_unspents(&ledger.without_address_index().with_address_index()) {
+            return TestResult::error("index differs when built from the unspents");
+        }
+
+        for (key, value) in initial_utxos.0.iter() {
+            if value.utxos.contains_key(&value.idx_to_remove) {
+                let (next, output) = ledger.remove(key, value.idx_to_remove).unwrap();
+                ledger = next;
+                let still_indexed = ledger
+                    .get_by_address(&output.address)
+                    .unwrap()
+                    .iter()
+                    .any(|e| e.fragment_id == *key && e.output_index == value.idx_to_remove);
+                if still_indexed {
+                    return TestResult::error("removed output is still indexed");
+                }
+            }
+        }
+        TestResult::from_bool(address_index_matches_unspents(&ledger))
+    }
+
+    #[test]
+    pub fn address_index_is_disabled_by_default() {
+        let output = AddressData::utxo(Discrimination::Test).make_output(&Value(100));
+        let ledger = Ledger::new()
+            .add(&TestGen::hash(), &[(0, output.clone())])
+            .unwrap();
+        assert!(!ledger.has_address_index());
+        assert!(ledger.get_by_address(&output.address).is_none());
+
+        let indexed = ledger.with_address_index();
+        assert_eq!(indexed.get_by_address(&output.address).unwrap().len(), 1);
+        assert!(indexed == ledger);
+    }
+
     #[test]
     pub fn remove_outputs_from_ledger() {
         let mut ledger = Ledger::new();
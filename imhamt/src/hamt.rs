@@ -1,9 +1,9 @@
 use super::hash::{Hash, HashedKey, Hasher};
 use super::node::{
-    insert_rec, lookup_one, remove_eq_rec, remove_rec, replace_rec, replace_with_rec, size_rec,
-    update_rec, Entry, LookupRet, Node, NodeIter,
+    diff_rec, insert_rec, lookup_one, remove_eq_rec, remove_rec, replace_rec, replace_with_rec,
+    size_rec, update_rec, Entry, LookupRet, Node, NodeIter,
 };
-pub use super::operation::{InsertError, RemoveError, ReplaceError, UpdateError};
+pub use super::operation::{Diff, InsertError, RemoveError, ReplaceError, UpdateError};
 use std::iter::FromIterator;
 use std::marker::PhantomData;
 use std::mem::swap;
@@ -198,6 +198,21 @@ impl<H: Hasher + Default, K: Hash + Eq, V> Hamt<H, K, V> {
     }
 }
 
+impl<H: Hasher + Default, K: Hash + Eq, V: PartialEq> Hamt<H, K, V> {
+    /// Compute the differences between this HAMT and a newer one: the keys
+    /// only present in `new` are added, the keys only present in `self` are
+    /// removed, and the keys with a different value are changed.
+    ///
+    /// The subtrees shared by both HAMTs are skipped without being visited,
+    /// so comparing a HAMT with a version derived from it is proportional to
+    /// the number of changes, not to the size of the HAMT.
+    pub fn diff<'a>(&'a self, new: &'a Self) -> Vec<Diff<'a, K, V>> {
+        let mut diffs = Vec::new();
+        diff_rec(&self.root, &new.root, &mut diffs);
+        diffs
+    }
+}
+
 impl<'a, K, V> Iterator for HamtIter<'a, K, V> {
     type Item = (&'a K, &'a V);
 
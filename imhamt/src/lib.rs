@@ -438,6 +438,57 @@ mod tests {
         let after_iter = BTreeMap::from_iter(h.iter().map(|(k, v)| (k.clone(), v.clone())));
         reference == after_iter
     }
+
+    #[quickcheck]
+    fn diff_equivalent(xs: Vec<(u16, u32)>, changes: Vec<(u16, Option<u32>)>) -> bool {
+        let old: Hamt<DefaultHasher, u16, u32> = xs.into_iter().collect();
+        let old_reference: BTreeMap<u16, u32> = old.iter().map(|(k, v)| (*k, *v)).collect();
+
+        let mut new = old.clone();
+        for (k, v) in changes {
+            new = match v {
+                None => new.remove(&k).unwrap_or(new),
+                Some(v) => new.insert_or_update_simple(k, v, |_| Some(v)),
+            }
+        }
+        let new_reference: BTreeMap<u16, u32> = new.iter().map(|(k, v)| (*k, *v)).collect();
+
+        let mut expected = BTreeMap::new();
+        for (k, v) in old_reference.iter() {
+            match new_reference.get(k) {
+                None => {
+                    expected.insert(*k, Diff::Removed(k, v));
+                }
+                Some(nv) if nv != v => {
+                    expected.insert(*k, Diff::Changed(k, v, nv));
+                }
+                Some(_) => {}
+            }
+        }
+        for (k, v) in new_reference.iter() {
+            if !old_reference.contains_key(k) {
+                expected.insert(*k, Diff::Added(k, v));
+            }
+        }
+
+        let diffs = old.diff(&new);
+        let got: BTreeMap<u16, Diff<u16, u32>> = diffs
+            .iter()
+            .map(|d| match d {
+                Diff::Added(k, _) | Diff::Removed(k, _) | Diff::Changed(k, _, _) => (**k, *d),
+            })
+            .collect();
+        diffs.len() == expected.len() && got == expected
+    }
+
+    #[test]
+    fn diff_same_is_empty() {
+        let h: Hamt<DefaultHasher, u32, u32> = (0..10_000).map(|i| (i, i)).collect();
+        let h2 = h.clone();
+        assert!(h.diff(&h2).is_empty());
+        let h3 = h2.replace(&42, 0).unwrap().0;
+        assert_eq!(h.diff(&h3), vec![Diff::Changed(&42, &42, &0)]);
+    }
 }
 
 #[cfg(test)]
@@ -1,5 +1,5 @@
 use super::super::bitmap::{ArrayIndex, SmallBitmap};
-use super::super::hash::{HashedKey, LevelIndex};
+use super::super::hash::{HashedKey, LevelIndex, SIZE};
 use super::super::helper;
 use super::super::operation::*;
 use super::super::sharedref::SharedRef;
@@ -538,6 +538,85 @@ where
     }
 }
 
+fn entry_items<'a, K, V>(entry: &'a Entry<K, V>, items: &mut Vec<(&'a K, &'a V)>) {
+    match entry {
+        Entry::Leaf(_, k, v) => items.push((k, v)),
+        Entry::LeafMany(_, col) => items.extend(col.iter().map(|(k, v)| (k, v))),
+        Entry::SubNode(sub) => {
+            for c in sub.children.iter() {
+                entry_items(c, items)
+            }
+        }
+    }
+}
+
+// compare 2 entries which are not both subnodes, by collecting their content.
+// one of them is a leaf or a collision, so this is cheap unless the other one
+// is a big subnode.
+fn diff_entries<'a, K: PartialEq, V: PartialEq>(
+    old: &'a Entry<K, V>,
+    new: &'a Entry<K, V>,
+    diffs: &mut Vec<Diff<'a, K, V>>,
+) {
+    let mut old_items = Vec::new();
+    let mut new_items = Vec::new();
+    entry_items(old, &mut old_items);
+    entry_items(new, &mut new_items);
+
+    for (k, v) in old_items.iter() {
+        match new_items.iter().find(|(nk, _)| nk == k) {
+            None => diffs.push(Diff::Removed(k, v)),
+            Some((_, nv)) if nv != v => diffs.push(Diff::Changed(k, v, nv)),
+            Some(_) => {}
+        }
+    }
+    for (k, v) in new_items.iter() {
+        if !old_items.iter().any(|(ok, _)| ok == k) {
+            diffs.push(Diff::Added(k, v))
+        }
+    }
+}
+
+// recursively compare 2 nodes at the same level, skipping the children
+// shared by both nodes.
+pub fn diff_rec<'a, K: PartialEq, V: PartialEq>(
+    old: &'a Node<K, V>,
+    new: &'a Node<K, V>,
+    diffs: &mut Vec<Diff<'a, K, V>>,
+) {
+    for i in 0..SIZE {
+        let level_hash = LevelIndex(i);
+        let old_idx = old.bitmap.get_index_sparse(level_hash);
+        let new_idx = new.bitmap.get_index_sparse(level_hash);
+        match (old_idx.is_not_found(), new_idx.is_not_found()) {
+            (true, true) => {}
+            (false, true) => {
+                let mut items = Vec::new();
+                entry_items(old.get_child(old_idx), &mut items);
+                diffs.extend(items.into_iter().map(|(k, v)| Diff::Removed(k, v)))
+            }
+            (true, false) => {
+                let mut items = Vec::new();
+                entry_items(new.get_child(new_idx), &mut items);
+                diffs.extend(items.into_iter().map(|(k, v)| Diff::Added(k, v)))
+            }
+            (false, false) => {
+                let old_child = old.get_child(old_idx);
+                let new_child = new.get_child(new_idx);
+                if SharedRef::ptr_eq(old_child, new_child) {
+                    continue;
+                }
+                match (old_child.as_ref(), new_child.as_ref()) {
+                    (Entry::SubNode(old_sub), Entry::SubNode(new_sub)) => {
+                        diff_rec(old_sub, new_sub, diffs)
+                    }
+                    (old_entry, new_entry) => diff_entries(old_entry, new_entry, diffs),
+                }
+            }
+        }
+    }
+}
+
 pub fn size_rec<K, V>(node: &Node<K, V>) -> usize {
     let mut sum = 0;
     for c in node.children.iter() {
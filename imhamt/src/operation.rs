@@ -19,3 +19,14 @@ pub enum UpdateError<T> {
 pub enum ReplaceError {
     KeyNotFound,
 }
+
+/// A difference between two HAMTs, as found by `Hamt::diff`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Diff<'a, K, V> {
+    /// The key is only present in the new HAMT
+    Added(&'a K, &'a V),
+    /// The key is only present in the old HAMT
+    Removed(&'a K, &'a V),
+    /// The key is present in both HAMTs, with the old and new values
+    Changed(&'a K, &'a V, &'a V),
+}